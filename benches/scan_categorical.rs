@@ -4,24 +4,21 @@ extern crate hedgecut;
 
 use bencher::Bencher;
 
-use hedgecut::dataset::ShoppingDataset;
-use hedgecut::tree::Split;
+use hedgecut::dataset::{AdultDataset, ShoppingDataset};
+use hedgecut::scan::ScanKernel;
+use hedgecut::tree::{CategorySet, Split};
 
-benchmark_group!(benches, bench_scan_with_branches, bench_scan_mlpack, bench_scan, bench_scan_simd);
+benchmark_group!(benches, bench_scan_with_branches, bench_scan_mlpack, bench_scan, bench_scan_simd, bench_scan_sse2,
+    bench_scan_high_cardinality, bench_scan_simd_high_cardinality);
 benchmark_main!(benches);
 
 
 fn bench_scan_with_branches(bench: &mut Bencher) {
 
     let samples = ShoppingDataset::samples_from_csv("datasets/shopping-train.csv");
-    let possible_values = vec![0, 7, 12];
+    let subset: CategorySet = vec![0, 7, 12].into_iter().collect();
 
-    let mut subset: u64 = 0;
-    for bit_to_set in possible_values.iter() {
-        subset |= 1_u64 << *bit_to_set as u64
-    }
-
-    let split = Split::Categorical { attribute_index: 14, subset };
+    let split = Split::Categorical { attribute_index: 14, subset, missing_left: false };
 
     bench.iter(|| {
         bencher::black_box(hedgecut::scan::scan_with_branches(&samples, &split));
@@ -31,14 +28,9 @@ fn bench_scan_with_branches(bench: &mut Bencher) {
 fn bench_scan_mlpack(bench: &mut Bencher) {
 
     let samples = ShoppingDataset::samples_from_csv("datasets/shopping-train.csv");
-    let possible_values = vec![0, 7, 12];
-
-    let mut subset: u64 = 0;
-    for bit_to_set in possible_values.iter() {
-        subset |= 1_u64 << *bit_to_set as u64
-    }
+    let subset: CategorySet = vec![0, 7, 12].into_iter().collect();
 
-    let split = Split::Categorical { attribute_index: 14, subset };
+    let split = Split::Categorical { attribute_index: 14, subset, missing_left: false };
 
     bench.iter(|| {
         bencher::black_box(hedgecut::scan::scan_mlpack(&samples, &split));
@@ -48,14 +40,9 @@ fn bench_scan_mlpack(bench: &mut Bencher) {
 fn bench_scan(bench: &mut Bencher) {
 
     let samples = ShoppingDataset::samples_from_csv("datasets/shopping-train.csv");
-    let possible_values = vec![0, 7, 12];
+    let subset: CategorySet = vec![0, 7, 12].into_iter().collect();
 
-    let mut subset: u64 = 0;
-    for bit_to_set in possible_values.iter() {
-        subset |= 1_u64 << *bit_to_set as u64
-    }
-
-    let split = Split::Categorical { attribute_index: 14, subset };
+    let split = Split::Categorical { attribute_index: 14, subset, missing_left: false };
 
     bench.iter(|| {
         bencher::black_box(hedgecut::scan::scan(&samples, &split));
@@ -65,16 +52,51 @@ fn bench_scan(bench: &mut Bencher) {
 fn bench_scan_simd(bench: &mut Bencher) {
 
     let samples = ShoppingDataset::samples_from_csv("datasets/shopping-train.csv");
-    let possible_values = vec![0, 7, 12];
-
-    let mut subset: u64 = 0;
-    for bit_to_set in possible_values.iter() {
-        subset |= 1_u64 << *bit_to_set as u64
-    }
+    let subset: CategorySet = vec![0, 7, 12].into_iter().collect();
 
-    let split = Split::Categorical { attribute_index: 14, subset };
+    let split = Split::Categorical { attribute_index: 14, subset, missing_left: false };
 
     bench.iter(|| {
         bencher::black_box(hedgecut::scan::scan_simd_categorical(&samples, &split));
     })
 }
+
+fn bench_scan_sse2(bench: &mut Bencher) {
+
+    let samples = ShoppingDataset::samples_from_csv("datasets/shopping-train.csv");
+    let subset: CategorySet = vec![0, 7, 12].into_iter().collect();
+
+    let split = Split::Categorical { attribute_index: 14, subset, missing_left: false };
+
+    bench.iter(|| {
+        bencher::black_box(hedgecut::scan::scan_categorical_with(ScanKernel::Sse2, &samples, &split));
+    })
+}
+
+// native_country of the adult dataset has 41 different values
+fn native_country_split() -> Split {
+    let subset: CategorySet = vec![0, 3, 17, 33, 38, 40].into_iter().collect();
+
+    Split::Categorical { attribute_index: 11, subset, missing_left: false }
+}
+
+fn bench_scan_high_cardinality(bench: &mut Bencher) {
+
+    let samples = AdultDataset::samples_from_csv("datasets/adult-train.csv");
+    let split = native_country_split();
+
+    bench.iter(|| {
+        bencher::black_box(hedgecut::scan::scan(&samples, &split));
+    })
+}
+
+fn bench_scan_simd_high_cardinality(bench: &mut Bencher) {
+
+    let samples = AdultDataset::samples_from_csv("datasets/adult-train.csv");
+    let split = native_country_split();
+    let kernel = ScanKernel::detect().for_categories(40);
+
+    bench.iter(|| {
+        bencher::black_box(hedgecut::scan::scan_categorical_with(kernel, &samples, &split));
+    })
+}
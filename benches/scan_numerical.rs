@@ -5,15 +5,16 @@ extern crate hedgecut;
 use bencher::Bencher;
 
 use hedgecut::dataset::GiveMeSomeCreditDataset;
+use hedgecut::scan::ScanKernel;
 use hedgecut::tree::Split;
 
-benchmark_group!(benches, bench_scan_with_branches, bench_scan_mlpack, bench_scan, bench_scan_simd);
+benchmark_group!(benches, bench_scan_with_branches, bench_scan_mlpack, bench_scan, bench_scan_simd, bench_scan_sse2);
 benchmark_main!(benches);
 
 fn bench_scan_with_branches(bench: &mut Bencher) {
 
     let samples = GiveMeSomeCreditDataset::samples_from_csv("datasets/givemesomecredit-train.csv");
-    let split = Split::Numerical { attribute_index: 3, cut_off: 11 };
+    let split = Split::Numerical { attribute_index: 3, cut_off: 11, missing_left: false };
 
     bench.iter(|| {
         bencher::black_box(hedgecut::scan::scan_with_branches(&samples, &split));
@@ -23,7 +24,7 @@ fn bench_scan_with_branches(bench: &mut Bencher) {
 fn bench_scan_mlpack(bench: &mut Bencher) {
 
     let samples = GiveMeSomeCreditDataset::samples_from_csv("datasets/givemesomecredit-train.csv");
-    let split = Split::Numerical { attribute_index: 3, cut_off: 11 };
+    let split = Split::Numerical { attribute_index: 3, cut_off: 11, missing_left: false };
 
     bench.iter(|| {
         bencher::black_box(hedgecut::scan::scan_mlpack(&samples, &split));
@@ -33,7 +34,7 @@ fn bench_scan_mlpack(bench: &mut Bencher) {
 fn bench_scan(bench: &mut Bencher) {
 
     let samples = GiveMeSomeCreditDataset::samples_from_csv("datasets/givemesomecredit-train.csv");
-    let split = Split::Numerical { attribute_index: 3, cut_off: 11 };
+    let split = Split::Numerical { attribute_index: 3, cut_off: 11, missing_left: false };
 
     bench.iter(|| {
         bencher::black_box(hedgecut::scan::scan(&samples, &split));
@@ -43,9 +44,19 @@ fn bench_scan(bench: &mut Bencher) {
 fn bench_scan_simd(bench: &mut Bencher) {
 
     let samples = GiveMeSomeCreditDataset::samples_from_csv("datasets/givemesomecredit-train.csv");
-    let split = Split::Numerical { attribute_index: 3, cut_off: 11 };
+    let split = Split::Numerical { attribute_index: 3, cut_off: 11, missing_left: false };
 
     bench.iter(|| {
         bencher::black_box(hedgecut::scan::scan_simd_numerical(&samples, &split));
     })
 }
+
+fn bench_scan_sse2(bench: &mut Bencher) {
+
+    let samples = GiveMeSomeCreditDataset::samples_from_csv("datasets/givemesomecredit-train.csv");
+    let split = Split::Numerical { attribute_index: 3, cut_off: 11, missing_left: false };
+
+    bench.iter(|| {
+        bencher::black_box(hedgecut::scan::scan_numerical_with(ScanKernel::Sse2, &samples, &split));
+    })
+}
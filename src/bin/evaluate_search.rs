@@ -1,6 +1,6 @@
 extern crate hedgecut;
 
-use hedgecut::split_stats::SplitStats;
+use hedgecut::split_stats::{SplitCriterion, SplitStats, MINUS, PLUS};
 use hedgecut::split_stats::is_robust;
 
 use rand::Rng;
@@ -46,8 +46,8 @@ fn main() {
             let mut s = SplitStats::new(num_plus_left_s, num_minus_left_s, num_plus_right_s, num_minus_right_s);
             let mut t = SplitStats::new(num_plus_left_t, num_minus_left_t, num_plus_right_t, num_minus_right_t);
 
-            s.update_score_and_impurity_before();
-            t.update_score_and_impurity_before();
+            s.update_score_and_impurity_before(SplitCriterion::Gini);
+            t.update_score_and_impurity_before(SplitCriterion::Gini);
 
             //println!("S: {} {}, T: {} {}", s.fmt(), s.score as f64 / 1_000_000_000_000_f64, t.fmt(), t.score as f64 / 1_000_000_000_000_f64);
 
@@ -57,7 +57,7 @@ fn main() {
                     std::mem::swap(&mut s, &mut t);
                 }
 
-                let (split_robust, num_steps) = is_robust(&s, &t, *robustness as usize);
+                let (split_robust, num_steps) = is_robust(&s, &t, *robustness as usize, SplitCriterion::Gini);
 
                 let exhaustive_result = if *robustness == 2 {
                     exhaustive2(&mut s, &mut t)
@@ -135,11 +135,11 @@ fn main() {
 
 fn exhaustive7(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
-    s.update_score_and_impurity_before();
-    t.update_score_and_impurity_before();
+    s.update_score_and_impurity_before(SplitCriterion::Gini);
+    t.update_score_and_impurity_before(SplitCriterion::Gini);
 
 
-    let enumerated1 = enumerate_changes(&s, &t);
+    let enumerated1 = enumerate_changes(s, t);
 
     for (_step, s_hat, t_hat) in enumerated1 {
         let score_diff1 = s_hat.score.unwrap() - t_hat.score.unwrap();
@@ -216,11 +216,11 @@ fn exhaustive7(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
 fn exhaustive8(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
-    s.update_score_and_impurity_before();
-    t.update_score_and_impurity_before();
+    s.update_score_and_impurity_before(SplitCriterion::Gini);
+    t.update_score_and_impurity_before(SplitCriterion::Gini);
 
 
-    let enumerated1 = enumerate_changes(&s, &t);
+    let enumerated1 = enumerate_changes(s, t);
 
     for (_step, s_hat, t_hat) in enumerated1 {
         let score_diff1 = s_hat.score.unwrap() - t_hat.score.unwrap();
@@ -309,11 +309,11 @@ fn exhaustive8(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
 fn exhaustive6(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
-    s.update_score_and_impurity_before();
-    t.update_score_and_impurity_before();
+    s.update_score_and_impurity_before(SplitCriterion::Gini);
+    t.update_score_and_impurity_before(SplitCriterion::Gini);
 
 
-    let enumerated1 = enumerate_changes(&s, &t);
+    let enumerated1 = enumerate_changes(s, t);
 
     for (_step, s_hat, t_hat) in enumerated1 {
         let score_diff1 = s_hat.score.unwrap() - t_hat.score.unwrap();
@@ -382,8 +382,8 @@ fn exhaustive6(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
 fn exhaustive5(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
-    s.update_score_and_impurity_before();
-    t.update_score_and_impurity_before();
+    s.update_score_and_impurity_before(SplitCriterion::Gini);
+    t.update_score_and_impurity_before(SplitCriterion::Gini);
 
     //let mut diffs1 = Vec::new();
     //let mut diffs2 = Vec::new();
@@ -391,7 +391,7 @@ fn exhaustive5(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
     //let mut diffs4 = Vec::new();
     //let mut diffs5 = Vec::new();
 
-    let enumerated1 = enumerate_changes(&s, &t);
+    let enumerated1 = enumerate_changes(s, t);
 
     for (_step, s_hat, t_hat) in enumerated1 {
         let score_diff1 = s_hat.score.unwrap() - t_hat.score.unwrap();
@@ -455,10 +455,10 @@ fn exhaustive5(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
 fn exhaustive4(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
-    s.update_score_and_impurity_before();
-    t.update_score_and_impurity_before();
+    s.update_score_and_impurity_before(SplitCriterion::Gini);
+    t.update_score_and_impurity_before(SplitCriterion::Gini);
 
-    let enumerated1 = enumerate_changes(&s, &t);
+    let enumerated1 = enumerate_changes(s, t);
 
     for (_step, s_hat, t_hat) in enumerated1 {
         let score_diff1 = s_hat.score.unwrap() - t_hat.score.unwrap();
@@ -504,11 +504,11 @@ fn exhaustive4(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
 fn exhaustive2(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
-    s.update_score_and_impurity_before();
-    t.update_score_and_impurity_before();
+    s.update_score_and_impurity_before(SplitCriterion::Gini);
+    t.update_score_and_impurity_before(SplitCriterion::Gini);
 
 
-    let enumerated1 = enumerate_changes(&s, &t);
+    let enumerated1 = enumerate_changes(s, t);
 
     for (_step, s_hat, t_hat) in enumerated1 {
         let score_diff1 = s_hat.score.unwrap() - t_hat.score.unwrap();
@@ -533,11 +533,11 @@ fn exhaustive2(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
 fn exhaustive3(s: &mut SplitStats, t: &mut SplitStats) -> Option<usize> {
 
-    s.update_score_and_impurity_before();
-    t.update_score_and_impurity_before();
+    s.update_score_and_impurity_before(SplitCriterion::Gini);
+    t.update_score_and_impurity_before(SplitCriterion::Gini);
 
 
-    let enumerated1 = enumerate_changes(&s, &t);
+    let enumerated1 = enumerate_changes(s, t);
 
     for (_step, s_hat, t_hat) in enumerated1 {
         let score_diff1 = s_hat.score.unwrap() - t_hat.score.unwrap();
@@ -589,69 +589,69 @@ fn enumerate_changes(
     for is_plus in truefalse.iter() {
         for passes_first in truefalse.iter() {
             for passes_second in truefalse.iter() {
-                let mut champion_stats = current_champion_split_stats.clone();
-                let mut runnerup_stats = current_runnerup_split_stats.clone();
+                let mut champion_stats = *current_champion_split_stats;
+                let mut runnerup_stats = *current_runnerup_split_stats;
 
                 if *is_plus && *passes_first && *passes_second {
-                    if champion_stats.num_plus_left != 0 && runnerup_stats.num_plus_left != 0 {
-                        champion_stats.num_plus_left -= 1;
-                        runnerup_stats.num_plus_left -= 1;
+                    if champion_stats.num_plus_left() != 0 && runnerup_stats.num_plus_left() != 0 {
+                        champion_stats.side_mut(true).class_counts_mut().remove(PLUS);
+                        runnerup_stats.side_mut(true).class_counts_mut().remove(PLUS);
                     } else {
                         continue;
                     }
                 } else if *is_plus && !*passes_first && *passes_second {
-                    if champion_stats.num_plus_right != 0 && runnerup_stats.num_plus_left != 0 {
-                        champion_stats.num_plus_right -= 1;
-                        runnerup_stats.num_plus_left -= 1;
+                    if champion_stats.num_plus_right() != 0 && runnerup_stats.num_plus_left() != 0 {
+                        champion_stats.side_mut(false).class_counts_mut().remove(PLUS);
+                        runnerup_stats.side_mut(true).class_counts_mut().remove(PLUS);
                     } else {
                         continue;
                     }
                 } else if *is_plus && *passes_first && !*passes_second {
-                    if champion_stats.num_plus_left != 0 && runnerup_stats.num_plus_right != 0 {
-                        champion_stats.num_plus_left -= 1;
-                        runnerup_stats.num_plus_right -= 1;
+                    if champion_stats.num_plus_left() != 0 && runnerup_stats.num_plus_right() != 0 {
+                        champion_stats.side_mut(true).class_counts_mut().remove(PLUS);
+                        runnerup_stats.side_mut(false).class_counts_mut().remove(PLUS);
                     } else {
                         continue;
                     }
                 } else if *is_plus && !*passes_first && !*passes_second {
-                    if champion_stats.num_plus_right != 0 && runnerup_stats.num_plus_right != 0 {
-                        champion_stats.num_plus_right -= 1;
-                        runnerup_stats.num_plus_right -= 1;
+                    if champion_stats.num_plus_right() != 0 && runnerup_stats.num_plus_right() != 0 {
+                        champion_stats.side_mut(false).class_counts_mut().remove(PLUS);
+                        runnerup_stats.side_mut(false).class_counts_mut().remove(PLUS);
                     } else {
                         continue;
                     }
                 } else if !*is_plus && *passes_first && *passes_second {
-                    if champion_stats.num_minus_left != 0 && runnerup_stats.num_minus_left != 0 {
-                        champion_stats.num_minus_left -= 1;
-                        runnerup_stats.num_minus_left -= 1;
+                    if champion_stats.num_minus_left() != 0 && runnerup_stats.num_minus_left() != 0 {
+                        champion_stats.side_mut(true).class_counts_mut().remove(MINUS);
+                        runnerup_stats.side_mut(true).class_counts_mut().remove(MINUS);
                     } else {
                         continue;
                     }
                 } else if !*is_plus && !*passes_first && *passes_second {
-                    if champion_stats.num_minus_right != 0 && runnerup_stats.num_minus_left != 0 {
-                        champion_stats.num_minus_right -= 1;
-                        runnerup_stats.num_minus_left -= 1;
+                    if champion_stats.num_minus_right() != 0 && runnerup_stats.num_minus_left() != 0 {
+                        champion_stats.side_mut(false).class_counts_mut().remove(MINUS);
+                        runnerup_stats.side_mut(true).class_counts_mut().remove(MINUS);
                     } else {
                         continue;
                     }
                 } else if !*is_plus && *passes_first && !*passes_second {
-                    if champion_stats.num_minus_left != 0 && runnerup_stats.num_minus_right != 0 {
-                        champion_stats.num_minus_left -= 1;
-                        runnerup_stats.num_minus_right -= 1;
+                    if champion_stats.num_minus_left() != 0 && runnerup_stats.num_minus_right() != 0 {
+                        champion_stats.side_mut(true).class_counts_mut().remove(MINUS);
+                        runnerup_stats.side_mut(false).class_counts_mut().remove(MINUS);
                     } else {
                         continue;
                     }
                 } else if !*is_plus && !*passes_first && !*passes_second {
-                    if champion_stats.num_minus_right != 0 && runnerup_stats.num_minus_right != 0 {
-                        champion_stats.num_minus_right -= 1;
-                        runnerup_stats.num_minus_right -= 1;
+                    if champion_stats.num_minus_right() != 0 && runnerup_stats.num_minus_right() != 0 {
+                        champion_stats.side_mut(false).class_counts_mut().remove(MINUS);
+                        runnerup_stats.side_mut(false).class_counts_mut().remove(MINUS);
                     } else {
                         continue;
                     }
                 }
 
-                champion_stats.update_score_and_impurity_before();
-                runnerup_stats.update_score_and_impurity_before();
+                champion_stats.update_score_and_impurity_before(SplitCriterion::Gini);
+                runnerup_stats.update_score_and_impurity_before(SplitCriterion::Gini);
 
                 let step = Step {
                     label: *is_plus,
@@ -659,7 +659,7 @@ fn enumerate_changes(
                     passes_second: *passes_second
                 };
 
-                enumerated.push((step, champion_stats.clone(), runnerup_stats.clone()));
+                enumerated.push((step, champion_stats, runnerup_stats));
             }
         }
     }
@@ -0,0 +1,35 @@
+extern crate hedgecut;
+
+use hedgecut::schema::{Schema, SchemaDataset};
+use hedgecut::evaluation::end_to_end;
+
+fn main() {
+
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.len() != 4 {
+        eprintln!("Usage: {} <schema.tsv> <train.csv> <test.csv>", args[0]);
+        std::process::exit(1);
+    }
+
+    let schema = Schema::from_file(&args[1]);
+    let samples = SchemaDataset::samples_from_csv(&schema, &args[2]);
+    let test_data = SchemaDataset::samples_from_csv(&schema, &args[3]);
+    let dataset = SchemaDataset::from_samples(schema, &samples);
+
+    let seed: u64 = 4545;
+    let num_trees = 100;
+    let min_leaf_size = 2;
+    let max_tries_per_split = 5;
+
+    end_to_end(
+        &args[2],
+        dataset,
+        samples,
+        test_data,
+        seed,
+        num_trees,
+        min_leaf_size,
+        max_tries_per_split
+    );
+}
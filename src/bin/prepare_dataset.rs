@@ -0,0 +1,53 @@
+extern crate hedgecut;
+
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use rand::seq::SliceRandom;
+
+use hedgecut::preprocessing::{specs_from_file, column_names, samples_to_csv, Preprocessor, RawTable};
+
+fn main() {
+
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.len() != 6 {
+        eprintln!(
+            "Usage: {} <spec.tsv> <raw.csv> <delimiter> <has-header:true|false> <output-prefix>",
+            args[0]
+        );
+        std::process::exit(1);
+    }
+
+    let specs = specs_from_file(&args[1]);
+    let delimiter = *args[3].as_bytes().first().unwrap();
+    let has_header = args[4] == "true";
+    let prefix = &args[5];
+
+    let column_names = if has_header { None } else { Some(column_names(&specs)) };
+    let table = RawTable::from_csv(&args[2], delimiter, column_names);
+
+    let mut rows: Vec<usize> = (0..table.rows.len()).collect();
+    let mut rng = XorShiftRng::from_seed([42; 16]);
+    rows.shuffle(&mut rng);
+
+    let (test_rows, train_rows) = rows.split_at(rows.len() / 5);
+
+    let preprocessor = Preprocessor::fit(&specs, &table, train_rows);
+    let schema = preprocessor.schema();
+
+    let train_samples = preprocessor.transform(&table, train_rows);
+    let test_samples = preprocessor.transform(&table, test_rows);
+
+    samples_to_csv(&schema, &train_samples, &format!("{}-train.csv", prefix));
+    samples_to_csv(&schema, &test_samples, &format!("{}-test.csv", prefix));
+    schema.to_file(&format!("{}-schema.tsv", prefix));
+    preprocessor.to_file(&format!("{}-preprocessor.tsv", prefix));
+
+    println!(
+        "Wrote {} training and {} test samples with {} attributes to {}-*",
+        train_samples.len(),
+        test_samples.len(),
+        schema.num_attributes(),
+        prefix
+    );
+}
@@ -0,0 +1,153 @@
+use crate::config::ForestConfig;
+use crate::dataset::Sample;
+use crate::utils::mix;
+
+// Multiplicities are capped, so that a draw cannot loop forever on rounding errors. The chance
+// of a Poisson(1) draw above the cap is far below the resolution of the uniform draw.
+const MAX_MULTIPLICITY: u32 = 20;
+
+// Largest weight of a sample, which keeps the weight in range when the sample is drawn the
+// maximum number of times
+pub const MAX_WEIGHT: u32 = u32::MAX / MAX_MULTIPLICITY;
+
+// Poisson bootstrap: a record appears in the bootstrap sample of a tree with a Poisson(1)
+// distributed multiplicity, which only depends on the seed, the tree and the record id. Unlike
+// drawing n samples with replacement, the multiplicity of a record does not depend on the other
+// records, so a forest retrained without the forgotten records draws the same multiplicities for
+// the remaining ones.
+pub fn multiplicity(seed: u64, tree_index: u64, record_id: u64) -> u32 {
+    let hash = mix(mix(mix(seed) ^ tree_index) ^ record_id);
+
+    // Uniform in [0, 1) from the upper 53 bits of the hash
+    let uniform = (hash >> 11) as f64 / (1_u64 << 53) as f64;
+
+    let mut multiplicity = 0;
+    let mut probability = (-1.0_f64).exp();
+    let mut cumulative_probability = probability;
+
+    while uniform >= cumulative_probability && multiplicity < MAX_MULTIPLICITY {
+        multiplicity += 1;
+        probability /= multiplicity as f64;
+        cumulative_probability += probability;
+    }
+
+    multiplicity
+}
+
+// A sample as seen by a tree, which counts it once per draw into its bootstrap sample
+#[derive(Clone)]
+pub struct Resampled<'a, S: Sample> {
+    sample: &'a S,
+    multiplicity: u32,
+}
+
+impl<'a, S: Sample> Resampled<'a, S> {
+
+    // Without bagging, every tree sees every sample exactly once
+    pub fn new(config: &ForestConfig, tree_index: u64, sample: &'a S) -> Resampled<'a, S> {
+        let multiplicity = if config.bootstrap {
+            multiplicity(config.seed, tree_index, sample.record_id())
+        } else {
+            1
+        };
+
+        Resampled { sample, multiplicity }
+    }
+
+    pub fn multiplicity(&self) -> u32 {
+        self.multiplicity
+    }
+}
+
+impl<S: Sample> Sample for Resampled<'_, S> {
+
+    fn attribute_value(&self, attribute_index: u16) -> u16 {
+        self.sample.attribute_value(attribute_index)
+    }
+
+    fn true_label(&self) -> bool {
+        self.sample.true_label()
+    }
+
+    fn record_id(&self) -> u64 {
+        self.sample.record_id()
+    }
+
+    fn class_label(&self) -> u8 {
+        self.sample.class_label()
+    }
+
+    fn target(&self) -> f64 {
+        self.sample.target()
+    }
+
+    fn weight(&self) -> u32 {
+        self.sample.weight().checked_mul(self.multiplicity).unwrap_or_else(|| {
+            panic!("Weight of record {} exceeds the maximum weight of {}!", self.record_id(), MAX_WEIGHT)
+        })
+    }
+}
+
+// Bootstrap sample of a tree, records which were not drawn are left out
+pub fn resample<'a, S: Sample>(config: &ForestConfig, tree_index: u64, samples: &'a [S]) -> Vec<Resampled<'a, S>> {
+    samples.iter()
+        .map(|sample| Resampled::new(config, tree_index, sample))
+        .filter(|resampled| resampled.multiplicity() > 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::bootstrap::{MAX_MULTIPLICITY, MAX_WEIGHT, Resampled, multiplicity, resample};
+    use crate::config::ForestConfig;
+    use crate::dataset::Sample;
+    use crate::testing::synthetic_weighted_samples;
+
+    #[test]
+    fn multiplicities_follow_a_poisson_distribution() {
+        let multiplicities: Vec<u32> = (0..100_000).map(|record_id| multiplicity(42, 3, record_id)).collect();
+
+        let mean = multiplicities.iter().sum::<u32>() as f64 / multiplicities.len() as f64;
+        let share_left_out = multiplicities.iter().filter(|m| **m == 0).count() as f64 / multiplicities.len() as f64;
+
+        assert!((mean - 1.0).abs() < 0.02);
+        assert!((share_left_out - (-1.0_f64).exp()).abs() < 0.01);
+
+        assert_eq!(multiplicity(42, 3, 17), multiplicity(42, 3, 17));
+        assert!((0..100).any(|record_id| multiplicity(42, 3, record_id) != multiplicity(42, 4, record_id)));
+    }
+
+    #[test]
+    fn resampled_samples_carry_their_multiplicity() {
+        let (_, samples) = synthetic_weighted_samples(500);
+
+        let config = ForestConfig::builder().seed(7).bootstrap(true).build().unwrap();
+        let bootstrap_sample = resample(&config, 1, &samples);
+
+        assert!(bootstrap_sample.len() < samples.len());
+
+        for resampled in &bootstrap_sample {
+            let sample = &samples[resampled.record_id() as usize];
+            assert_eq!(resampled.multiplicity(), multiplicity(7, 1, sample.record_id()));
+            assert_eq!(resampled.weight(), sample.weight() * resampled.multiplicity());
+        }
+
+        let all_samples = resample(&ForestConfig::default(), 1, &samples);
+        assert!(all_samples.iter().all(|resampled| resampled.multiplicity() == 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the maximum weight")]
+    fn resampled_weights_do_not_overflow() {
+        let (_, mut samples) = synthetic_weighted_samples(1);
+
+        samples[0].weight = MAX_WEIGHT;
+        let heaviest = Resampled { sample: &samples[0], multiplicity: MAX_MULTIPLICITY };
+        assert_eq!(heaviest.weight(), MAX_WEIGHT * MAX_MULTIPLICITY);
+
+        samples[0].weight = u32::MAX;
+        let overflowing = Resampled { sample: &samples[0], multiplicity: 2 };
+        overflowing.weight();
+    }
+}
@@ -0,0 +1,209 @@
+use crate::dataset::{Dataset, Sample, Task, MISSING};
+use crate::split_stats::LabelStats;
+
+// The SIMD gathers load four bytes at a time, so the columns are padded with an extra value and
+// the label bitmap with three extra bytes
+const COLUMN_PADDING: usize = 1;
+const LABEL_PADDING: usize = 3;
+
+// Training samples stored column by column. The tree construction refers to them by row index,
+// so that all trees of a forest share a single copy of the data.
+pub struct ColumnarSamples {
+    num_rows: usize,
+    task: Task,
+    columns: Vec<Vec<u16>>,
+    // Whether a column holds any MISSING values
+    has_missing: Vec<bool>,
+    // Bit i % 8 of byte i / 8 is set if row i has a positive label
+    label_bits: Vec<u8>,
+    class_labels: Vec<u8>,
+    // Only filled for regression, the targets of classification samples are their class indexes
+    targets: Vec<f64>,
+    record_ids: Vec<u64>,
+    // Only filled if any sample has a weight other than one
+    weights: Vec<u32>,
+}
+
+impl ColumnarSamples {
+
+    pub fn from_samples<D: Dataset, S: Sample>(dataset: &D, samples: &[S]) -> ColumnarSamples {
+
+        let num_rows = samples.len();
+        assert!(num_rows <= i32::MAX as usize, "Too many samples for a columnar layout!");
+
+        let task = dataset.task();
+
+        let columns: Vec<Vec<u16>> = (0..dataset.num_attributes())
+            .map(|attribute_index| {
+                let mut column = Vec::with_capacity(num_rows + COLUMN_PADDING);
+                column.extend(samples.iter().map(|sample| sample.attribute_value(attribute_index)));
+                column.extend_from_slice(&[0; COLUMN_PADDING]);
+                column
+            })
+            .collect();
+
+        let has_missing = columns.iter()
+            .map(|column| column[..num_rows].contains(&MISSING))
+            .collect();
+
+        let mut label_bits = vec![0_u8; num_rows.div_ceil(8) + LABEL_PADDING];
+        for (row, sample) in samples.iter().enumerate() {
+            if sample.true_label() {
+                label_bits[row / 8] |= 1 << (row % 8);
+            }
+        }
+
+        let targets = match task {
+            Task::Classification => Vec::new(),
+            Task::Regression => samples.iter().map(|sample| sample.target()).collect(),
+        };
+
+        let weights = if samples.iter().all(|sample| sample.weight() == 1) {
+            Vec::new()
+        } else {
+            samples.iter().map(|sample| sample.weight()).collect()
+        };
+
+        ColumnarSamples {
+            num_rows,
+            task,
+            columns,
+            has_missing,
+            label_bits,
+            class_labels: samples.iter().map(|sample| sample.class_label()).collect(),
+            targets,
+            record_ids: samples.iter().map(|sample| sample.record_id()).collect(),
+            weights,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_rows == 0
+    }
+
+    pub fn task(&self) -> Task {
+        self.task
+    }
+
+    pub fn all_rows(&self) -> Vec<u32> {
+        (0..self.num_rows as u32).collect()
+    }
+
+    pub fn column(&self, attribute_index: u16) -> &[u16] {
+        &self.columns[attribute_index as usize][..self.num_rows]
+    }
+
+    pub fn has_missing(&self, attribute_index: u16) -> bool {
+        self.has_missing[attribute_index as usize]
+    }
+
+    pub fn is_weighted(&self) -> bool {
+        !self.weights.is_empty()
+    }
+
+    pub fn max_weight(&self) -> u32 {
+        self.weights.iter().copied().max().unwrap_or(1)
+    }
+
+    pub(crate) fn padded_column(&self, attribute_index: u16) -> &[u16] {
+        &self.columns[attribute_index as usize]
+    }
+
+    pub(crate) fn padded_label_bits(&self) -> &[u8] {
+        &self.label_bits
+    }
+
+    #[inline(always)]
+    pub fn is_plus(&self, row: u32) -> bool {
+        self.label_bits[row as usize / 8] & (1 << (row % 8)) != 0
+    }
+
+    pub fn row(&self, row: u32) -> ColumnarRow<'_> {
+        assert!((row as usize) < self.num_rows);
+        ColumnarRow { samples: self, row }
+    }
+
+    pub fn label_stats(&self, rows: &[u32]) -> LabelStats {
+        let mut stats = LabelStats::empty(self.task);
+        for row in rows {
+            stats.add(&self.row(*row));
+        }
+        stats
+    }
+}
+
+// View of a single row, which behaves like the sample it was built from
+#[derive(Clone,Copy)]
+pub struct ColumnarRow<'a> {
+    samples: &'a ColumnarSamples,
+    row: u32,
+}
+
+impl Sample for ColumnarRow<'_> {
+
+    fn attribute_value(&self, attribute_index: u16) -> u16 {
+        self.samples.columns[attribute_index as usize][self.row as usize]
+    }
+
+    fn true_label(&self) -> bool {
+        self.samples.is_plus(self.row)
+    }
+
+    fn record_id(&self) -> u64 {
+        self.samples.record_ids[self.row as usize]
+    }
+
+    fn class_label(&self) -> u8 {
+        self.samples.class_labels[self.row as usize]
+    }
+
+    fn target(&self) -> f64 {
+        match self.samples.task {
+            Task::Classification => self.class_label() as f64,
+            Task::Regression => self.samples.targets[self.row as usize],
+        }
+    }
+
+    fn weight(&self) -> u32 {
+        if self.samples.weights.is_empty() { 1 } else { self.samples.weights[self.row as usize] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::columnar::ColumnarSamples;
+    use crate::dataset::Sample;
+    use crate::testing::{synthetic_samples, synthetic_regression_samples};
+
+    #[test]
+    fn rows_behave_like_the_original_samples() {
+        let (dataset, samples) = synthetic_samples(77);
+        let columns = ColumnarSamples::from_samples(&dataset, &samples);
+
+        assert_eq!(columns.len(), 77);
+        assert_eq!(columns.column(1).len(), 77);
+
+        for (row, sample) in samples.iter().enumerate() {
+            let columnar_row = columns.row(row as u32);
+
+            let values: Vec<u16> = (0..3).map(|index| columnar_row.attribute_value(index)).collect();
+
+            assert_eq!(values, sample.values);
+            assert_eq!(columnar_row.true_label(), sample.true_label());
+            assert_eq!(columnar_row.class_label(), sample.class_label());
+            assert_eq!(columnar_row.record_id(), sample.record_id());
+        }
+
+        let (dataset, samples) = synthetic_regression_samples(10);
+        let columns = ColumnarSamples::from_samples(&dataset, &samples);
+
+        for (row, sample) in samples.iter().enumerate() {
+            assert_eq!(columns.row(row as u32).target(), sample.target);
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::split_stats::SplitCriterion;
+
+// Number of randomly chosen attributes for which a candidate split is drawn at every node
+#[derive(PartialEq,Clone,Copy,Debug,Serialize,Deserialize)]
+pub enum MaxFeatures {
+    Sqrt,
+    Log2,
+    All,
+    Count(usize),
+    // Share of the attributes, in (0, 1]
+    Fraction(f64),
+}
+
+impl MaxFeatures {
+
+    // At least one and at most all attributes are tried
+    pub fn resolve(&self, num_attributes: usize) -> usize {
+        let num_features = match self {
+            MaxFeatures::Sqrt => (num_attributes as f64).sqrt().round() as usize,
+            MaxFeatures::Log2 => (num_attributes as f64).log2().round() as usize,
+            MaxFeatures::All => num_attributes,
+            MaxFeatures::Count(count) => *count,
+            MaxFeatures::Fraction(fraction) => (num_attributes as f64 * fraction).round() as usize,
+        };
+
+        num_features.max(1).min(num_attributes)
+    }
+}
+
+// Number of removals which every split must be robust against, either as share of the training
+// records or as an absolute number
+#[derive(PartialEq,Clone,Copy,Debug,Serialize,Deserialize)]
+pub enum Robustness {
+    Epsilon(f64),
+    Absolute(usize),
+}
+
+impl Robustness {
+
+    pub fn target_robustness(&self, num_records: usize) -> usize {
+        match self {
+            // A positive epsilon covers at least one removal, even if it rounds to zero
+            Robustness::Epsilon(epsilon) if *epsilon > 0.0 => {
+                (((num_records as f64) * epsilon).round() as usize).max(1)
+            },
+            Robustness::Epsilon(_) => 0,
+            Robustness::Absolute(num_removals) => *num_removals,
+        }
+    }
+}
+
+#[derive(Eq,PartialEq,Clone,Copy,Debug)]
+pub enum ConfigError {
+    NoTrees,
+    NoTriesPerSplit,
+    // Nodes with fewer than two samples cannot be split
+    MinSamplesSplitTooSmall,
+    // A depth of zero would only leave the root as a leaf
+    ZeroMaxDepth,
+    InvalidMinImpurityDecrease,
+    InvalidMaxFeatures,
+    InvalidEpsilon,
+    // At least the best split has to be kept at a non-robust position
+    NoAlternatives,
+    NoThreads,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::NoTrees => write!(f, "The forest needs at least one tree"),
+            ConfigError::NoTriesPerSplit => write!(f, "At least one try per split is required"),
+            ConfigError::MinSamplesSplitTooSmall => write!(f, "Splitting requires at least two samples"),
+            ConfigError::ZeroMaxDepth => write!(f, "The maximum depth must be positive"),
+            ConfigError::InvalidMinImpurityDecrease =>
+                write!(f, "The minimum impurity decrease must be finite and non-negative"),
+            ConfigError::InvalidMaxFeatures => write!(f, "At least one feature must be tried per split"),
+            ConfigError::InvalidEpsilon => write!(f, "Epsilon must be in [0, 1]"),
+            ConfigError::NoAlternatives => write!(f, "At least one alternative must be kept per split"),
+            ConfigError::NoThreads => write!(f, "At least one thread is required"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Training parameters of a forest. The defaults match ExtremelyRandomizedTrees::fit with 100
+// trees, a minimum leaf size of two and five tries per split.
+#[derive(PartialEq,Clone,Copy,Debug,Serialize,Deserialize)]
+pub struct ForestConfig {
+    pub seed: u64,
+    pub num_trees: usize,
+    // Nodes with at most this many samples become leaves
+    pub min_leaf_size: usize,
+    // Nodes with fewer samples become leaves
+    pub min_samples_split: usize,
+    // The root has depth zero, nodes at the maximum depth become leaves
+    pub max_depth: Option<usize>,
+    // Splits must score at least this much under the split criterion, which is the decrease of
+    // the impurity of their node or the Hellinger distance of their sides. Nodes without such a
+    // split become leaves.
+    pub min_impurity_decrease: f64,
+    pub split_criterion: SplitCriterion,
+    pub max_features: MaxFeatures,
+    pub robustness: Robustness,
+    pub max_tries_per_split: usize,
+    // Maximum number of subtrees at a non-robust position, including the one of the best split.
+    // Dropping alternatives lowers the removal budget of the position accordingly.
+    pub max_alternatives: Option<usize>,
+    // Threads used for fitting, all available cores by default
+    pub num_threads: Option<usize>,
+    // Every tree is fitted on its own bootstrap sample instead of all samples
+    pub bootstrap: bool,
+}
+
+impl Default for ForestConfig {
+    fn default() -> ForestConfig {
+        ForestConfig {
+            seed: 0,
+            num_trees: 100,
+            min_leaf_size: 2,
+            min_samples_split: 2,
+            max_depth: None,
+            min_impurity_decrease: 0.0,
+            split_criterion: SplitCriterion::Gini,
+            max_features: MaxFeatures::Sqrt,
+            robustness: Robustness::Epsilon(1.0 / 1000.0),
+            max_tries_per_split: 5,
+            max_alternatives: None,
+            num_threads: None,
+            bootstrap: false,
+        }
+    }
+}
+
+impl ForestConfig {
+
+    pub fn builder() -> ForestConfigBuilder {
+        ForestConfigBuilder { config: ForestConfig::default() }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.num_trees == 0 {
+            return Err(ConfigError::NoTrees);
+        }
+
+        if self.max_tries_per_split == 0 {
+            return Err(ConfigError::NoTriesPerSplit);
+        }
+
+        if self.min_samples_split < 2 {
+            return Err(ConfigError::MinSamplesSplitTooSmall);
+        }
+
+        if self.max_depth == Some(0) {
+            return Err(ConfigError::ZeroMaxDepth);
+        }
+
+        if !(self.min_impurity_decrease.is_finite() && self.min_impurity_decrease >= 0.0) {
+            return Err(ConfigError::InvalidMinImpurityDecrease);
+        }
+
+        match self.max_features {
+            MaxFeatures::Count(0) => return Err(ConfigError::InvalidMaxFeatures),
+            MaxFeatures::Fraction(fraction) if !(fraction > 0.0 && fraction <= 1.0) =>
+                return Err(ConfigError::InvalidMaxFeatures),
+            _ => {}
+        }
+
+        if let Robustness::Epsilon(epsilon) = self.robustness {
+            if !(0.0..=1.0).contains(&epsilon) {
+                return Err(ConfigError::InvalidEpsilon);
+            }
+        }
+
+        if self.max_alternatives == Some(0) {
+            return Err(ConfigError::NoAlternatives);
+        }
+
+        if self.num_threads == Some(0) {
+            return Err(ConfigError::NoThreads);
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ForestConfigBuilder {
+    config: ForestConfig,
+}
+
+impl ForestConfigBuilder {
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = seed;
+        self
+    }
+
+    pub fn num_trees(mut self, num_trees: usize) -> Self {
+        self.config.num_trees = num_trees;
+        self
+    }
+
+    pub fn min_leaf_size(mut self, min_leaf_size: usize) -> Self {
+        self.config.min_leaf_size = min_leaf_size;
+        self
+    }
+
+    pub fn min_samples_split(mut self, min_samples_split: usize) -> Self {
+        self.config.min_samples_split = min_samples_split;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.config.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn min_impurity_decrease(mut self, min_impurity_decrease: f64) -> Self {
+        self.config.min_impurity_decrease = min_impurity_decrease;
+        self
+    }
+
+    pub fn split_criterion(mut self, split_criterion: SplitCriterion) -> Self {
+        self.config.split_criterion = split_criterion;
+        self
+    }
+
+    pub fn max_features(mut self, max_features: MaxFeatures) -> Self {
+        self.config.max_features = max_features;
+        self
+    }
+
+    pub fn epsilon(mut self, epsilon: f64) -> Self {
+        self.config.robustness = Robustness::Epsilon(epsilon);
+        self
+    }
+
+    pub fn target_robustness(mut self, num_removals: usize) -> Self {
+        self.config.robustness = Robustness::Absolute(num_removals);
+        self
+    }
+
+    pub fn max_tries_per_split(mut self, max_tries_per_split: usize) -> Self {
+        self.config.max_tries_per_split = max_tries_per_split;
+        self
+    }
+
+    pub fn max_alternatives(mut self, max_alternatives: usize) -> Self {
+        self.config.max_alternatives = Some(max_alternatives);
+        self
+    }
+
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.config.num_threads = Some(num_threads);
+        self
+    }
+
+    pub fn bootstrap(mut self, bootstrap: bool) -> Self {
+        self.config.bootstrap = bootstrap;
+        self
+    }
+
+    pub fn build(self) -> Result<ForestConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::config::{ConfigError, ForestConfig, MaxFeatures, Robustness};
+
+    #[test]
+    fn builder_validates_parameters() {
+        let config = ForestConfig::builder()
+            .seed(7)
+            .num_trees(20)
+            .max_depth(12)
+            .max_features(MaxFeatures::Fraction(0.5))
+            .target_robustness(3)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.seed, 7);
+        assert_eq!(config.max_depth, Some(12));
+        assert_eq!(config.robustness, Robustness::Absolute(3));
+        assert_eq!(config.min_leaf_size, ForestConfig::default().min_leaf_size);
+
+        assert_eq!(ForestConfig::builder().num_trees(0).build(), Err(ConfigError::NoTrees));
+        assert_eq!(ForestConfig::builder().min_samples_split(1).build(), Err(ConfigError::MinSamplesSplitTooSmall));
+        assert_eq!(ForestConfig::builder().max_depth(0).build(), Err(ConfigError::ZeroMaxDepth));
+        assert_eq!(
+            ForestConfig::builder().min_impurity_decrease(-0.1).build(),
+            Err(ConfigError::InvalidMinImpurityDecrease)
+        );
+        assert_eq!(ForestConfig::builder().epsilon(1.5).build(), Err(ConfigError::InvalidEpsilon));
+        assert_eq!(ForestConfig::builder().max_alternatives(0).build(), Err(ConfigError::NoAlternatives));
+        assert_eq!(ForestConfig::builder().num_threads(0).build(), Err(ConfigError::NoThreads));
+        assert_eq!(
+            ForestConfig::builder().max_features(MaxFeatures::Fraction(0.0)).build(),
+            Err(ConfigError::InvalidMaxFeatures)
+        );
+    }
+
+    #[test]
+    fn max_features_stay_within_the_attributes() {
+        assert_eq!(MaxFeatures::Sqrt.resolve(14), 4);
+        assert_eq!(MaxFeatures::Log2.resolve(1), 1);
+        assert_eq!(MaxFeatures::Count(50).resolve(14), 14);
+        assert_eq!(MaxFeatures::Fraction(0.5).resolve(3), 2);
+        assert_eq!(MaxFeatures::All.resolve(9), 9);
+    }
+
+    #[test]
+    fn small_datasets_keep_a_positive_target_robustness() {
+        assert_eq!(Robustness::Epsilon(0.001).target_robustness(40), 1);
+        assert_eq!(Robustness::Epsilon(0.001).target_robustness(3000), 3);
+        assert_eq!(Robustness::Epsilon(0.0).target_robustness(3000), 0);
+        assert_eq!(Robustness::Absolute(0).target_robustness(40), 0);
+    }
+}
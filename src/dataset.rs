@@ -1,14 +1,43 @@
 use std::str::FromStr;
+use serde::{Serialize, Deserialize};
 use crate::tree::Split;
+use crate::preprocessing::is_missing;
+
+// Attribute value of samples for which the value is unknown, attribute ranges must stay below it
+pub const MISSING: u16 = u16::MAX;
+
+// Empty or NA cells are read as missing values, like in the loader of the schema datasets
+fn parse_value(cell: &str) -> u16 {
+    if is_missing(cell) {
+        MISSING
+    } else {
+        u16::from_str(cell).unwrap()
+    }
+}
 
 pub trait Dataset {
     fn num_records(&self) -> u32;
     fn num_plus(&self) -> u32;
-    fn num_attributes(&self) -> u8;
-    fn attribute_range(&self, index: u8) -> (u8, u8);
-    fn attribute_type(&self, index: u8) -> AttributeType;
+    fn num_attributes(&self) -> u16;
+    fn attribute_range(&self, index: u16) -> (u16, u16);
+    fn attribute_type(&self, index: u16) -> AttributeType;
+
+    fn num_classes(&self) -> u8 {
+        2
+    }
+
+    fn task(&self) -> Task {
+        Task::Classification
+    }
+}
+
+#[derive(Eq,PartialEq,Debug,Clone,Copy,Serialize,Deserialize)]
+pub enum Task {
+    Classification,
+    Regression
 }
 
+#[derive(Eq,PartialEq,Debug,Clone,Copy)]
 pub enum AttributeType {
     Numerical,
     Categorical
@@ -17,23 +46,32 @@ pub enum AttributeType {
 pub trait Sample: Clone {
 
     fn is_left_of(&self, split: &Split) -> bool {
+        split.is_left(self.attribute_value(split.attribute_index()))
+    }
 
-        let attribute_index = split.attribute_index();
-        let attribute_value = self.attribute_value(attribute_index);
+    // MISSING if the value is unknown
+    fn attribute_value(&self, attribute_index: u16) -> u16;
+    fn true_label(&self) -> bool;
+    fn record_id(&self) -> u64;
 
-        match split {
-            Split::Numerical { attribute_index: _, cut_off } => {
-                attribute_value < *cut_off
-            },
-            Split::Categorical { attribute_index: _, subset } => {
-                *subset & (1_u64 << attribute_value as u64) != 0
-            }
+    // Index of the class of the sample, binary samples map their label to MINUS and PLUS
+    fn class_label(&self) -> u8 {
+        self.true_label() as u8
+    }
 
-        }
+    // Numeric target for regression, classification samples use their class index
+    fn target(&self) -> f64 {
+        self.class_label() as f64
     }
 
-    fn attribute_value(&self, attribute_index: u8) -> u8;
-    fn true_label(&self) -> bool;
+    // Integer importance of the sample, so that forgetting it subtracts exactly what it added
+    fn weight(&self) -> u32 {
+        1
+    }
+
+    fn is_missing(&self, attribute_index: u16) -> bool {
+        self.attribute_value(attribute_index) == MISSING
+    }
 }
 
 pub struct TitanicDataset {
@@ -43,18 +81,19 @@ pub struct TitanicDataset {
 
 #[derive(Eq,PartialEq,Debug,Clone)]
 pub struct TitanicSample {
-    pub age: u8,
-    pub fare: u8,
-    pub siblings: u8,
-    pub children: u8,
-    pub gender: u8,
-    pub pclass: u8,
+    pub record_id: u64,
+    pub age: u16,
+    pub fare: u16,
+    pub siblings: u16,
+    pub children: u16,
+    pub gender: u16,
+    pub pclass: u16,
     pub label: bool,
 }
 
 impl Sample for TitanicSample {
 
-    fn attribute_value(&self, attribute_index: u8) -> u8 {
+    fn attribute_value(&self, attribute_index: u16) -> u16 {
         match attribute_index {
             0 => self.age,
             1 => self.fare,
@@ -69,6 +108,10 @@ impl Sample for TitanicSample {
     fn true_label(&self) -> bool {
         self.label
     }
+
+    fn record_id(&self) -> u64 {
+        self.record_id
+    }
 }
 
 
@@ -97,15 +140,17 @@ impl TitanicDataset {
         for result in reader.records() {
             let record = result.unwrap();
 
-            let age = u8::from_str(record.get(1).unwrap()).unwrap();
-            let fare = u8::from_str(record.get(2).unwrap()).unwrap();
-            let siblings = u8::from_str(record.get(3).unwrap()).unwrap();
-            let children = u8::from_str(record.get(4).unwrap()).unwrap();
-            let gender = u8::from_str(record.get(5).unwrap()).unwrap();
-            let pclass = u8::from_str(record.get(6).unwrap()).unwrap();
+            let record_id = u64::from_str(record.get(0).unwrap()).unwrap();
+
+            let age = parse_value(record.get(1).unwrap());
+            let fare = parse_value(record.get(2).unwrap());
+            let siblings = parse_value(record.get(3).unwrap());
+            let children = parse_value(record.get(4).unwrap());
+            let gender = parse_value(record.get(5).unwrap());
+            let pclass = parse_value(record.get(6).unwrap());
             let label = u8::from_str(record.get(4).unwrap()).unwrap() == 1;
 
-            let sample = TitanicSample { age, fare, siblings, children, gender, pclass, label };
+            let sample = TitanicSample { record_id, age, fare, siblings, children, gender, pclass, label };
 
             samples.push(sample);
         }
@@ -121,9 +166,9 @@ impl Dataset for TitanicDataset {
 
     fn num_plus(&self) -> u32 { self.num_plus }
 
-    fn num_attributes(&self) -> u8 { 6 }
+    fn num_attributes(&self) -> u16 { 6 }
 
-    fn attribute_range(&self, index: u8) -> (u8, u8) {
+    fn attribute_range(&self, index: u16) -> (u16, u16) {
         match index {
             0 => (0, 19),
             1 => (0, 19),
@@ -135,7 +180,7 @@ impl Dataset for TitanicDataset {
         }
     }
 
-    fn attribute_type(&self, index: u8) -> AttributeType {
+    fn attribute_type(&self, index: u16) -> AttributeType {
         match index {
             0 => AttributeType::Numerical,
             1 => AttributeType::Numerical,
@@ -177,32 +222,35 @@ impl DefaultsDataset {
         for result in reader.records() {
             let record = result.unwrap();
 
-            let limit = u8::from_str(record.get(1).unwrap()).unwrap();
-            let sex = u8::from_str(record.get(2).unwrap()).unwrap();
-            let education = u8::from_str(record.get(3).unwrap()).unwrap();
-            let marriage = u8::from_str(record.get(4).unwrap()).unwrap();
-            let age = u8::from_str(record.get(5).unwrap()).unwrap();
-            let pay0 = u8::from_str(record.get(6).unwrap()).unwrap();
-            let pay2 = u8::from_str(record.get(7).unwrap()).unwrap();
-            let pay3 = u8::from_str(record.get(8).unwrap()).unwrap();
-            let pay4 = u8::from_str(record.get(9).unwrap()).unwrap();
-            let pay5 = u8::from_str(record.get(10).unwrap()).unwrap();
-            let pay6 = u8::from_str(record.get(11).unwrap()).unwrap();
-            let bill_amt1 = u8::from_str(record.get(12).unwrap()).unwrap();
-            let bill_amt2 = u8::from_str(record.get(13).unwrap()).unwrap();
-            let bill_amt3 = u8::from_str(record.get(14).unwrap()).unwrap();
-            let bill_amt4 = u8::from_str(record.get(15).unwrap()).unwrap();
-            let bill_amt5 = u8::from_str(record.get(16).unwrap()).unwrap();
-            let bill_amt6 = u8::from_str(record.get(17).unwrap()).unwrap();
-            let pay_amt1 = u8::from_str(record.get(18).unwrap()).unwrap();
-            let pay_amt2 = u8::from_str(record.get(19).unwrap()).unwrap();
-            let pay_amt3 = u8::from_str(record.get(20).unwrap()).unwrap();
-            let pay_amt4 = u8::from_str(record.get(21).unwrap()).unwrap();
-            let pay_amt5 = u8::from_str(record.get(22).unwrap()).unwrap();
-            let pay_amt6 = u8::from_str(record.get(23).unwrap()).unwrap();
+            let record_id = u64::from_str(record.get(0).unwrap()).unwrap();
+
+            let limit = parse_value(record.get(1).unwrap());
+            let sex = parse_value(record.get(2).unwrap());
+            let education = parse_value(record.get(3).unwrap());
+            let marriage = parse_value(record.get(4).unwrap());
+            let age = parse_value(record.get(5).unwrap());
+            let pay0 = parse_value(record.get(6).unwrap());
+            let pay2 = parse_value(record.get(7).unwrap());
+            let pay3 = parse_value(record.get(8).unwrap());
+            let pay4 = parse_value(record.get(9).unwrap());
+            let pay5 = parse_value(record.get(10).unwrap());
+            let pay6 = parse_value(record.get(11).unwrap());
+            let bill_amt1 = parse_value(record.get(12).unwrap());
+            let bill_amt2 = parse_value(record.get(13).unwrap());
+            let bill_amt3 = parse_value(record.get(14).unwrap());
+            let bill_amt4 = parse_value(record.get(15).unwrap());
+            let bill_amt5 = parse_value(record.get(16).unwrap());
+            let bill_amt6 = parse_value(record.get(17).unwrap());
+            let pay_amt1 = parse_value(record.get(18).unwrap());
+            let pay_amt2 = parse_value(record.get(19).unwrap());
+            let pay_amt3 = parse_value(record.get(20).unwrap());
+            let pay_amt4 = parse_value(record.get(21).unwrap());
+            let pay_amt5 = parse_value(record.get(22).unwrap());
+            let pay_amt6 = parse_value(record.get(23).unwrap());
             let label = u8::from_str(record.get(24).unwrap()).unwrap() == 1;
 
             let sample = DefaultsSample {
+                record_id,
                 limit,
                 sex,
                 education,
@@ -244,11 +292,11 @@ impl Dataset for DefaultsDataset {
 
     fn num_plus(&self) -> u32 { self.num_plus }
 
-    fn num_attributes(&self) -> u8 {
+    fn num_attributes(&self) -> u16 {
         23
     }
 
-    fn attribute_range(&self, index: u8) -> (u8, u8) {
+    fn attribute_range(&self, index: u16) -> (u16, u16) {
         match index {
             0 => (0, 14),
             1 => (0, 1),
@@ -277,7 +325,7 @@ impl Dataset for DefaultsDataset {
         }
     }
 
-    fn attribute_type(&self, index: u8) -> AttributeType {
+    fn attribute_type(&self, index: u16) -> AttributeType {
         match index {
             0 => AttributeType::Numerical,
             1 => AttributeType::Categorical,
@@ -309,35 +357,36 @@ impl Dataset for DefaultsDataset {
 
 #[derive(Eq,PartialEq,Debug,Clone)]
 pub struct DefaultsSample {
-    pub limit: u8,
-    pub sex: u8,
-    pub education: u8,
-    pub marriage: u8,
-    pub age: u8,
-    pub pay0: u8,
-    pub pay2: u8,
-    pub pay3: u8,
-    pub pay4: u8,
-    pub pay5: u8,
-    pub pay6: u8,
-    pub bill_amt1: u8,
-    pub bill_amt2: u8,
-    pub bill_amt3: u8,
-    pub bill_amt4: u8,
-    pub bill_amt5: u8,
-    pub bill_amt6: u8,
-    pub pay_amt1: u8,
-    pub pay_amt2: u8,
-    pub pay_amt3: u8,
-    pub pay_amt4: u8,
-    pub pay_amt5: u8,
-    pub pay_amt6: u8,
+    pub record_id: u64,
+    pub limit: u16,
+    pub sex: u16,
+    pub education: u16,
+    pub marriage: u16,
+    pub age: u16,
+    pub pay0: u16,
+    pub pay2: u16,
+    pub pay3: u16,
+    pub pay4: u16,
+    pub pay5: u16,
+    pub pay6: u16,
+    pub bill_amt1: u16,
+    pub bill_amt2: u16,
+    pub bill_amt3: u16,
+    pub bill_amt4: u16,
+    pub bill_amt5: u16,
+    pub bill_amt6: u16,
+    pub pay_amt1: u16,
+    pub pay_amt2: u16,
+    pub pay_amt3: u16,
+    pub pay_amt4: u16,
+    pub pay_amt5: u16,
+    pub pay_amt6: u16,
     pub label: bool,
 }
 
 impl Sample for DefaultsSample {
 
-    fn attribute_value(&self, attribute_index: u8) -> u8 {
+    fn attribute_value(&self, attribute_index: u16) -> u16 {
         match attribute_index {
             0 => self.limit,
             1 => self.sex,
@@ -370,6 +419,10 @@ impl Sample for DefaultsSample {
     fn true_label(&self) -> bool {
         self.label
     }
+
+    fn record_id(&self) -> u64 {
+        self.record_id
+    }
 }
 
 pub struct AdultDataset {
@@ -379,18 +432,19 @@ pub struct AdultDataset {
 
 #[derive(Eq,PartialEq,Debug,Clone)]
 pub struct AdultSample {
-    pub age: u8,
-    pub workclass: u8,
-    pub fnlwgt: u8,
-    pub education: u8,
-    pub marital_status: u8,
-    pub occupation: u8,
-    pub relationship: u8,
-    pub race: u8,
-    pub sex: u8,
-    pub capital_gain: u8,
-    pub hours_per_week: u8,
-    pub native_country: u8,
+    pub record_id: u64,
+    pub age: u16,
+    pub workclass: u16,
+    pub fnlwgt: u16,
+    pub education: u16,
+    pub marital_status: u16,
+    pub occupation: u16,
+    pub relationship: u16,
+    pub race: u16,
+    pub sex: u16,
+    pub capital_gain: u16,
+    pub hours_per_week: u16,
+    pub native_country: u16,
     pub label: bool,
 }
 
@@ -418,21 +472,24 @@ impl AdultDataset {
         for result in reader.records() {
             let record = result.unwrap();
 
-            let age = u8::from_str(record.get(1).unwrap()).unwrap();
-            let workclass = u8::from_str(record.get(2).unwrap()).unwrap();
-            let fnlwgt = u8::from_str(record.get(3).unwrap()).unwrap();
-            let education = u8::from_str(record.get(4).unwrap()).unwrap();
-            let marital_status = u8::from_str(record.get(5).unwrap()).unwrap();
-            let occupation = u8::from_str(record.get(6).unwrap()).unwrap();
-            let relationship = u8::from_str(record.get(7).unwrap()).unwrap();
-            let race = u8::from_str(record.get(8).unwrap()).unwrap();
-            let sex = u8::from_str(record.get(9).unwrap()).unwrap();
-            let capital_gain = u8::from_str(record.get(10).unwrap()).unwrap();
-            let hours_per_week = u8::from_str(record.get(11).unwrap()).unwrap();
-            let native_country = u8::from_str(record.get(12).unwrap()).unwrap();
+            let record_id = u64::from_str(record.get(0).unwrap()).unwrap();
+
+            let age = parse_value(record.get(1).unwrap());
+            let workclass = parse_value(record.get(2).unwrap());
+            let fnlwgt = parse_value(record.get(3).unwrap());
+            let education = parse_value(record.get(4).unwrap());
+            let marital_status = parse_value(record.get(5).unwrap());
+            let occupation = parse_value(record.get(6).unwrap());
+            let relationship = parse_value(record.get(7).unwrap());
+            let race = parse_value(record.get(8).unwrap());
+            let sex = parse_value(record.get(9).unwrap());
+            let capital_gain = parse_value(record.get(10).unwrap());
+            let hours_per_week = parse_value(record.get(11).unwrap());
+            let native_country = parse_value(record.get(12).unwrap());
             let label = u8::from_str(record.get(13).unwrap()).unwrap() == 1;
 
             let sample = AdultSample {
+                record_id,
                 age,
                 workclass,
                 fnlwgt,
@@ -461,9 +518,9 @@ impl Dataset for AdultDataset {
 
     fn num_plus(&self) -> u32 { self.num_plus }
 
-    fn num_attributes(&self) -> u8 { 12 }
+    fn num_attributes(&self) -> u16 { 12 }
 
-    fn attribute_range(&self, index: u8) -> (u8, u8) {
+    fn attribute_range(&self, index: u16) -> (u16, u16) {
         match index {
             0 => (0, 15),
             1 => (0, 6),
@@ -481,7 +538,7 @@ impl Dataset for AdultDataset {
         }
     }
 
-    fn attribute_type(&self, index: u8) -> AttributeType {
+    fn attribute_type(&self, index: u16) -> AttributeType {
         match index {
             0 => AttributeType::Numerical,
             1 => AttributeType::Categorical,
@@ -502,7 +559,7 @@ impl Dataset for AdultDataset {
 
 impl Sample for AdultSample {
 
-    fn attribute_value(&self, attribute_index: u8) -> u8 {
+    fn attribute_value(&self, attribute_index: u16) -> u16 {
 
         match attribute_index {
             0 => self.age,
@@ -524,6 +581,10 @@ impl Sample for AdultSample {
     fn true_label(&self) -> bool {
         self.label
     }
+
+    fn record_id(&self) -> u64 {
+        self.record_id
+    }
 }
 
 pub struct ShoppingDataset {
@@ -533,23 +594,24 @@ pub struct ShoppingDataset {
 
 #[derive(Eq,PartialEq,Debug,Clone)]
 pub struct ShoppingSample {
-    pub administrative: u8,
-    pub administrative_duration: u8,
-    pub informational: u8,
-    pub informational_duration: u8,
-    pub product_related: u8,
-    pub product_related_duration: u8,
-    pub bounce_rates: u8,
-    pub exit_rates: u8,
-    pub page_values: u8,
-    pub special_day: u8,
-    pub month: u8,
-    pub operating_systems: u8,
-    pub browser: u8,
-    pub region: u8,
-    pub traffic_type: u8,
-    pub visitor_type: u8,
-    pub weekend: u8,
+    pub record_id: u64,
+    pub administrative: u16,
+    pub administrative_duration: u16,
+    pub informational: u16,
+    pub informational_duration: u16,
+    pub product_related: u16,
+    pub product_related_duration: u16,
+    pub bounce_rates: u16,
+    pub exit_rates: u16,
+    pub page_values: u16,
+    pub special_day: u16,
+    pub month: u16,
+    pub operating_systems: u16,
+    pub browser: u16,
+    pub region: u16,
+    pub traffic_type: u16,
+    pub visitor_type: u16,
+    pub weekend: u16,
     pub label: bool,
 }
 
@@ -577,26 +639,29 @@ impl ShoppingDataset {
         for result in reader.records() {
             let record = result.unwrap();
 
-            let administrative = u8::from_str(record.get(1).unwrap()).unwrap();
-            let administrative_duration = u8::from_str(record.get(2).unwrap()).unwrap();
-            let informational = u8::from_str(record.get(3).unwrap()).unwrap();
-            let informational_duration = u8::from_str(record.get(4).unwrap()).unwrap();
-            let product_related = u8::from_str(record.get(5).unwrap()).unwrap();
-            let product_related_duration = u8::from_str(record.get(6).unwrap()).unwrap();
-            let bounce_rates = u8::from_str(record.get(7).unwrap()).unwrap();
-            let exit_rates = u8::from_str(record.get(8).unwrap()).unwrap();
-            let page_values = u8::from_str(record.get(9).unwrap()).unwrap();
-            let special_day = u8::from_str(record.get(10).unwrap()).unwrap();
-            let month = u8::from_str(record.get(11).unwrap()).unwrap();
-            let operating_systems = u8::from_str(record.get(12).unwrap()).unwrap();
-            let browser = u8::from_str(record.get(13).unwrap()).unwrap();
-            let region = u8::from_str(record.get(14).unwrap()).unwrap();
-            let traffic_type = u8::from_str(record.get(15).unwrap()).unwrap();
-            let visitor_type = u8::from_str(record.get(16).unwrap()).unwrap();
-            let weekend = u8::from_str(record.get(17).unwrap()).unwrap();
+            let record_id = u64::from_str(record.get(0).unwrap()).unwrap();
+
+            let administrative = parse_value(record.get(1).unwrap());
+            let administrative_duration = parse_value(record.get(2).unwrap());
+            let informational = parse_value(record.get(3).unwrap());
+            let informational_duration = parse_value(record.get(4).unwrap());
+            let product_related = parse_value(record.get(5).unwrap());
+            let product_related_duration = parse_value(record.get(6).unwrap());
+            let bounce_rates = parse_value(record.get(7).unwrap());
+            let exit_rates = parse_value(record.get(8).unwrap());
+            let page_values = parse_value(record.get(9).unwrap());
+            let special_day = parse_value(record.get(10).unwrap());
+            let month = parse_value(record.get(11).unwrap());
+            let operating_systems = parse_value(record.get(12).unwrap());
+            let browser = parse_value(record.get(13).unwrap());
+            let region = parse_value(record.get(14).unwrap());
+            let traffic_type = parse_value(record.get(15).unwrap());
+            let visitor_type = parse_value(record.get(16).unwrap());
+            let weekend = parse_value(record.get(17).unwrap());
             let label: bool = u8::from_str(record.get(18).unwrap()).unwrap() == 1;
 
             let sample = ShoppingSample {
+                record_id,
                 administrative,
                 administrative_duration,
                 informational,
@@ -634,9 +699,9 @@ impl Dataset for ShoppingDataset {
         self.num_plus
     }
 
-    fn num_attributes(&self) -> u8 { 17 }
+    fn num_attributes(&self) -> u16 { 17 }
 
-    fn attribute_range(&self, index: u8) -> (u8, u8) {
+    fn attribute_range(&self, index: u16) -> (u16, u16) {
         match index {
             0 => (0, 7),
             1 => (0, 8),
@@ -659,7 +724,7 @@ impl Dataset for ShoppingDataset {
         }
     }
 
-    fn attribute_type(&self, index: u8) -> AttributeType {
+    fn attribute_type(&self, index: u16) -> AttributeType {
         match index {
             0 => AttributeType::Numerical,
             1 => AttributeType::Numerical,
@@ -686,7 +751,7 @@ impl Dataset for ShoppingDataset {
 
 impl Sample for ShoppingSample {
 
-    fn attribute_value(&self, attribute_index: u8) -> u8 {
+    fn attribute_value(&self, attribute_index: u16) -> u16 {
         match attribute_index {
             0 => self.administrative,
             1 => self.administrative_duration,
@@ -712,6 +777,10 @@ impl Sample for ShoppingSample {
     fn true_label(&self) -> bool {
         self.label
     }
+
+    fn record_id(&self) -> u64 {
+        self.record_id
+    }
 }
 
 pub struct CardioDataset {
@@ -721,17 +790,18 @@ pub struct CardioDataset {
 
 #[derive(Eq,PartialEq,Debug,Clone)]
 pub struct CardioSample {
-    age: u8,
-    gender: u8,
-    height: u8,
-    weight: u8,
-    ap_hi: u8,
-    ap_lo: u8,
-    cholesterol: u8,
-    glucose: u8,
-    smoke: u8,
-    alcohol: u8,
-    active: u8,
+    pub record_id: u64,
+    age: u16,
+    gender: u16,
+    height: u16,
+    weight: u16,
+    ap_hi: u16,
+    ap_lo: u16,
+    cholesterol: u16,
+    glucose: u16,
+    smoke: u16,
+    alcohol: u16,
+    active: u16,
     label: bool,
 }
 
@@ -759,20 +829,23 @@ impl CardioDataset {
         for result in reader.records() {
             let record = result.unwrap();
 
-            let age = u8::from_str(record.get(1).unwrap()).unwrap();
-            let gender = u8::from_str(record.get(2).unwrap()).unwrap();
-            let height = u8::from_str(record.get(3).unwrap()).unwrap();
-            let weight = u8::from_str(record.get(4).unwrap()).unwrap();
-            let ap_hi = u8::from_str(record.get(5).unwrap()).unwrap();
-            let ap_lo = u8::from_str(record.get(6).unwrap()).unwrap();
-            let cholesterol = u8::from_str(record.get(7).unwrap()).unwrap();
-            let glucose = u8::from_str(record.get(8).unwrap()).unwrap();
-            let smoke = u8::from_str(record.get(9).unwrap()).unwrap();
-            let alcohol = u8::from_str(record.get(10).unwrap()).unwrap();
-            let active = u8::from_str(record.get(11).unwrap()).unwrap();
+            let record_id = u64::from_str(record.get(0).unwrap()).unwrap();
+
+            let age = parse_value(record.get(1).unwrap());
+            let gender = parse_value(record.get(2).unwrap());
+            let height = parse_value(record.get(3).unwrap());
+            let weight = parse_value(record.get(4).unwrap());
+            let ap_hi = parse_value(record.get(5).unwrap());
+            let ap_lo = parse_value(record.get(6).unwrap());
+            let cholesterol = parse_value(record.get(7).unwrap());
+            let glucose = parse_value(record.get(8).unwrap());
+            let smoke = parse_value(record.get(9).unwrap());
+            let alcohol = parse_value(record.get(10).unwrap());
+            let active = parse_value(record.get(11).unwrap());
             let label = u8::from_str(record.get(12).unwrap()).unwrap() == 1;
 
             let sample = CardioSample {
+                record_id,
                 age,
                 gender,
                 height,
@@ -804,11 +877,11 @@ impl Dataset for CardioDataset {
         self.num_plus
     }
 
-    fn num_attributes(&self) -> u8 {
+    fn num_attributes(&self) -> u16 {
         11
     }
 
-    fn attribute_range(&self, index: u8) -> (u8, u8) {
+    fn attribute_range(&self, index: u16) -> (u16, u16) {
         match index {
             0 => (0, 15),
             1 => (0, 1),
@@ -825,7 +898,7 @@ impl Dataset for CardioDataset {
         }
     }
 
-    fn attribute_type(&self, index: u8) -> AttributeType {
+    fn attribute_type(&self, index: u16) -> AttributeType {
         match index {
             0 => AttributeType::Numerical,
             1 => AttributeType::Categorical,
@@ -844,7 +917,7 @@ impl Dataset for CardioDataset {
 }
 
 impl Sample for CardioSample {
-    fn attribute_value(&self, attribute_index: u8) -> u8 {
+    fn attribute_value(&self, attribute_index: u16) -> u16 {
         match attribute_index {
             0 => self.age,
             1 => self.gender,
@@ -864,6 +937,10 @@ impl Sample for CardioSample {
     fn true_label(&self) -> bool {
         self.label
     }
+
+    fn record_id(&self) -> u64 {
+        self.record_id
+    }
 }
 
 
@@ -874,16 +951,17 @@ pub struct PropublicaDataset {
 
 #[derive(Eq,PartialEq,Debug,Clone)]
 pub struct PropublicaSample {
-    pub age: u8,
-    pub decile_score: u8,
-    pub priors_count: u8,
-    pub days_b_screening_arrest: u8,
-    pub is_recid: u8,
-    pub c_charge_degree: u8,
-    pub sex: u8,
-    pub age_cat: u8,
-    pub score_text: u8,
-    pub race: u8,
+    pub record_id: u64,
+    pub age: u16,
+    pub decile_score: u16,
+    pub priors_count: u16,
+    pub days_b_screening_arrest: u16,
+    pub is_recid: u16,
+    pub c_charge_degree: u16,
+    pub sex: u16,
+    pub age_cat: u16,
+    pub score_text: u16,
+    pub race: u16,
     pub label: bool,
 }
 
@@ -912,19 +990,22 @@ impl PropublicaDataset {
         for result in reader.records() {
             let record = result.unwrap();
 
-            let age = u8::from_str(record.get(1).unwrap()).unwrap();
-            let decile_score = u8::from_str(record.get(2).unwrap()).unwrap();
-            let priors_count = u8::from_str(record.get(3).unwrap()).unwrap();
-            let days_b_screening_arrest = u8::from_str(record.get(4).unwrap()).unwrap();
-            let is_recid = u8::from_str(record.get(5).unwrap()).unwrap();
-            let c_charge_degree = u8::from_str(record.get(6).unwrap()).unwrap();
-            let sex = u8::from_str(record.get(7).unwrap()).unwrap();
-            let age_cat = u8::from_str(record.get(8).unwrap()).unwrap();
-            let score_text = u8::from_str(record.get(9).unwrap()).unwrap();
-            let race = u8::from_str(record.get(10).unwrap()).unwrap();
+            let record_id = u64::from_str(record.get(0).unwrap()).unwrap();
+
+            let age = parse_value(record.get(1).unwrap());
+            let decile_score = parse_value(record.get(2).unwrap());
+            let priors_count = parse_value(record.get(3).unwrap());
+            let days_b_screening_arrest = parse_value(record.get(4).unwrap());
+            let is_recid = parse_value(record.get(5).unwrap());
+            let c_charge_degree = parse_value(record.get(6).unwrap());
+            let sex = parse_value(record.get(7).unwrap());
+            let age_cat = parse_value(record.get(8).unwrap());
+            let score_text = parse_value(record.get(9).unwrap());
+            let race = parse_value(record.get(10).unwrap());
             let label = u8::from_str(record.get(11).unwrap()).unwrap() == 1;
 
             let sample = PropublicaSample {
+                record_id,
                 age,
                 decile_score,
                 priors_count,
@@ -955,11 +1036,11 @@ impl Dataset for PropublicaDataset {
         self.num_plus
     }
 
-    fn num_attributes(&self) -> u8 {
+    fn num_attributes(&self) -> u16 {
         10
     }
 
-    fn attribute_range(&self, index: u8) -> (u8, u8) {
+    fn attribute_range(&self, index: u16) -> (u16, u16) {
         match index {
             0 => (0, 15),
             1 => (0, 8),
@@ -975,7 +1056,7 @@ impl Dataset for PropublicaDataset {
         }
     }
 
-    fn attribute_type(&self, index: u8) -> AttributeType {
+    fn attribute_type(&self, index: u16) -> AttributeType {
         match index {
             0 => AttributeType::Numerical,
             1 => AttributeType::Numerical,
@@ -994,7 +1075,7 @@ impl Dataset for PropublicaDataset {
 
 impl Sample for PropublicaSample {
 
-    fn attribute_value(&self, attribute_index: u8) -> u8 {
+    fn attribute_value(&self, attribute_index: u16) -> u16 {
         match attribute_index {
             0 => self.age,
             1 => self.decile_score,
@@ -1013,6 +1094,10 @@ impl Sample for PropublicaSample {
     fn true_label(&self) -> bool {
         self.label
     }
+
+    fn record_id(&self) -> u64 {
+        self.record_id
+    }
 }
 
 
@@ -1024,14 +1109,15 @@ pub struct GiveMeSomeCreditDataset {
 
 #[derive(Eq,PartialEq,Debug,Clone)]
 pub struct GiveMeSomeCreditSample {
-    pub revolving_util: u8,
-    pub age: u8,
-    pub past_due: u8,
-    pub debt_ratio: u8,
-    pub income: u8,
-    pub lines: u8,
-    pub real_estate: u8,
-    pub dependents: u8,
+    pub record_id: u64,
+    pub revolving_util: u16,
+    pub age: u16,
+    pub past_due: u16,
+    pub debt_ratio: u16,
+    pub income: u16,
+    pub lines: u16,
+    pub real_estate: u16,
+    pub dependents: u16,
     pub label: bool,
 }
 
@@ -1059,17 +1145,20 @@ impl GiveMeSomeCreditDataset {
         for result in reader.records() {
             let record = result.unwrap();
 
-            let revolving_util = u8::from_str(record.get(1).unwrap()).unwrap();
-            let age = u8::from_str(record.get(2).unwrap()).unwrap();
-            let past_due = u8::from_str(record.get(3).unwrap()).unwrap();
-            let debt_ratio = u8::from_str(record.get(4).unwrap()).unwrap();
-            let income = u8::from_str(record.get(5).unwrap()).unwrap();
-            let lines = u8::from_str(record.get(6).unwrap()).unwrap();
-            let real_estate = u8::from_str(record.get(7).unwrap()).unwrap();
-            let dependents = u8::from_str(record.get(8).unwrap()).unwrap();
+            let record_id = u64::from_str(record.get(0).unwrap()).unwrap();
+
+            let revolving_util = parse_value(record.get(1).unwrap());
+            let age = parse_value(record.get(2).unwrap());
+            let past_due = parse_value(record.get(3).unwrap());
+            let debt_ratio = parse_value(record.get(4).unwrap());
+            let income = parse_value(record.get(5).unwrap());
+            let lines = parse_value(record.get(6).unwrap());
+            let real_estate = parse_value(record.get(7).unwrap());
+            let dependents = parse_value(record.get(8).unwrap());
             let label = u8::from_str(record.get(9).unwrap()).unwrap() == 1;
 
             let sample = GiveMeSomeCreditSample {
+                record_id,
                 revolving_util,
                 age,
                 past_due,
@@ -1098,11 +1187,11 @@ impl Dataset for GiveMeSomeCreditDataset {
         self.num_plus
     }
 
-    fn num_attributes(&self) -> u8 {
+    fn num_attributes(&self) -> u16 {
         8
     }
 
-    fn attribute_range(&self, index: u8) -> (u8, u8) {
+    fn attribute_range(&self, index: u16) -> (u16, u16) {
         match index {
             0 => (0, 14),
             1 => (0, 1),
@@ -1116,7 +1205,7 @@ impl Dataset for GiveMeSomeCreditDataset {
         }
     }
 
-    fn attribute_type(&self, index: u8) -> AttributeType {
+    fn attribute_type(&self, index: u16) -> AttributeType {
         match index {
             0 => AttributeType::Numerical,
             1 => AttributeType::Numerical,
@@ -1132,7 +1221,7 @@ impl Dataset for GiveMeSomeCreditDataset {
 }
 
 impl Sample for GiveMeSomeCreditSample {
-    fn attribute_value(&self, attribute_index: u8) -> u8 {
+    fn attribute_value(&self, attribute_index: u16) -> u16 {
         match attribute_index {
             0 => self.revolving_util,
             1 => self.age,
@@ -1149,4 +1238,34 @@ impl Sample for GiveMeSomeCreditSample {
     fn true_label(&self) -> bool {
         self.label
     }
-}
\ No newline at end of file
+
+    fn record_id(&self) -> u64 {
+        self.record_id
+    }
+}
+#[cfg(test)]
+mod tests {
+
+    use crate::dataset::{Sample, TitanicDataset, MISSING};
+
+    #[test]
+    fn loaders_read_empty_and_na_cells_as_missing() {
+        let file = std::env::temp_dir().join("hedgecut-titanic-missing-test.tsv");
+        std::fs::write(
+            &file,
+            "record_id\tage\tfare\tsiblings\tchildren\tgender\tpclass\tlabel\n\
+             0\t3\t\t1\t0\t1\t2\t1\n\
+             1\tNA\t5\t?\t1\t0\t1\t0\n"
+        ).unwrap();
+
+        let samples = TitanicDataset::samples_from_csv(file.to_str().unwrap());
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].attribute_value(0), 3);
+        assert!(samples[0].is_missing(1));
+        assert_eq!(samples[1].attribute_value(0), MISSING);
+        assert_eq!(samples[1].attribute_value(1), 5);
+        assert!(samples[1].is_missing(2));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::dataset::{Sample, Dataset};
-use crate::tree::ExtremelyRandomizedTrees;
+use crate::tree::{ExtremelyRandomizedTrees, ForgetError};
 use rand::{thread_rng, RngCore, Rng};
 use std::time::Instant;
 use rand::seq::SliceRandom;
@@ -7,7 +7,7 @@ use rand::seq::SliceRandom;
 pub fn evaluate<S: Sample + Sync>(
     name: &str,
     trees: &ExtremelyRandomizedTrees,
-    test_data: &Vec<S>,
+    test_data: &[S],
     training_time_and_max_tries: Option<(u128, usize)>,
 
 ) {
@@ -16,8 +16,9 @@ pub fn evaluate<S: Sample + Sync>(
     let mut t_n = 0;
     let mut _f_n = 0;
 
-    for sample in test_data.iter() {
-        let predicted_label = trees.predict(sample);
+    let predicted_labels = trees.predict_batch(test_data);
+
+    for (sample, predicted_label) in test_data.iter().zip(predicted_labels) {
 
         if sample.true_label() {
             if predicted_label {
@@ -47,6 +48,42 @@ pub fn evaluate<S: Sample + Sync>(
 
 }
 
+// Area under the ROC curve, computed from the ranks of the scores with ties sharing their
+// average rank
+pub fn roc_auc(scores: &[f64], labels: &[bool]) -> f64 {
+    assert_eq!(scores.len(), labels.len());
+
+    let num_plus = labels.iter().filter(|label| **label).count();
+    let num_minus = labels.len() - num_plus;
+
+    assert!(num_plus > 0 && num_minus > 0, "ROC AUC requires both labels to be present!");
+    assert!(scores.iter().all(|score| !score.is_nan()), "ROC AUC is undefined for NaN scores!");
+
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|a, b| scores[*a].total_cmp(&scores[*b]));
+
+    let mut sum_of_plus_ranks = 0.0;
+    let mut start = 0;
+
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && scores[order[end]] == scores[order[start]] {
+            end += 1;
+        }
+
+        // Ranks are one-based
+        let average_rank = (start + end + 1) as f64 / 2.0;
+        let num_plus_tied = order[start..end].iter().filter(|index| labels[**index]).count();
+        sum_of_plus_ranks += average_rank * num_plus_tied as f64;
+
+        start = end;
+    }
+
+    let num_plus = num_plus as f64;
+    (sum_of_plus_ranks - num_plus * (num_plus + 1.0) / 2.0) / (num_plus * num_minus as f64)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn end_to_end<D: Dataset + Sync, S: Sample + Sync>(
     name: &str,
     dataset: D,
@@ -98,7 +135,7 @@ pub fn accuracy_forget<D: Dataset + Sync, S: Sample + Sync + Eq>(
 
     let samples_for_retraining: Vec<S> = samples.iter()
         .filter_map(|s| {
-            if samples_to_forget.contains(&s) {
+            if samples_to_forget.contains(s) {
                 None
             } else {
                 Some(s.clone())
@@ -120,8 +157,9 @@ pub fn accuracy_forget<D: Dataset + Sync, S: Sample + Sync + Eq>(
     let mut t_n = 0;
     let mut f_n = 0;
 
-    for sample in test_data.iter() {
-        let predicted_label = trees.predict(sample);
+    let predicted_labels = trees.predict_batch(&test_data);
+
+    for (sample, predicted_label) in test_data.iter().zip(predicted_labels) {
 
         if sample.true_label() {
             if predicted_label {
@@ -141,7 +179,7 @@ pub fn accuracy_forget<D: Dataset + Sync, S: Sample + Sync + Eq>(
     let accuracy = (t_p + t_n) as f64 / test_data.len() as f64;
 
     for sample in &samples_to_forget {
-        trees.forget(sample);
+        trees.forget(sample).unwrap();
     }
 
     let mut t_p_forget = 0;
@@ -149,8 +187,9 @@ pub fn accuracy_forget<D: Dataset + Sync, S: Sample + Sync + Eq>(
     let mut t_n_forget = 0;
     let mut f_n_forget = 0;
 
-    for sample in test_data.iter() {
-        let predicted_label = trees.predict(sample);
+    let predicted_labels = trees.predict_batch(&test_data);
+
+    for (sample, predicted_label) in test_data.iter().zip(predicted_labels) {
 
         if sample.true_label() {
             if predicted_label {
@@ -183,8 +222,9 @@ pub fn accuracy_forget<D: Dataset + Sync, S: Sample + Sync + Eq>(
     let mut t_n_retrained = 0;
     let mut f_n_retrained = 0;
 
-    for sample in test_data.iter() {
-        let predicted_label = retrained_trees.predict(sample);
+    let predicted_labels = retrained_trees.predict_batch(&test_data);
+
+    for (sample, predicted_label) in test_data.iter().zip(predicted_labels) {
 
         if sample.true_label() {
             if predicted_label {
@@ -263,7 +303,7 @@ pub fn forget2<D: Dataset + Sync, S: Sample + Sync>(
 
     for sample in &samples_to_forget {
         ert.trees.iter_mut().for_each(|tree| {
-            let (hit, changed) = Tree::forget_from2(tree, sample, 1);
+            let (hit, changed) = Tree::forget_from2(tree, sample);
             total_hit += hit;
             total_changed += changed;
         });
@@ -274,6 +314,7 @@ pub fn forget2<D: Dataset + Sync, S: Sample + Sync>(
     println!("{},{},{},{}", name, min_leaf_size, total_hit, total_changed);
 }
 
+// Returns the errors of the samples which could not be forgotten
 pub fn forget<D: Dataset + Sync, S: Sample + Sync>(
     name: &str,
     dataset: D,
@@ -281,7 +322,7 @@ pub fn forget<D: Dataset + Sync, S: Sample + Sync>(
     num_trees: usize,
     min_leaf_size: usize,
     max_tries_per_split: usize,
-) {
+) -> Vec<ForgetError> {
 
     let mut rng = thread_rng();
     let seed = rng.next_u64();
@@ -307,12 +348,19 @@ pub fn forget<D: Dataset + Sync, S: Sample + Sync>(
     // let training_duration = training_start.elapsed();
     // println!("Fitted {} trees in {} ms", num_trees, training_duration.as_millis());
 
+    let mut errors = Vec::new();
+
     for sample in &samples_to_forget {
         let removal_start = Instant::now();
-        trees.forget(sample);
+        // Samples are drawn with replacement, so we might try to forget one twice
+        if let Err(error) = trees.forget(sample) {
+            errors.push(error);
+        }
         let removal_duration = removal_start.elapsed();
         println!("{},hedgecut,{}", name, removal_duration.as_micros());
     }
+
+    errors
 }
 
 pub fn max_tries<D: Dataset + Sync, S: Sample + Sync>(
@@ -340,14 +388,14 @@ pub fn max_tries<D: Dataset + Sync, S: Sample + Sync>(
                 seed,
                 num_trees,
                 min_leaf_size,
-                max_tries_per_split.clone()
+                *max_tries_per_split
             );
             let training_duration = training_start.elapsed();
             evaluate(
                 name,
                 &trees,
                 &test_data,
-                Some((training_duration.as_millis(), max_tries_per_split.clone()))
+                Some((training_duration.as_millis(), *max_tries_per_split))
             );
         }
     }
@@ -376,7 +424,7 @@ pub fn train_time<D: Dataset + Sync, S: Sample + Sync>(
         seed,
         num_trees,
         min_leaf_size,
-        max_tries_per_split.clone()
+        max_tries_per_split
     );
     let training_duration = training_start.elapsed();
     println!("{},hedgecut,{}", name, training_duration.as_millis());
@@ -406,7 +454,7 @@ pub fn robustness<D: Dataset + Sync, S: Sample + Sync>(
             seed,
             num_trees,
             min_leaf_size,
-            max_tries_per_split.clone(),
+            max_tries_per_split,
             epsilon
         );
         let training_duration = training_start.elapsed();
@@ -437,7 +485,7 @@ pub fn robustness2<D: Dataset + Sync, S: Sample + Sync>(
             seed,
             num_trees,
             min_leaf_size,
-            max_tries_per_split.clone(),
+            max_tries_per_split,
             epsilon
         );
 
@@ -463,7 +511,7 @@ pub fn node_count(tree: crate::tree::Tree) -> (usize, usize) {
     num_robust += tree.num_robust_nodes;
     num_non_robust += tree.num_non_robust_nodes;
 
-    for (_, subtrees) in tree.alternative_subtrees {
+    for subtrees in tree.alternative_subtrees {
         for subtree in subtrees {
             let (subtree_num_robust, subtree_num_non_robust) = node_count(subtree.tree);
             num_robust += subtree_num_robust;
@@ -498,15 +546,16 @@ pub fn robustness_accuracy<D: Dataset + Sync, S: Sample + Sync>(
             seed,
             num_trees,
             min_leaf_size,
-            max_tries_per_split.clone(),
+            max_tries_per_split,
             epsilon
         );
 
         let mut t_p = 0;
         let mut t_n = 0;
 
-        for sample in test_data.iter() {
-            let predicted_label = trees.predict(sample);
+        let predicted_labels = trees.predict_batch(&test_data);
+
+        for (sample, predicted_label) in test_data.iter().zip(predicted_labels) {
 
             if sample.true_label() {
                 if predicted_label {
@@ -532,6 +581,7 @@ enum Request<S: Sample> {
     Forget(S),
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn stress_test<D: Dataset + Sync, S: Sample + Sync>(
     name: &str,
     dataset: D,
@@ -576,14 +626,14 @@ pub fn stress_test<D: Dataset + Sync, S: Sample + Sync>(
         seed,
         num_trees,
         min_leaf_size,
-        max_tries_per_split.clone()
+        max_tries_per_split
     );
 
     let prediction_start = Instant::now();
     for test_sample in &stress_test_data {
         match test_sample {
             Request::Predict(sample) => { trees.predict(sample); } ,
-            Request::Forget(sample) => trees.forget(sample),
+            Request::Forget(sample) => { trees.forget(sample).ok(); },
         }
     }
     let prediction_duration = prediction_start.elapsed();
@@ -597,7 +647,7 @@ pub fn stress_test<D: Dataset + Sync, S: Sample + Sync>(
     for test_sample in &stress_test_data_with_forgets {
         match test_sample {
             Request::Predict(sample) => { trees.predict(sample); } ,
-            Request::Forget(sample) => trees.forget(sample),
+            Request::Forget(sample) => { trees.forget(sample).ok(); },
         }
     }
     let prediction_duration = prediction_start.elapsed();
@@ -607,3 +657,48 @@ pub fn stress_test<D: Dataset + Sync, S: Sample + Sync>(
 
     println!("{},forgets,{},{}", name, prediction_duration.as_millis(), throughput);
 }
+
+#[cfg(test)]
+mod tests {
+
+    use crate::evaluation::roc_auc;
+    use crate::testing::synthetic_samples;
+    use crate::tree::ExtremelyRandomizedTrees;
+    use crate::dataset::Sample;
+
+    #[test]
+    fn roc_auc_handles_ties() {
+        assert_eq!(roc_auc(&[0.1, 0.4, 0.35, 0.8], &[false, false, true, true]), 0.75);
+        assert_eq!(roc_auc(&[0.5, 0.5, 0.5, 0.5], &[false, true, false, true]), 0.5);
+        assert_eq!(roc_auc(&[0.9, 0.1], &[false, true]), 0.0);
+        assert_eq!(roc_auc(&[-0.0, 0.0, 1.0], &[false, true, true]), 0.75);
+    }
+
+    #[test]
+    #[should_panic(expected = "NaN")]
+    fn roc_auc_rejects_nan_scores() {
+        roc_auc(&[0.1, f64::NAN, 0.8], &[false, true, true]);
+    }
+
+    #[test]
+    fn probabilities_agree_with_votes() {
+        let (dataset, samples) = synthetic_samples(1000);
+        let (training_samples, test_samples) = samples.split_at(800);
+
+        let trees = ExtremelyRandomizedTrees::fit(&dataset, training_samples.to_vec(), 42, 10, 2, 5);
+
+        for sample in test_samples {
+            let probability = trees.predict_proba(sample);
+            let (num_plus, num_minus) = trees.predict_votes(sample);
+
+            assert!((0.0..=1.0).contains(&probability));
+            assert_eq!(num_plus + num_minus, 10);
+            assert_eq!(trees.predict(sample), num_plus > num_minus);
+        }
+
+        let scores: Vec<f64> = test_samples.iter().map(|sample| trees.predict_proba(sample)).collect();
+        let labels: Vec<bool> = test_samples.iter().map(|sample| sample.true_label()).collect();
+
+        assert!(roc_auc(&scores, &labels) > 0.8);
+    }
+}
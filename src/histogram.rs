@@ -0,0 +1,262 @@
+use crate::columnar::ColumnarSamples;
+use crate::dataset::{Dataset, Task, MISSING};
+use crate::split_stats::{LabelStats, SplitStats};
+use crate::tree::Split;
+
+// Label statistics of the rows of a node for every value of an attribute, the stats of any
+// split on the attribute are derived from them without touching the rows again
+#[derive(Clone,Debug)]
+pub struct Histogram {
+    task: Task,
+    min_value: u16,
+    bins: Vec<LabelStats>,
+    missing: LabelStats,
+}
+
+impl Histogram {
+
+    pub fn from_rows(
+        columns: &ColumnarSamples,
+        rows: &[u32],
+        attribute_index: u16,
+        attribute_range: (u16, u16)
+    ) -> Histogram {
+
+        let (min_value, max_value) = attribute_range;
+        let column = columns.column(attribute_index);
+
+        let mut bins = vec![LabelStats::empty(columns.task()); (max_value - min_value) as usize + 1];
+        let mut missing = LabelStats::empty(columns.task());
+
+        for row in rows {
+            let value = column[*row as usize];
+            if value == MISSING {
+                missing.add(&columns.row(*row));
+            } else {
+                bins[(value - min_value) as usize].add(&columns.row(*row));
+            }
+        }
+
+        Histogram { task: columns.task(), min_value, bins, missing }
+    }
+
+    // Histogram of the sibling node, which holds the rows of the parent that are not in this node
+    pub fn sibling(&self, parent: &Histogram) -> Histogram {
+        assert_eq!(self.min_value, parent.min_value);
+        assert_eq!(self.bins.len(), parent.bins.len());
+
+        let bins = parent.bins.iter()
+            .zip(self.bins.iter())
+            .map(|(parent_bin, bin)| subtracted(parent_bin, bin))
+            .collect();
+
+        let missing = subtracted(&parent.missing, &self.missing);
+
+        Histogram { task: self.task, min_value: self.min_value, bins, missing }
+    }
+
+    pub fn has_missing(&self) -> bool {
+        self.missing.total() > 0
+    }
+
+    pub fn split_stats(&self, split: &Split) -> SplitStats {
+        let mut left = LabelStats::empty(self.task);
+        let mut right = LabelStats::empty(self.task);
+
+        if split.missing_left() {
+            left = left.merged(&self.missing);
+        } else {
+            right = right.merged(&self.missing);
+        }
+
+        for (offset, bin) in self.bins.iter().enumerate() {
+            if bin.total() == 0 {
+                continue;
+            }
+
+            if split.is_left(self.min_value + offset as u16) {
+                left = left.merged(bin);
+            } else {
+                right = right.merged(bin);
+            }
+        }
+
+        SplitStats::from_label_stats(left, right)
+    }
+}
+
+fn subtracted(stats: &LabelStats, other: &LabelStats) -> LabelStats {
+    match (stats, other) {
+        (LabelStats::Classes(counts), LabelStats::Classes(other_counts)) =>
+            LabelStats::Classes(counts.subtracted(other_counts)),
+        _ => panic!("Only class counts can be subtracted!"),
+    }
+}
+
+// Histograms of the attributes at a node, they are only built for the attributes of candidate
+// splits and reused for all retries and alternative subtrees at the node
+pub struct Histograms {
+    histograms: Vec<Option<Histogram>>,
+}
+
+impl Histograms {
+
+    pub fn new(num_attributes: u16) -> Histograms {
+        Histograms { histograms: vec![None; num_attributes as usize] }
+    }
+
+    // Deriving stats from a histogram only beats a scan if there are fewer values than rows
+    pub fn pays_off(attribute_range: (u16, u16), num_rows: usize) -> bool {
+        let (min_value, max_value) = attribute_range;
+        ((max_value - min_value) as usize) < num_rows
+    }
+
+    pub fn get_or_build<D: Dataset>(
+        &mut self,
+        dataset: &D,
+        columns: &ColumnarSamples,
+        rows: &[u32],
+        attribute_index: u16
+    ) -> &Histogram {
+        self.histograms[attribute_index as usize].get_or_insert_with(|| {
+            Histogram::from_rows(columns, rows, attribute_index, dataset.attribute_range(attribute_index))
+        })
+    }
+
+    // Only the histograms of the smaller child are built from its rows, the ones of the larger
+    // child are the difference to the parent. Regression histograms cannot be subtracted, as the
+    // extreme targets of the larger child are unknown, so the children build their own ones.
+    pub fn for_children<D: Dataset>(
+        &self,
+        dataset: &D,
+        columns: &ColumnarSamples,
+        rows_left: &[u32],
+        rows_right: &[u32]
+    ) -> (Histograms, Histograms) {
+
+        let mut histograms_left = Histograms::new(self.histograms.len() as u16);
+        let mut histograms_right = Histograms::new(self.histograms.len() as u16);
+
+        if columns.task() == Task::Regression {
+            return (histograms_left, histograms_right);
+        }
+
+        let left_is_smaller = rows_left.len() <= rows_right.len();
+        let (smaller_rows, larger_rows) =
+            if left_is_smaller { (rows_left, rows_right) } else { (rows_right, rows_left) };
+
+        for (attribute_index, histogram) in self.histograms.iter().enumerate() {
+
+            let attribute_range = dataset.attribute_range(attribute_index as u16);
+
+            if let Some(parent) = histogram {
+                if !Histograms::pays_off(attribute_range, larger_rows.len()) {
+                    continue;
+                }
+
+                let smaller = Histogram::from_rows(columns, smaller_rows, attribute_index as u16, attribute_range);
+                let larger = smaller.sibling(parent);
+
+                let smaller = Some(smaller).filter(|_| Histograms::pays_off(attribute_range, smaller_rows.len()));
+
+                if left_is_smaller {
+                    histograms_left.histograms[attribute_index] = smaller;
+                    histograms_right.histograms[attribute_index] = Some(larger);
+                } else {
+                    histograms_left.histograms[attribute_index] = Some(larger);
+                    histograms_right.histograms[attribute_index] = smaller;
+                }
+            }
+        }
+
+        (histograms_left, histograms_right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::columnar::ColumnarSamples;
+    use crate::dataset::Dataset;
+    use crate::histogram::{Histogram, Histograms};
+    use crate::scan::scan_labels;
+    use crate::tree::{CategorySet, Split};
+    use crate::testing::{synthetic_samples, synthetic_samples_with_classes, synthetic_regression_samples};
+
+    fn splits() -> Vec<Split> {
+        vec![
+            Split::Numerical { attribute_index: 0, cut_off: 0, missing_left: false },
+            Split::Numerical { attribute_index: 0, cut_off: 7, missing_left: false },
+            Split::Numerical { attribute_index: 1, cut_off: 5, missing_left: false },
+            Split::Categorical { attribute_index: 2, subset: CategorySet::from(0b1010), missing_left: false },
+            Split::Categorical { attribute_index: 2, subset: CategorySet::new(), missing_left: false },
+        ]
+    }
+
+    #[test]
+    fn histogram_stats_match_scans() {
+        let datasets = vec![synthetic_samples(500), synthetic_samples_with_classes(500, 4)];
+
+        for (dataset, samples) in datasets {
+            let columns = ColumnarSamples::from_samples(&dataset, &samples);
+            let rows = columns.all_rows();
+            let mut histograms = Histograms::new(dataset.num_attributes());
+
+            for split in splits() {
+                let histogram = histograms.get_or_build(&dataset, &columns, &rows, split.attribute_index());
+                let stats = histogram.split_stats(&split);
+                let scanned_stats = scan_labels(&samples, &split, dataset.task());
+
+                assert_eq!((stats.left, stats.right), (scanned_stats.left, scanned_stats.right));
+            }
+        }
+
+        let (dataset, samples) = synthetic_regression_samples(500);
+        let columns = ColumnarSamples::from_samples(&dataset, &samples);
+
+        for split in splits() {
+            let range = dataset.attribute_range(split.attribute_index());
+            let histogram = Histogram::from_rows(&columns, &columns.all_rows(), split.attribute_index(), range);
+
+            let stats = histogram.split_stats(&split);
+            let scanned_stats = scan_labels(&samples, &split, dataset.task());
+
+            for (side, scanned_side) in [(stats.left, scanned_stats.left), (stats.right, scanned_stats.right)].iter() {
+                let (targets, scanned_targets) = (side.target_stats(), scanned_side.target_stats());
+                assert_eq!(targets.count, scanned_targets.count);
+                assert_eq!((targets.min, targets.max), (scanned_targets.min, scanned_targets.max));
+                assert!((targets.sum - scanned_targets.sum).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn sibling_histograms_match_built_ones() {
+        let (dataset, samples) = synthetic_samples_with_classes(700, 3);
+        let columns = ColumnarSamples::from_samples(&dataset, &samples);
+        let rows = columns.all_rows();
+
+        let mut histograms = Histograms::new(dataset.num_attributes());
+        for attribute_index in 0..dataset.num_attributes() {
+            histograms.get_or_build(&dataset, &columns, &rows, attribute_index);
+        }
+
+        let (rows_left, rows_right): (Vec<u32>, Vec<u32>) = rows.iter().partition(|row| *row % 3 == 0);
+        let (histograms_left, histograms_right) =
+            histograms.for_children(&dataset, &columns, &rows_left, &rows_right);
+
+        for (child_rows, mut child_histograms) in [(rows_left, histograms_left), (rows_right, histograms_right)] {
+            assert!(child_histograms.histograms.iter().all(|histogram| histogram.is_some()));
+
+            let child_samples: Vec<_> = child_rows.iter().map(|row| samples[*row as usize].clone()).collect();
+
+            for split in splits() {
+                let histogram = child_histograms.get_or_build(&dataset, &columns, &child_rows, split.attribute_index());
+                let stats = histogram.split_stats(&split);
+                let scanned_stats = scan_labels(&child_samples, &split, dataset.task());
+
+                assert_eq!((stats.left, stats.right), (scanned_stats.left, scanned_stats.right));
+            }
+        }
+    }
+}
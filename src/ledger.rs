@@ -0,0 +1,88 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hashbrown::HashMap;
+use serde::{Serialize, Deserialize};
+
+#[derive(Eq,PartialEq,Debug,Clone,Serialize,Deserialize)]
+pub struct LedgerEntry {
+    pub record_id: u64,
+    // Milliseconds since the unix epoch
+    pub forgotten_at: u64,
+}
+
+// Append-only record of the samples which have been forgotten from a model, in the order in
+// which the deletion requests were applied
+#[derive(Debug,Default,Serialize,Deserialize)]
+pub struct DeletionLedger {
+    entries: Vec<LedgerEntry>,
+    positions: HashMap<u64, usize>,
+}
+
+impl DeletionLedger {
+
+    pub fn new() -> DeletionLedger {
+        DeletionLedger::default()
+    }
+
+    pub(crate) fn record(&mut self, record_id: u64) {
+        assert!(!self.contains(record_id), "Record {} has already been forgotten!", record_id);
+
+        let forgotten_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        self.positions.insert(record_id, self.entries.len());
+        self.entries.push(LedgerEntry { record_id, forgotten_at });
+    }
+
+    pub fn contains(&self, record_id: u64) -> bool {
+        self.positions.contains_key(&record_id)
+    }
+
+    pub fn get(&self, record_id: u64) -> Option<&LedgerEntry> {
+        self.positions.get(&record_id).map(|position| &self.entries[*position])
+    }
+
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Writes the ledger as tab separated file with the header "record_id forgotten_at"
+    pub fn to_csv(&self, file: &str) {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(b'\t')
+            .from_path(file)
+            .unwrap();
+
+        writer.write_record(["record_id", "forgotten_at"]).unwrap();
+
+        for entry in &self.entries {
+            writer.write_record(&[entry.record_id.to_string(), entry.forgotten_at.to_string()]).unwrap();
+        }
+
+        writer.flush().unwrap();
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.positions.len() != self.entries.len() {
+            return Err("Deletion ledger contains duplicate records".to_string());
+        }
+
+        for (position, entry) in self.entries.iter().enumerate() {
+            if self.positions.get(&entry.record_id) != Some(&position) {
+                return Err(format!("Deletion ledger index is inconsistent for record {}", entry.record_id));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,11 +1,25 @@
 extern crate csv;
 extern crate rand;
+extern crate rand_xorshift;
 extern crate rayon;
 extern crate hashbrown;
+extern crate serde;
+extern crate bincode;
+extern crate serde_json;
 
 pub mod dataset;
 pub mod tree;
 pub mod evaluation;
 pub mod split_stats;
 pub mod scan;
+pub mod columnar;
+pub mod histogram;
+pub mod schema;
+pub mod preprocessing;
+pub mod persistence;
+pub mod ledger;
+pub mod config;
+pub mod bootstrap;
 mod utils;
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,198 @@
+use std::fmt;
+use std::fs;
+use std::convert::TryInto;
+
+use serde::{Serialize, Deserialize};
+
+use crate::tree::ExtremelyRandomizedTrees;
+
+// Binary models start with the magic bytes, followed by the format version as little endian u32
+// and the bincode encoded forest. Bump the version whenever the layout of the serialized types
+// changes, models written with other versions are rejected at load time.
+pub const FORMAT_VERSION: u32 = 1;
+const MAGIC: &[u8; 8] = b"HEDGECUT";
+const HEADER_SIZE: usize = 12;
+
+#[derive(Debug)]
+pub enum PersistenceError {
+    Io(std::io::Error),
+    NotAModel,
+    UnsupportedVersion(u32),
+    Corrupt(String),
+    Invalid(String),
+}
+
+impl fmt::Display for PersistenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PersistenceError::Io(error) => write!(f, "I/O error: {}", error),
+            PersistenceError::NotAModel => write!(f, "Not a hedgecut model"),
+            PersistenceError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported model format version {}, expected {}",
+                version,
+                FORMAT_VERSION
+            ),
+            PersistenceError::Corrupt(reason) => write!(f, "Corrupt model: {}", reason),
+            PersistenceError::Invalid(reason) => write!(f, "Invalid model: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PersistenceError {}
+
+impl From<std::io::Error> for PersistenceError {
+    fn from(error: std::io::Error) -> Self {
+        PersistenceError::Io(error)
+    }
+}
+
+#[derive(Serialize)]
+struct JsonModelRef<'a> {
+    format_version: u32,
+    model: &'a ExtremelyRandomizedTrees,
+}
+
+// The format version is checked before the model is deserialized
+#[derive(Deserialize)]
+struct JsonModel {
+    model: ExtremelyRandomizedTrees,
+}
+
+impl ExtremelyRandomizedTrees {
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+
+        bincode::serialize_into(&mut bytes, self).unwrap();
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ExtremelyRandomizedTrees, PersistenceError> {
+
+        if bytes.len() < HEADER_SIZE || &bytes[0..MAGIC.len()] != MAGIC {
+            return Err(PersistenceError::NotAModel);
+        }
+
+        let version = u32::from_le_bytes(bytes[MAGIC.len()..HEADER_SIZE].try_into().unwrap());
+        if version != FORMAT_VERSION {
+            return Err(PersistenceError::UnsupportedVersion(version));
+        }
+
+        let model: ExtremelyRandomizedTrees = bincode::deserialize(&bytes[HEADER_SIZE..])
+            .map_err(|error| PersistenceError::Corrupt(error.to_string()))?;
+
+        model.validate().map_err(PersistenceError::Invalid)?;
+
+        Ok(model)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&JsonModelRef { format_version: FORMAT_VERSION, model: self }).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<ExtremelyRandomizedTrees, PersistenceError> {
+
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|error| PersistenceError::Corrupt(error.to_string()))?;
+
+        match value.get("format_version").and_then(|version| version.as_u64()) {
+            Some(version) if version == FORMAT_VERSION as u64 => {},
+            Some(version) => return Err(PersistenceError::UnsupportedVersion(version as u32)),
+            None => return Err(PersistenceError::NotAModel),
+        }
+
+        let json_model: JsonModel = serde_json::from_value(value)
+            .map_err(|error| PersistenceError::Corrupt(error.to_string()))?;
+
+        json_model.model.validate().map_err(PersistenceError::Invalid)?;
+
+        Ok(json_model.model)
+    }
+
+    pub fn save(&self, file: &str) -> Result<(), PersistenceError> {
+        fs::write(file, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(file: &str) -> Result<ExtremelyRandomizedTrees, PersistenceError> {
+        ExtremelyRandomizedTrees::from_bytes(&fs::read(file)?)
+    }
+
+    pub fn save_json(&self, file: &str) -> Result<(), PersistenceError> {
+        fs::write(file, self.to_json())?;
+        Ok(())
+    }
+
+    pub fn load_json(file: &str) -> Result<ExtremelyRandomizedTrees, PersistenceError> {
+        ExtremelyRandomizedTrees::from_json(&fs::read_to_string(file)?)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.trees.is_empty() {
+            return Err("Forest has no trees".to_string());
+        }
+
+        for tree in &self.trees {
+            tree.validate()?;
+        }
+
+        self.validate_class_weights()?;
+        self.validate_records()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::persistence::PersistenceError;
+    use crate::testing::synthetic_samples;
+    use crate::tree::ExtremelyRandomizedTrees;
+
+    #[test]
+    fn roundtrip_preserves_predictions_and_forgetting() {
+        let (dataset, samples) = synthetic_samples(2000);
+
+        let mut trees = ExtremelyRandomizedTrees::fit_with_epsilon(&dataset, samples.clone(), 42, 10, 2, 5, 0.01);
+
+        let mut from_binary = ExtremelyRandomizedTrees::from_bytes(&trees.to_bytes()).unwrap();
+        let mut from_json = ExtremelyRandomizedTrees::from_json(&trees.to_json()).unwrap();
+
+        for sample in samples.iter().take(20) {
+            trees.forget(sample).unwrap();
+            from_binary.forget(sample).unwrap();
+            from_json.forget(sample).unwrap();
+        }
+
+        for sample in &samples {
+            let prediction = trees.predict(sample);
+            assert_eq!(prediction, from_binary.predict(sample));
+            assert_eq!(prediction, from_json.predict(sample));
+        }
+    }
+
+    #[test]
+    fn rejects_foreign_and_outdated_models() {
+        let (dataset, samples) = synthetic_samples(200);
+
+        let trees = ExtremelyRandomizedTrees::fit(&dataset, samples, 42, 2, 2, 5);
+        let mut bytes = trees.to_bytes();
+
+        assert!(matches!(
+            ExtremelyRandomizedTrees::from_bytes(b"not a model at all"),
+            Err(PersistenceError::NotAModel)
+        ));
+
+        let truncated = &bytes[..bytes.len() / 2];
+        assert!(matches!(ExtremelyRandomizedTrees::from_bytes(truncated), Err(PersistenceError::Corrupt(_))));
+
+        bytes[8] = bytes[8].wrapping_add(1);
+        assert!(matches!(
+            ExtremelyRandomizedTrees::from_bytes(&bytes),
+            Err(PersistenceError::UnsupportedVersion(_))
+        ));
+    }
+}
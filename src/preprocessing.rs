@@ -0,0 +1,647 @@
+use std::str::FromStr;
+
+use crate::dataset::{AttributeType, Task, MISSING};
+use crate::schema::{Schema, AttributeSpec, RowSample};
+use crate::split_stats::{MINUS, PLUS};
+
+// Cells with these values are treated as missing
+const MISSING_VALUES: [&str; 5] = ["", "?", "NA", "NaN", "nan"];
+
+#[derive(PartialEq,Debug,Clone)]
+pub enum Encoding {
+    Quantile(usize),
+    EqualWidth(usize),
+    Ordinal,
+    Binary(String),
+    Label(String),
+    Ignore,
+}
+
+#[derive(PartialEq,Debug,Clone)]
+pub struct ColumnSpec {
+    pub name: String,
+    pub encoding: Encoding,
+}
+
+// A spec file is tab separated with the header "column encoding parameter", the parameter is
+// the number of bins for "quantile" and "equal_width" and the positive value for "binary" and
+// "label". If the raw data has no header, the spec has to list all of its columns in order.
+pub fn specs_from_file(file: &str) -> Vec<ColumnSpec> {
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(b'\t')
+        .flexible(true)
+        .from_path(file)
+        .unwrap();
+
+    reader.records()
+        .map(|result| {
+            let record = result.unwrap();
+
+            let name = record.get(0).unwrap().to_string();
+            let parameter = record.get(2);
+
+            let encoding = match record.get(1).unwrap() {
+                "quantile" => Encoding::Quantile(usize::from_str(parameter.unwrap()).unwrap()),
+                "equal_width" => Encoding::EqualWidth(usize::from_str(parameter.unwrap()).unwrap()),
+                "ordinal" => Encoding::Ordinal,
+                "binary" => Encoding::Binary(parameter.unwrap().to_string()),
+                "label" => Encoding::Label(parameter.unwrap().to_string()),
+                "ignore" => Encoding::Ignore,
+                other => panic!("Unknown encoding {} for column {} in {}!", other, name, file)
+            };
+
+            ColumnSpec { name, encoding }
+        })
+        .collect()
+}
+
+pub struct RawTable {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl RawTable {
+
+    // Reads the header from the file unless column names are given
+    pub fn from_csv(file: &str, delimiter: u8, column_names: Option<Vec<String>>) -> RawTable {
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(column_names.is_none())
+            .delimiter(delimiter)
+            .trim(csv::Trim::All)
+            .from_path(file)
+            .unwrap();
+
+        let columns = match column_names {
+            Some(names) => names,
+            None => reader.headers().unwrap().iter().map(|name| name.to_string()).collect(),
+        };
+
+        let rows = reader.records()
+            .map(|result| result.unwrap().iter().map(|value| value.to_string()).collect())
+            .collect();
+
+        RawTable { columns, rows }
+    }
+
+    pub fn column(&self, name: &str) -> usize {
+        self.columns.iter().position(|column| column == name)
+            .unwrap_or_else(|| panic!("Column {} not found!", name))
+    }
+
+    fn numeric_values(&self, column: usize, rows: &[usize]) -> Vec<f64> {
+        rows.iter()
+            .filter_map(|row| parse_number(&self.rows[*row][column]))
+            .collect()
+    }
+}
+
+pub(crate) fn is_missing(value: &str) -> bool {
+    MISSING_VALUES.contains(&value)
+}
+
+// Values like "inf" or "NAN" parse as numbers, but are disregarded like missing values
+fn parse_number(value: &str) -> Option<f64> {
+    if is_missing(value) {
+        None
+    } else {
+        f64::from_str(value).ok().filter(|number| number.is_finite())
+    }
+}
+
+// Encoded values are u16 and MISSING is reserved, so there are at most 65535 bins or categories
+// per column
+const MAX_CODES: usize = MISSING as usize;
+
+#[derive(PartialEq,Debug,Clone)]
+pub enum ColumnEncoder {
+    // Inner and outer bin edges, like the ones of sklearn's KBinsDiscretizer
+    Discretizer { edges: Vec<f64> },
+    Ordinal { categories: Vec<String> },
+    Binary { positive_value: String },
+}
+
+impl ColumnEncoder {
+
+    // A column without values gets a single bin, like a constant column
+    pub fn fit_quantile(values: &[f64], num_bins: usize) -> ColumnEncoder {
+        assert!(num_bins > 0 && num_bins <= MAX_CODES);
+
+        if values.is_empty() {
+            return ColumnEncoder::Discretizer { edges: remove_narrow_bins(vec![0.0]) };
+        }
+
+        let mut sorted_values = values.to_vec();
+        sorted_values.sort_by(|a, b| a.total_cmp(b));
+
+        let quantiles: Vec<f64> = (0..=num_bins)
+            .map(|bin| percentile(&sorted_values, bin as f64 / num_bins as f64))
+            .collect();
+
+        ColumnEncoder::Discretizer { edges: remove_narrow_bins(quantiles) }
+    }
+
+    pub fn fit_equal_width(values: &[f64], num_bins: usize) -> ColumnEncoder {
+        assert!(num_bins > 0 && num_bins <= MAX_CODES);
+
+        if values.is_empty() {
+            return ColumnEncoder::Discretizer { edges: remove_narrow_bins(vec![0.0]) };
+        }
+
+        let min_value = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max_value = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let width = (max_value - min_value) / num_bins as f64;
+
+        let edges: Vec<f64> = (0..=num_bins)
+            .map(|bin| min_value + bin as f64 * width)
+            .collect();
+
+        ColumnEncoder::Discretizer { edges: remove_narrow_bins(edges) }
+    }
+
+    // Like sklearn's LabelEncoder, the categories are sorted, numerically if possible. A column
+    // without values has no categories and encodes every value as missing.
+    pub fn fit_ordinal(values: &[&str]) -> ColumnEncoder {
+        let mut categories: Vec<String> = values.iter()
+            .filter(|value| !is_missing(value))
+            .map(|value| value.to_string())
+            .collect();
+
+        if categories.iter().all(|category| f64::from_str(category).is_ok()) {
+            categories.sort_by(|a, b| {
+                f64::from_str(a).unwrap().total_cmp(&f64::from_str(b).unwrap())
+            });
+        } else {
+            categories.sort();
+        }
+        categories.dedup();
+
+        assert!(categories.len() <= MAX_CODES, "Unsupported number of categories!");
+
+        ColumnEncoder::Ordinal { categories }
+    }
+
+    pub fn encode(&self, value: &str) -> Option<u16> {
+        if is_missing(value) {
+            return None;
+        }
+
+        match self {
+            ColumnEncoder::Discretizer { edges } => {
+                let number = f64::from_str(value).ok()?;
+                if !number.is_finite() {
+                    return Some(MISSING);
+                }
+                let inner_edges = &edges[1..edges.len() - 1];
+                let bin = inner_edges.iter().filter(|edge| **edge <= number).count();
+                Some(bin as u16)
+            },
+            // Unseen categories are treated like missing values
+            ColumnEncoder::Ordinal { categories } => {
+                let code = categories.iter().position(|category| category == value)
+                    .map(|code| code as u16)
+                    .unwrap_or(MISSING);
+                Some(code)
+            },
+            ColumnEncoder::Binary { positive_value } => Some((value == positive_value) as u16),
+        }
+    }
+
+    pub fn max_code(&self) -> u16 {
+        match self {
+            ColumnEncoder::Discretizer { edges } => (edges.len() - 2) as u16,
+            ColumnEncoder::Ordinal { categories } => categories.len().saturating_sub(1) as u16,
+            ColumnEncoder::Binary { positive_value: _ } => 1,
+        }
+    }
+
+    pub fn attribute_type(&self) -> AttributeType {
+        match self {
+            ColumnEncoder::Discretizer { edges: _ } => AttributeType::Numerical,
+            ColumnEncoder::Ordinal { categories: _ } => AttributeType::Categorical,
+            ColumnEncoder::Binary { positive_value: _ } => AttributeType::Categorical,
+        }
+    }
+}
+
+// Linear interpolation between the closest ranks, as in numpy's percentile
+fn percentile(sorted_values: &[f64], fraction: f64) -> f64 {
+    let rank = fraction * (sorted_values.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+
+    sorted_values[lower] + (sorted_values[upper] - sorted_values[lower]) * (rank - lower as f64)
+}
+
+fn remove_narrow_bins(edges: Vec<f64>) -> Vec<f64> {
+    let mut distinct_edges: Vec<f64> = Vec::with_capacity(edges.len());
+
+    for edge in edges {
+        match distinct_edges.last() {
+            Some(previous) if edge - previous <= 1e-8 => {},
+            _ => distinct_edges.push(edge),
+        }
+    }
+
+    // A constant column still needs a single bin
+    if distinct_edges.len() == 1 {
+        distinct_edges.push(distinct_edges[0]);
+    }
+
+    distinct_edges
+}
+
+#[derive(PartialEq,Debug,Clone)]
+pub struct Preprocessor {
+    pub columns: Vec<(String, ColumnEncoder)>,
+    pub label_column: String,
+    pub positive_label: String,
+}
+
+impl Preprocessor {
+
+    // All encoders are fitted on the training rows only, categories which only occur in the test
+    // data are encoded as missing. Missing values are disregarded when fitting the encoders.
+    pub fn fit(specs: &[ColumnSpec], table: &RawTable, training_rows: &[usize]) -> Preprocessor {
+
+        let mut columns = Vec::new();
+        let mut label: Option<(String, String)> = None;
+
+        for spec in specs {
+            let column = table.column(&spec.name);
+
+            let encoder = match &spec.encoding {
+                Encoding::Quantile(num_bins) => {
+                    ColumnEncoder::fit_quantile(&table.numeric_values(column, training_rows), *num_bins)
+                },
+                Encoding::EqualWidth(num_bins) => {
+                    ColumnEncoder::fit_equal_width(&table.numeric_values(column, training_rows), *num_bins)
+                },
+                Encoding::Ordinal => {
+                    let values: Vec<&str> = training_rows.iter()
+                        .map(|row| table.rows[*row][column].as_str())
+                        .collect();
+                    ColumnEncoder::fit_ordinal(&values)
+                },
+                Encoding::Binary(positive_value) => {
+                    ColumnEncoder::Binary { positive_value: positive_value.clone() }
+                },
+                Encoding::Label(positive_value) => {
+                    assert!(label.is_none(), "More than one label column specified!");
+                    label = Some((spec.name.clone(), positive_value.clone()));
+                    continue;
+                },
+                Encoding::Ignore => continue,
+            };
+
+            columns.push((spec.name.clone(), encoder));
+        }
+
+        let (label_column, positive_label) = label.expect("No label column specified!");
+
+        Preprocessor { columns, label_column, positive_label }
+    }
+
+    pub fn schema(&self) -> Schema {
+        let attributes = self.columns.iter()
+            .map(|(name, encoder)| {
+                assert!(encoder.max_code() < MISSING, "Too many codes for column {}!", name);
+                AttributeSpec {
+                    name: name.clone(),
+                    attribute_type: encoder.attribute_type(),
+                    min_value: 0,
+                    max_value: encoder.max_code(),
+                }
+            })
+            .collect();
+
+        Schema {
+            attributes,
+            label_column: self.label_column.clone(),
+            id_column: Some("record_id".to_string()),
+            num_classes: 2,
+            task: Task::Classification,
+            weight_column: None,
+        }
+    }
+
+    // Missing values are encoded as MISSING. Returns None if the label is missing or if the row has
+    // values which cannot be encoded.
+    pub fn transform_row(&self, record_id: u64, columns: &[String], row: &[String]) -> Option<RowSample> {
+
+        let value_of = |name: &str| -> Option<&str> {
+            columns.iter().position(|column| column == name).map(|index| row[index].as_str())
+        };
+
+        let mut values = Vec::with_capacity(self.columns.len());
+        for (name, encoder) in &self.columns {
+            let value = value_of(name)?;
+            if is_missing(value) {
+                values.push(MISSING);
+            } else {
+                values.push(encoder.encode(value)?);
+            }
+        }
+
+        let label_value = value_of(&self.label_column)?;
+        if is_missing(label_value) {
+            return None;
+        }
+
+        let label = if label_value == self.positive_label { PLUS } else { MINUS };
+
+        Some(RowSample { record_id, values, label, target: label as f64, weight: 1 })
+    }
+
+    // Record ids are the positions of the rows in the raw table
+    pub fn transform(&self, table: &RawTable, rows: &[usize]) -> Vec<RowSample> {
+        rows.iter()
+            .filter_map(|row| self.transform_row(*row as u64, &table.columns, &table.rows[*row]))
+            .collect()
+    }
+
+    pub fn to_file(&self, file: &str) {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(b'\t')
+            .flexible(true)
+            .from_path(file)
+            .unwrap();
+
+        for (name, encoder) in &self.columns {
+            let mut record = vec![name.clone()];
+
+            match encoder {
+                ColumnEncoder::Discretizer { edges } => {
+                    record.push("discretizer".to_string());
+                    record.extend(edges.iter().map(|edge| edge.to_string()));
+                },
+                ColumnEncoder::Ordinal { categories } => {
+                    record.push("ordinal".to_string());
+                    record.extend(categories.iter().cloned());
+                },
+                ColumnEncoder::Binary { positive_value } => {
+                    record.push("binary".to_string());
+                    record.push(positive_value.clone());
+                },
+            }
+
+            writer.write_record(&record).unwrap();
+        }
+
+        writer.write_record([&self.label_column, "label", &self.positive_label]).unwrap();
+        writer.flush().unwrap();
+    }
+
+    pub fn from_file(file: &str) -> Preprocessor {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(b'\t')
+            .flexible(true)
+            .from_path(file)
+            .unwrap();
+
+        let mut columns = Vec::new();
+        let mut label: Option<(String, String)> = None;
+
+        for result in reader.records() {
+            let record = result.unwrap();
+
+            let name = record.get(0).unwrap().to_string();
+            let parameters: Vec<String> = record.iter().skip(2).map(|value| value.to_string()).collect();
+
+            let encoder = match record.get(1).unwrap() {
+                "discretizer" => ColumnEncoder::Discretizer {
+                    edges: parameters.iter().map(|edge| f64::from_str(edge).unwrap()).collect()
+                },
+                "ordinal" => ColumnEncoder::Ordinal { categories: parameters },
+                "binary" => ColumnEncoder::Binary { positive_value: parameters[0].clone() },
+                "label" => {
+                    label = Some((name, parameters[0].clone()));
+                    continue;
+                },
+                other => panic!("Unknown encoder {} in {}!", other, file)
+            };
+
+            columns.push((name, encoder));
+        }
+
+        let (label_column, positive_label) =
+            label.unwrap_or_else(|| panic!("No label column found in {}!", file));
+
+        Preprocessor { columns, label_column, positive_label }
+    }
+}
+
+// Writes samples in the tab separated format which the loaders in this crate expect
+pub fn samples_to_csv(schema: &Schema, samples: &[RowSample], file: &str) {
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .from_path(file)
+        .unwrap();
+
+    let mut header = vec!["record_id".to_string()];
+    header.extend(schema.attributes.iter().map(|attribute| attribute.name.clone()));
+    header.push(schema.label_column.clone());
+    header.extend(schema.weight_column.iter().cloned());
+    writer.write_record(&header).unwrap();
+
+    for sample in samples {
+        let mut record = vec![sample.record_id.to_string()];
+        record.extend(sample.values.iter().map(|value| {
+            if *value == MISSING { String::new() } else { value.to_string() }
+        }));
+        match schema.task {
+            Task::Classification => record.push(sample.label.to_string()),
+            Task::Regression => record.push(sample.target.to_string()),
+        }
+        if schema.weight_column.is_some() {
+            record.push(sample.weight.to_string());
+        }
+        writer.write_record(&record).unwrap();
+    }
+
+    writer.flush().unwrap();
+}
+
+pub fn column_names(specs: &[ColumnSpec]) -> Vec<String> {
+    specs.iter().map(|spec| spec.name.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::dataset::MISSING;
+    use crate::preprocessing::{ColumnEncoder, ColumnSpec, Encoding, Preprocessor, RawTable, samples_to_csv};
+    use crate::schema::SchemaDataset;
+    use crate::split_stats::PLUS;
+
+    #[test]
+    fn quantile_bins_like_sklearn() {
+        let values: Vec<f64> = (1..=10).map(|value| value as f64).collect();
+
+        let encoder = ColumnEncoder::fit_quantile(&values, 4);
+
+        assert_eq!(encoder, ColumnEncoder::Discretizer { edges: vec![1.0, 3.25, 5.5, 7.75, 10.0] });
+        assert_eq!(encoder.encode("1"), Some(0));
+        assert_eq!(encoder.encode("3.25"), Some(1));
+        assert_eq!(encoder.encode("7"), Some(2));
+        assert_eq!(encoder.encode("100"), Some(3));
+        assert_eq!(encoder.encode("-5"), Some(0));
+        assert_eq!(encoder.encode("?"), None);
+        assert_eq!(encoder.max_code(), 3);
+
+        // Duplicate edges collapse into a single bin
+        let skewed = vec![0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 2.0];
+        let encoder = ColumnEncoder::fit_quantile(&skewed, 4);
+        assert_eq!(encoder.max_code(), 1);
+    }
+
+    #[test]
+    fn persisted_preprocessor_transforms_identically() {
+        let values: Vec<f64> = (0..100).map(|value| (value * value) as f64).collect();
+
+        let preprocessor = Preprocessor {
+            columns: vec![
+                ("amount".to_string(), ColumnEncoder::fit_quantile(&values, 16)),
+                ("city".to_string(), ColumnEncoder::fit_ordinal(&["b", "a", "c", "a"])),
+                ("weekend".to_string(), ColumnEncoder::Binary { positive_value: "TRUE".to_string() }),
+            ],
+            label_column: "income".to_string(),
+            positive_label: ">50K".to_string(),
+        };
+
+        let file = std::env::temp_dir().join("hedgecut-preprocessor-test.tsv");
+        preprocessor.to_file(file.to_str().unwrap());
+        let loaded = Preprocessor::from_file(file.to_str().unwrap());
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(preprocessor, loaded);
+
+        let columns: Vec<String> = ["income", "weekend", "city", "amount"].iter()
+            .map(|name| name.to_string())
+            .collect();
+        let row: Vec<String> = [">50K", "FALSE", "c", "1234.5"].iter()
+            .map(|value| value.to_string())
+            .collect();
+
+        let sample = loaded.transform_row(0, &columns, &row).unwrap();
+        assert_eq!(sample, preprocessor.transform_row(0, &columns, &row).unwrap());
+        assert_eq!(sample.values[1], 2);
+        assert_eq!(sample.values[2], 0);
+        assert_eq!(sample.label, PLUS);
+
+        let unseen_category: Vec<String> = [">50K", "FALSE", "d", "1234.5"].iter()
+            .map(|value| value.to_string())
+            .collect();
+        assert_eq!(loaded.transform_row(1, &columns, &unseen_category).unwrap().values[1], MISSING);
+    }
+
+    #[test]
+    fn missing_values_survive_the_round_trip() {
+        let preprocessor = Preprocessor {
+            columns: vec![
+                ("amount".to_string(), ColumnEncoder::fit_quantile(&[1.0, 2.0, 3.0, 4.0], 2)),
+                ("city".to_string(), ColumnEncoder::fit_ordinal(&["b", "a", "NA"])),
+            ],
+            label_column: "income".to_string(),
+            positive_label: ">50K".to_string(),
+        };
+
+        let columns: Vec<String> = ["amount", "city", "income"].iter().map(|name| name.to_string()).collect();
+        let rows: Vec<Vec<String>> = [["?", "b", ">50K"], ["3", "", "<=50K"], ["1", "a", "NA"]].iter()
+            .map(|row| row.iter().map(|value| value.to_string()).collect())
+            .collect();
+
+        let samples: Vec<_> = rows.iter().enumerate()
+            .filter_map(|(record_id, row)| preprocessor.transform_row(record_id as u64, &columns, row))
+            .collect();
+
+        // Rows without a label are still dropped
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].values, vec![MISSING, 1]);
+        assert_eq!(samples[1].values, vec![1, MISSING]);
+
+        let schema = preprocessor.schema();
+        let file = std::env::temp_dir().join("hedgecut-missing-values-test.tsv");
+        samples_to_csv(&schema, &samples, file.to_str().unwrap());
+        let loaded = SchemaDataset::samples_from_csv(&schema, file.to_str().unwrap());
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(loaded, samples);
+    }
+
+    #[test]
+    fn categories_are_fitted_on_the_training_rows_only() {
+        let table = RawTable {
+            columns: vec!["city".to_string(), "income".to_string()],
+            rows: [["b", ">50K"], ["a", "<=50K"], ["c", ">50K"]].iter()
+                .map(|row| row.iter().map(|value| value.to_string()).collect())
+                .collect(),
+        };
+
+        let specs = vec![
+            ColumnSpec { name: "city".to_string(), encoding: Encoding::Ordinal },
+            ColumnSpec { name: "income".to_string(), encoding: Encoding::Label(">50K".to_string()) },
+        ];
+
+        let preprocessor = Preprocessor::fit(&specs, &table, &[0, 1]);
+
+        assert_eq!(preprocessor.columns[0].1, ColumnEncoder::fit_ordinal(&["a", "b"]));
+
+        let samples = preprocessor.transform(&table, &[0, 1, 2]);
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].values, vec![1]);
+        assert_eq!(samples[1].values, vec![0]);
+        assert_eq!(samples[2].values, vec![MISSING]);
+    }
+
+    #[test]
+    fn missing_and_non_finite_values_are_disregarded_when_fitting() {
+        let table = RawTable {
+            columns: vec!["amount".to_string(), "rate".to_string(), "city".to_string(), "income".to_string()],
+            rows: [["NA", "1.5", "", ">50K"], ["?", "inf", "NA", "<=50K"], ["", "NAN", "?", ">50K"]].iter()
+                .map(|row| row.iter().map(|value| value.to_string()).collect())
+                .collect(),
+        };
+
+        let specs = vec![
+            ColumnSpec { name: "amount".to_string(), encoding: Encoding::Quantile(4) },
+            ColumnSpec { name: "rate".to_string(), encoding: Encoding::EqualWidth(4) },
+            ColumnSpec { name: "city".to_string(), encoding: Encoding::Ordinal },
+            ColumnSpec { name: "income".to_string(), encoding: Encoding::Label(">50K".to_string()) },
+        ];
+
+        let preprocessor = Preprocessor::fit(&specs, &table, &[0, 1, 2]);
+
+        assert!(preprocessor.columns.iter().all(|(_, encoder)| encoder.max_code() == 0));
+        assert_eq!(preprocessor.schema().attributes.len(), 3);
+
+        let samples = preprocessor.transform(&table, &[0, 1, 2]);
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].values, vec![MISSING, 0, MISSING]);
+        assert_eq!(samples[1].values, vec![MISSING, MISSING, MISSING]);
+        assert_eq!(samples[2].values, vec![MISSING, MISSING, MISSING]);
+
+        // Numbers like "nan" or "inf" do not break the numeric sort of the categories
+        let encoder = ColumnEncoder::fit_ordinal(&["2", "inf", "NAN", "1"]);
+        assert_eq!(encoder.encode("1"), Some(0));
+        assert_eq!(encoder.encode("2"), Some(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many codes")]
+    fn codes_do_not_collide_with_missing_values() {
+        let categories = (0..=MISSING as usize).map(|category| category.to_string()).collect();
+
+        let preprocessor = Preprocessor {
+            columns: vec![("id".to_string(), ColumnEncoder::Ordinal { categories })],
+            label_column: "income".to_string(),
+            positive_label: ">50K".to_string(),
+        };
+
+        preprocessor.schema();
+    }
+}
@@ -1,7 +1,10 @@
+#[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
+use std::sync::OnceLock;
 
-use crate::dataset::Sample;
-use crate::split_stats::SplitStats;
+use crate::columnar::ColumnarSamples;
+use crate::dataset::{Sample, Task};
+use crate::split_stats::{SplitStats, LabelStats};
 use crate::tree::Split;
 
 pub fn scan_with_branches<S: Sample>(
@@ -101,12 +104,10 @@ fn simd_sum_mlpack(labels: &[bool]) -> [usize; 2] {
     /*
        counts += counts2 + counts3 + counts4;
     */
-    let all_counts = [
+    [
         counts[0] + counts2[0] + counts3[0] + counts4[0],
         counts[1] + counts2[1] + counts3[1] + counts4[1],
-    ];
-
-    all_counts
+    ]
 }
 
 pub fn scan_mlpack<S: Sample>(
@@ -173,226 +174,635 @@ pub fn scan<S: Sample>(
     SplitStats::new(num_plus_left, num_minus_left, num_plus_right, num_minus_right)
 }
 
-pub fn scan_simd_numerical<S: Sample>(
+pub fn scan_labels<S: Sample>(
     samples: &[S],
     split: &Split,
+    task: Task,
 ) -> SplitStats {
 
-    let (attribute_index, cut_off) = match split {
-        Split::Numerical { attribute_index, cut_off } => (*attribute_index, *cut_off as i8),
-        Split::Categorical { attribute_index: _, subset: _ } => {
-            panic!("Don't call this method with a categorical split!")
+    let mut left = LabelStats::empty(task);
+    let mut right = LabelStats::empty(task);
+
+    for sample in samples {
+        if sample.is_left_of(split) {
+            left.add(sample);
+        } else {
+            right.add(sample);
         }
-    };
+    }
 
-    let mut offset = 0;
-    let batch_size = 16;
+    SplitStats::from_label_stats(left, right)
+}
 
-    let mut num_left: isize = 0;
-    let mut num_plus_left: isize = 0;
-    let mut num_plus_right: isize = 0;
-
-    unsafe {
-
-        let cut_off_batch = _mm_set1_epi8(cut_off);
-        let additional_samples = samples.len() % batch_size;
-
-        while offset < samples.len() - additional_samples {
-
-            let attribute_values_batch = _mm_set_epi8(
-                samples.get_unchecked(offset + 0).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 1).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 2).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 3).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 4).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 5).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 6).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 7).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 8).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 9).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 10).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 11).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 12).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 13).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 14).attribute_value(attribute_index) as i8,
-                samples.get_unchecked(offset + 15).attribute_value(attribute_index) as i8,
-            );
-
-            let is_plus_batch = _mm_set_epi8(
-                samples.get_unchecked(offset + 0).true_label() as i8,
-                samples.get_unchecked(offset + 1).true_label() as i8,
-                samples.get_unchecked(offset + 2).true_label() as i8,
-                samples.get_unchecked(offset + 3).true_label() as i8,
-                samples.get_unchecked(offset + 4).true_label() as i8,
-                samples.get_unchecked(offset + 5).true_label() as i8,
-                samples.get_unchecked(offset + 6).true_label() as i8,
-                samples.get_unchecked(offset + 7).true_label() as i8,
-                samples.get_unchecked(offset + 8).true_label() as i8,
-                samples.get_unchecked(offset + 9).true_label() as i8,
-                samples.get_unchecked(offset + 10).true_label() as i8,
-                samples.get_unchecked(offset + 11).true_label() as i8,
-                samples.get_unchecked(offset + 12).true_label() as i8,
-                samples.get_unchecked(offset + 13).true_label() as i8,
-                samples.get_unchecked(offset + 14).true_label() as i8,
-                samples.get_unchecked(offset + 15).true_label() as i8,
-            );
-
-            let shifted_is_plus_batch = _mm_slli_epi16(is_plus_batch, 7);
-
-            let is_left_batch = _mm_cmplt_epi8(attribute_values_batch, cut_off_batch);
-
-            let plus_left_batch = _mm_and_si128(is_left_batch, shifted_is_plus_batch);
-            let plus_right_batch = _mm_andnot_si128(is_left_batch, shifted_is_plus_batch);
-
-            let left_result = _mm_movemask_epi8(is_left_batch);
-            let plus_left_result = _mm_movemask_epi8(plus_left_batch);
-            let plus_right_result = _mm_movemask_epi8(plus_right_batch);
-
-            num_left += left_result.count_ones() as isize;
-            num_plus_left += plus_left_result.count_ones() as isize;
-            num_plus_right += plus_right_result.count_ones() as isize;
-
-            offset += batch_size;
-        }
+// Instruction set used by the SIMD scans, the fastest one supported by the cpu is picked at runtime
+#[derive(Eq,PartialEq,Debug,Clone,Copy)]
+pub enum ScanKernel {
+    Avx2,
+    Sse2,
+    Scalar,
+}
 
-        if offset < samples.len() {
+impl ScanKernel {
 
-            // Process last samples without SIMD
-            while offset < samples.len() {
+    // The cpu features are only detected on the first call
+    pub fn detect() -> ScanKernel {
+        static DETECTED: OnceLock<ScanKernel> = OnceLock::new();
 
-                let sample = samples.get_unchecked(offset);
-                let is_left = sample.is_left_of(split);
-                let is_plus = sample.true_label();
+        *DETECTED.get_or_init(|| {
+            ScanKernel::supported().into_iter().next().unwrap()
+        })
+    }
 
-                num_left += is_left as isize;
-                num_plus_left += (is_left & is_plus) as isize;
-                num_plus_right += (!is_left & is_plus) as isize;
+    pub fn is_supported(&self) -> bool {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            ScanKernel::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "x86_64")]
+            ScanKernel::Sse2 => is_x86_feature_detected!("sse2"),
+            ScanKernel::Scalar => true,
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
 
-                offset += 1;
-            }
+    pub fn supported() -> Vec<ScanKernel> {
+        [ScanKernel::Avx2, ScanKernel::Sse2, ScanKernel::Scalar].iter()
+            .filter(|kernel| kernel.is_supported())
+            .cloned()
+            .collect()
+    }
+
+    // Whether the categorical scan of the kernel handles values up to the given one
+    pub fn supports_categories(&self, max_value: u16) -> bool {
+        match self {
+            ScanKernel::Avx2 => max_value < 64,
+            ScanKernel::Sse2 => max_value < 32,
+            ScanKernel::Scalar => true,
         }
+    }
 
-        let num_minus_left: u32 = (num_left - num_plus_left) as u32;
-        let num_minus_right: u32 =
-            ((samples.len() - num_left as usize) as u32) - num_plus_right as u32;
+    // Whether the kernel can look up the values of the split, other splits are scanned without SIMD
+    fn handles(&self, split: &Split) -> bool {
+        match split {
+            Split::Numerical { attribute_index: _, cut_off: _, missing_left: _ } => true,
+            Split::Categorical { attribute_index: _, subset, missing_left: _ } =>
+                subset.max_value().is_none_or(|max_value| self.supports_categories(max_value)),
+        }
+    }
+
+    // This kernel or the fastest slower one which handles categorical values up to the given one.
+    // The slower kernels are supported whenever this one is.
+    pub fn for_categories(&self, max_value: u16) -> ScanKernel {
+        [ScanKernel::Avx2, ScanKernel::Sse2, ScanKernel::Scalar].iter()
+            .skip_while(|kernel| *kernel != self)
+            .find(|kernel| kernel.supports_categories(max_value))
+            .cloned()
+            .unwrap()
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    num_left: usize,
+    num_plus_left: usize,
+    num_plus_right: usize,
+}
+
+impl Counts {
+
+    #[inline(always)]
+    fn add(&mut self, is_left: bool, is_plus: bool) {
+        self.num_left += is_left as usize;
+        self.num_plus_left += (is_left & is_plus) as usize;
+        self.num_plus_right += (!is_left & is_plus) as usize;
+    }
+
+    fn into_stats(self, num_samples: usize) -> SplitStats {
+        let num_minus_left = self.num_left - self.num_plus_left;
+        let num_minus_right = (num_samples - self.num_left) - self.num_plus_right;
 
         SplitStats::new(
-            num_plus_left as u32,
-            num_minus_left,
-            num_plus_right as u32,
-            num_minus_right,
+            self.num_plus_left as u32,
+            num_minus_left as u32,
+            self.num_plus_right as u32,
+            num_minus_right as u32,
         )
     }
 }
 
+pub fn scan_simd_numerical<S: Sample>(
+    samples: &[S],
+    split: &Split,
+) -> SplitStats {
+    scan_numerical_with(ScanKernel::detect(), samples, split)
+}
+
+pub fn scan_numerical_with<S: Sample>(
+    kernel: ScanKernel,
+    samples: &[S],
+    split: &Split,
+) -> SplitStats {
+
+    if let Split::Categorical { attribute_index: _, subset: _, missing_left: _ } = split {
+        panic!("Don't call this method with a categorical split!")
+    }
+
+    scan_samples_with(kernel, samples, split)
+}
+
 pub fn scan_simd_categorical<S: Sample>(
     samples: &[S],
     split: &Split,
 ) -> SplitStats {
+    scan_categorical_with(ScanKernel::detect(), samples, split)
+}
+
+// Subsets with values the kernel does not support, see supports_categories, are scanned without SIMD
+pub fn scan_categorical_with<S: Sample>(
+    kernel: ScanKernel,
+    samples: &[S],
+    split: &Split,
+) -> SplitStats {
+
+    if let Split::Numerical { attribute_index: _, cut_off: _, missing_left: _ } = split {
+        panic!("Don't call this method with a numerical split!");
+    }
+
+    scan_samples_with(kernel, samples, split)
+}
+
+fn scan_samples_with<S: Sample>(
+    kernel: ScanKernel,
+    samples: &[S],
+    split: &Split,
+) -> SplitStats {
+
+    assert!(kernel.is_supported(), "Scan kernel {:?} is not supported by this cpu!", kernel);
+
+    let attribute_index = split.attribute_index();
+
+    scan_lanes(
+        kernel,
+        split,
+        samples.len(),
+        |index| samples[index].attribute_value(attribute_index),
+        |index| samples[index].true_label(),
+    )
+}
+
+// Scans the rows of the columnar samples, the AVX2 kernel gathers the values and label bits of
+// eight rows at a time directly from the columns
+pub fn scan_columns(
+    kernel: ScanKernel,
+    samples: &ColumnarSamples,
+    rows: &[u32],
+    split: &Split,
+) -> SplitStats {
+
+    assert!(kernel.is_supported(), "Scan kernel {:?} is not supported by this cpu!", kernel);
+
+    let column = samples.column(split.attribute_index());
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        if kernel == ScanKernel::Avx2 && !split.missing_left() && kernel.handles(split) {
+            let mut counts = Counts::default();
+            let num_processed = unsafe { gather_avx2(samples, rows, split, &mut counts) };
+
+            for row in &rows[num_processed..] {
+                counts.add(split.is_left(column[*row as usize]), samples.is_plus(*row));
+            }
 
-    let (attribute_index, subset) = match split {
-        Split::Numerical { attribute_index: _, cut_off: _ } => {
-            panic!("Don't call this method with a numerical split!");
+            return counts.into_stats(rows.len());
         }
-        Split::Categorical { attribute_index, subset } => {
-            (*attribute_index, *subset)
+    }
+
+    scan_lanes(
+        kernel,
+        split,
+        rows.len(),
+        |index| column[rows[index] as usize],
+        |index| samples.is_plus(rows[index]),
+    )
+}
+
+// Label statistics of the rows of the columnar samples, for labels which the kernels do not count
+pub fn scan_column_labels(
+    samples: &ColumnarSamples,
+    rows: &[u32],
+    split: &Split,
+) -> SplitStats {
+
+    let column = samples.column(split.attribute_index());
+
+    let mut left = LabelStats::empty(samples.task());
+    let mut right = LabelStats::empty(samples.task());
+
+    for row in rows {
+        if split.is_left(column[*row as usize]) {
+            left.add(&samples.row(*row));
+        } else {
+            right.add(&samples.row(*row));
         }
-    };
+    }
 
-    let mut offset = 0;
-    let batch_size = 4;
+    SplitStats::from_label_stats(left, right)
+}
 
-    let mut num_left: isize = 0;
-    let mut num_plus_left: isize = 0;
-    let mut num_plus_right: isize = 0;
+// Runs the SIMD kernel on full batches of samples and processes the remaining ones without SIMD
+fn scan_lanes<V, P>(
+    kernel: ScanKernel,
+    split: &Split,
+    num_samples: usize,
+    value_of: V,
+    is_plus: P,
+) -> SplitStats
+    where V: Fn(usize) -> u16, P: Fn(usize) -> bool
+{
+    let mut counts = Counts::default();
+    let num_processed = scan_batches(kernel, split, num_samples, &value_of, &is_plus, &mut counts);
+
+    for index in num_processed..num_samples {
+        counts.add(split.is_left(value_of(index)), is_plus(index));
+    }
 
-    unsafe {
+    counts.into_stats(num_samples)
+}
 
-        let subset_batch = _mm_set1_epi32(subset as i32);
-        let no_match_batch = _mm_set1_epi32(0);
-        let indexes_batch = _mm_set1_epi32(1);
+// The kernels return the number of samples which they processed. They send missing values to the
+// right, so splits which send them left are left to the scalar scan, as are subsets with values
+// the kernel cannot look up.
+#[cfg(target_arch = "x86_64")]
+fn scan_batches<V, P>(
+    kernel: ScanKernel,
+    split: &Split,
+    num_samples: usize,
+    value_of: &V,
+    is_plus: &P,
+    counts: &mut Counts,
+) -> usize
+    where V: Fn(usize) -> u16, P: Fn(usize) -> bool
+{
+    if split.missing_left() || !kernel.handles(split) {
+        return 0;
+    }
+
+    match (kernel, split) {
+        (ScanKernel::Avx2, Split::Numerical { attribute_index: _, cut_off, missing_left: _ }) => unsafe {
+            numerical_avx2(num_samples, *cut_off, value_of, is_plus, counts)
+        },
+        (ScanKernel::Sse2, Split::Numerical { attribute_index: _, cut_off, missing_left: _ }) => unsafe {
+            numerical_sse2(num_samples, *cut_off, value_of, is_plus, counts)
+        },
+        (ScanKernel::Avx2, Split::Categorical { attribute_index: _, subset, missing_left: _ }) => unsafe {
+            categorical_avx2(num_samples, subset.lower_bits(), value_of, is_plus, counts)
+        },
+        (ScanKernel::Sse2, Split::Categorical { attribute_index: _, subset, missing_left: _ }) => unsafe {
+            categorical_sse2(num_samples, subset.lower_bits(), value_of, is_plus, counts)
+        },
+        _ => 0,
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn scan_batches<V, P>(
+    _kernel: ScanKernel,
+    _split: &Split,
+    _num_samples: usize,
+    _value_of: &V,
+    _is_plus: &P,
+    _counts: &mut Counts,
+) -> usize
+    where V: Fn(usize) -> u16, P: Fn(usize) -> bool
+{
+    0
+}
 
-        let additional_samples = samples.len() % batch_size;
+// Flips the sign bit, so that signed comparisons order the values like unsigned ones
+#[cfg(target_arch = "x86_64")]
+fn biased(value: u16) -> i16 {
+    (value ^ 0x8000) as i16
+}
+
+// The byte masks of comparisons on 16 bit lanes hold two bits per lane, we only keep the upper one
+#[cfg(target_arch = "x86_64")]
+const UPPER_BYTES: u32 = 0xAAAA_AAAA;
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn numerical_avx2<V, P>(
+    num_samples: usize,
+    cut_off: u16,
+    value_of: &V,
+    is_plus: &P,
+    counts: &mut Counts,
+) -> usize
+    where V: Fn(usize) -> u16, P: Fn(usize) -> bool
+{
+    let batch_size = 16;
+    let num_batched = num_samples - num_samples % batch_size;
 
-        while offset < samples.len() - additional_samples {
+    let mut attribute_values = [0_i16; 16];
+    let mut plus_labels = [0_i16; 16];
 
-            let attribute_values_batch = _mm_set_epi32(
-                samples.get_unchecked(offset + 0).attribute_value(attribute_index) as i32,
-                samples.get_unchecked(offset + 1).attribute_value(attribute_index) as i32,
-                samples.get_unchecked(offset + 2).attribute_value(attribute_index) as i32,
-                samples.get_unchecked(offset + 3).attribute_value(attribute_index) as i32,
-            );
+    let cut_off_batch = _mm256_set1_epi16(biased(cut_off));
 
-            let is_plus_batch = _mm_set_epi32(
-                samples.get_unchecked(offset + 0).true_label() as i32,
-                samples.get_unchecked(offset + 1).true_label() as i32,
-                samples.get_unchecked(offset + 2).true_label() as i32,
-                samples.get_unchecked(offset + 3).true_label() as i32,
-            );
+    for offset in (0..num_batched).step_by(batch_size) {
 
-            let positions = _mm_sllv_epi32(indexes_batch, attribute_values_batch);
+        for lane in 0..batch_size {
+            attribute_values[lane] = biased(value_of(offset + lane));
+            plus_labels[lane] = is_plus(offset + lane) as i16;
+        }
+
+        let attribute_values_batch = _mm256_loadu_si256(attribute_values.as_ptr() as *const __m256i);
+        let is_plus_batch = _mm256_loadu_si256(plus_labels.as_ptr() as *const __m256i);
 
-            let is_in = _mm_and_si128(positions, subset_batch);
-            let is_left_batch = _mm_cmpeq_epi32(is_in, no_match_batch);
-            let shifted_is_plus_batch = _mm_slli_epi32(is_plus_batch, 15);
+        let shifted_is_plus_batch = _mm256_slli_epi16(is_plus_batch, 15);
 
-            let plus_left_batch = _mm_andnot_si128(is_left_batch, shifted_is_plus_batch);
-            let plus_right_batch = _mm_and_si128(is_left_batch, shifted_is_plus_batch);
+        let is_left_batch = _mm256_cmpgt_epi16(cut_off_batch, attribute_values_batch);
 
-            let left_result = _mm_movemask_epi8(is_left_batch);
-            let plus_left_result = _mm_movemask_epi8(plus_left_batch);
-            let plus_right_result = _mm_movemask_epi8(plus_right_batch);
+        let left_result = _mm256_movemask_epi8(is_left_batch) as u32 & UPPER_BYTES;
+        let plus_result = _mm256_movemask_epi8(shifted_is_plus_batch) as u32;
 
-            num_left += (4 - left_result.count_ones()  / 4) as isize;
-            num_plus_left += plus_left_result.count_ones() as isize;
-            num_plus_right += plus_right_result.count_ones() as isize;
+        counts.num_left += left_result.count_ones() as usize;
+        counts.num_plus_left += (left_result & plus_result).count_ones() as usize;
+        counts.num_plus_right += (!left_result & plus_result).count_ones() as usize;
+    }
 
-            offset += batch_size;
+    num_batched
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+unsafe fn numerical_sse2<V, P>(
+    num_samples: usize,
+    cut_off: u16,
+    value_of: &V,
+    is_plus: &P,
+    counts: &mut Counts,
+) -> usize
+    where V: Fn(usize) -> u16, P: Fn(usize) -> bool
+{
+    let batch_size = 8;
+    let num_batched = num_samples - num_samples % batch_size;
+
+    let mut attribute_values = [0_i16; 8];
+    let mut plus_labels = [0_i16; 8];
+
+    let cut_off_batch = _mm_set1_epi16(biased(cut_off));
+
+    for offset in (0..num_batched).step_by(batch_size) {
+
+        for lane in 0..batch_size {
+            attribute_values[lane] = biased(value_of(offset + lane));
+            plus_labels[lane] = is_plus(offset + lane) as i16;
         }
 
-        if offset < samples.len() {
+        let attribute_values_batch = _mm_loadu_si128(attribute_values.as_ptr() as *const __m128i);
+        let is_plus_batch = _mm_loadu_si128(plus_labels.as_ptr() as *const __m128i);
 
-            // Process last samples without SIMD
-            while offset < samples.len() {
+        let shifted_is_plus_batch = _mm_slli_epi16(is_plus_batch, 15);
 
-                let sample = samples.get_unchecked(offset);
-                let is_left = sample.is_left_of(split);
-                let is_plus = sample.true_label();
+        let is_left_batch = _mm_cmplt_epi16(attribute_values_batch, cut_off_batch);
 
-                num_left += is_left as isize;
-                num_plus_left += (is_left & is_plus) as isize;
-                num_plus_right += (!is_left & is_plus) as isize;
+        let left_result = _mm_movemask_epi8(is_left_batch) as u32 & UPPER_BYTES;
+        let plus_result = _mm_movemask_epi8(shifted_is_plus_batch) as u32;
 
-                offset += 1;
-            }
+        counts.num_left += left_result.count_ones() as usize;
+        counts.num_plus_left += (left_result & plus_result).count_ones() as usize;
+        counts.num_plus_right += (!left_result & plus_result).count_ones() as usize;
+    }
+
+    num_batched
+}
+
+// Looks up the membership of 32 values at a time with two byte shuffles: the first one picks the
+// byte of the subset which holds the bit of a value, the second one the position of the bit. The
+// values have to be below 64.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn categorical_avx2<V, P>(
+    num_samples: usize,
+    subset: u64,
+    value_of: &V,
+    is_plus: &P,
+    counts: &mut Counts,
+) -> usize
+    where V: Fn(usize) -> u16, P: Fn(usize) -> bool
+{
+    let batch_size = 32;
+    let num_batched = num_samples - num_samples % batch_size;
+
+    let mut attribute_values = [0_u8; 32];
+    let mut plus_labels = [0_i8; 32];
+
+    // The shuffles work within 128 bit lanes, so both tables are repeated in the upper lane. The
+    // upper half of the subset table stays empty, values from 64 to 127 are never in the subset.
+    let mut subset_table = [0_u8; 32];
+    subset_table[..8].copy_from_slice(&subset.to_le_bytes());
+    subset_table[16..24].copy_from_slice(&subset.to_le_bytes());
+
+    let bit_table: [u8; 32] = [
+        1, 2, 4, 8, 16, 32, 64, 128, 1, 2, 4, 8, 16, 32, 64, 128,
+        1, 2, 4, 8, 16, 32, 64, 128, 1, 2, 4, 8, 16, 32, 64, 128,
+    ];
+
+    let subset_table_batch = _mm256_loadu_si256(subset_table.as_ptr() as *const __m256i);
+    let bit_table_batch = _mm256_loadu_si256(bit_table.as_ptr() as *const __m256i);
+    let byte_index_mask = _mm256_set1_epi8(0x0F);
+    let bit_index_mask = _mm256_set1_epi8(7);
+
+    for offset in (0..num_batched).step_by(batch_size) {
+
+        for lane in 0..batch_size {
+            // Values outside of the subset range and missing values become 255, whose bit is never
+            // set in the empty upper half of the table
+            let value = value_of(offset + lane);
+            attribute_values[lane] = if value < 64 { value as u8 } else { 255 };
+            plus_labels[lane] = is_plus(offset + lane) as i8;
         }
 
-        let num_minus_left: u32 = (num_left - num_plus_left) as u32;
-        let num_minus_right: u32 =
-            ((samples.len() - num_left as usize) as u32) - num_plus_right as u32;
+        let attribute_values_batch = _mm256_loadu_si256(attribute_values.as_ptr() as *const __m256i);
+        let is_plus_batch = _mm256_loadu_si256(plus_labels.as_ptr() as *const __m256i);
 
-        SplitStats::new(
-            num_plus_left as u32,
-            num_minus_left,
-            num_plus_right as u32,
-            num_minus_right,
-        )
+        // There is no byte shift, the mask removes the bits shifted in from the neighboring byte
+        let byte_indexes = _mm256_and_si256(_mm256_srli_epi16(attribute_values_batch, 3), byte_index_mask);
+        let bit_indexes = _mm256_and_si256(attribute_values_batch, bit_index_mask);
+
+        let subset_bytes = _mm256_shuffle_epi8(subset_table_batch, byte_indexes);
+        let bits = _mm256_shuffle_epi8(bit_table_batch, bit_indexes);
+
+        let is_right_batch = _mm256_cmpeq_epi8(_mm256_and_si256(subset_bytes, bits), _mm256_setzero_si256());
+        let shifted_is_plus_batch = _mm256_slli_epi16(is_plus_batch, 7);
+
+        let right_result = _mm256_movemask_epi8(is_right_batch) as u32;
+        let plus_result = _mm256_movemask_epi8(shifted_is_plus_batch) as u32;
+
+        counts.num_left += (!right_result).count_ones() as usize;
+        counts.num_plus_left += (!right_result & plus_result).count_ones() as usize;
+        counts.num_plus_right += (right_result & plus_result).count_ones() as usize;
     }
+
+    num_batched
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+unsafe fn categorical_sse2<V, P>(
+    num_samples: usize,
+    subset: u64,
+    value_of: &V,
+    is_plus: &P,
+    counts: &mut Counts,
+) -> usize
+    where V: Fn(usize) -> u16, P: Fn(usize) -> bool
+{
+    let batch_size = 4;
+    let num_batched = num_samples - num_samples % batch_size;
+
+    let mut attribute_values = [0_i32; 4];
+    let mut plus_labels = [0_i32; 4];
+
+    let subset_batch = _mm_set1_epi32(subset as i32);
+    let no_match_batch = _mm_set1_epi32(0);
+    let exponent_bias_batch = _mm_set1_epi32(127);
+
+    for offset in (0..num_batched).step_by(batch_size) {
+
+        for lane in 0..batch_size {
+            // Values outside of the subset range and missing values get a zero exponent below,
+            // which yields a position in no subset
+            let value = value_of(offset + lane);
+            attribute_values[lane] = if value < 32 { value as i32 } else { -127 };
+            plus_labels[lane] = is_plus(offset + lane) as i32;
+        }
+
+        let attribute_values_batch = _mm_loadu_si128(attribute_values.as_ptr() as *const __m128i);
+        let is_plus_batch = _mm_loadu_si128(plus_labels.as_ptr() as *const __m128i);
+
+        // SSE2 has no variable shifts, so we compute 1 << value via the exponent of a float
+        let exponents = _mm_slli_epi32(_mm_add_epi32(attribute_values_batch, exponent_bias_batch), 23);
+        let positions = _mm_cvttps_epi32(_mm_castsi128_ps(exponents));
+
+        let is_in = _mm_and_si128(positions, subset_batch);
+        let is_right_batch = _mm_cmpeq_epi32(is_in, no_match_batch);
+        let shifted_is_plus_batch = _mm_slli_epi32(is_plus_batch, 31);
+
+        let plus_left_batch = _mm_andnot_si128(is_right_batch, shifted_is_plus_batch);
+        let plus_right_batch = _mm_and_si128(is_right_batch, shifted_is_plus_batch);
+
+        let right_result = _mm_movemask_ps(_mm_castsi128_ps(is_right_batch));
+
+        counts.num_left += (4 - right_result.count_ones()) as usize;
+        counts.num_plus_left += _mm_movemask_ps(_mm_castsi128_ps(plus_left_batch)).count_ones() as usize;
+        counts.num_plus_right += _mm_movemask_ps(_mm_castsi128_ps(plus_right_batch)).count_ones() as usize;
+    }
+
+    num_batched
+}
+
+// Gathers the values of eight rows from the padded column and their label bits from the bitmap.
+// The subsets of categorical splits have to be below 64, larger values are in no subset.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn gather_avx2(
+    samples: &ColumnarSamples,
+    rows: &[u32],
+    split: &Split,
+    counts: &mut Counts,
+) -> usize {
+
+    let batch_size = 8;
+    let num_batched = rows.len() - rows.len() % batch_size;
+
+    let column = samples.padded_column(split.attribute_index()).as_ptr() as *const i32;
+    let label_bits = samples.padded_label_bits().as_ptr() as *const i32;
+
+    let value_mask = _mm256_set1_epi32(0xFFFF);
+    let bit_index_mask = _mm256_set1_epi32(7);
+    let ones = _mm256_set1_epi32(1);
+
+    let thirty_two = _mm256_set1_epi32(32);
+
+    // Categorical values below 32 are looked up in the lower and all others in the upper half of
+    // the subset, variable shifts by 32 or more bits yield zero
+    let (threshold, subset_halves) = match split {
+        Split::Numerical { attribute_index: _, cut_off, missing_left: _ } => (_mm256_set1_epi32(*cut_off as i32), None),
+        Split::Categorical { attribute_index: _, subset, missing_left: _ } => {
+            let bits = subset.lower_bits();
+            let lower_half = _mm256_set1_epi32(bits as u32 as i32);
+            let upper_half = _mm256_set1_epi32((bits >> 32) as u32 as i32);
+            (ones, Some((lower_half, upper_half)))
+        },
+    };
+
+    for offset in (0..num_batched).step_by(batch_size) {
+
+        let rows_batch = _mm256_loadu_si256(rows.as_ptr().add(offset) as *const __m256i);
+
+        let attribute_values_batch =
+            _mm256_and_si256(_mm256_i32gather_epi32(column, rows_batch, 2), value_mask);
+
+        let label_bytes = _mm256_i32gather_epi32(label_bits, _mm256_srli_epi32(rows_batch, 3), 1);
+        let labels = _mm256_srlv_epi32(label_bytes, _mm256_and_si256(rows_batch, bit_index_mask));
+        let is_plus_batch = _mm256_cmpeq_epi32(_mm256_and_si256(labels, ones), ones);
+
+        let is_left_batch = match subset_halves {
+            None => _mm256_cmpgt_epi32(threshold, attribute_values_batch),
+            Some((lower_half, upper_half)) => {
+                let lower_positions = _mm256_sllv_epi32(ones, attribute_values_batch);
+                let upper_positions = _mm256_sllv_epi32(ones, _mm256_sub_epi32(attribute_values_batch, thirty_two));
+
+                let is_in = _mm256_or_si256(
+                    _mm256_and_si256(lower_positions, lower_half),
+                    _mm256_and_si256(upper_positions, upper_half)
+                );
+                let is_right = _mm256_cmpeq_epi32(is_in, _mm256_setzero_si256());
+                _mm256_xor_si256(is_right, _mm256_set1_epi32(-1))
+            },
+        };
+
+        let left_result = _mm256_movemask_ps(_mm256_castsi256_ps(is_left_batch)) as u32;
+        let plus_result = _mm256_movemask_ps(_mm256_castsi256_ps(is_plus_batch)) as u32;
+
+        counts.num_left += left_result.count_ones() as usize;
+        counts.num_plus_left += (left_result & plus_result).count_ones() as usize;
+        counts.num_plus_right += (!left_result & plus_result).count_ones() as usize;
+    }
+
+    num_batched
 }
 
 #[cfg(test)]
 mod tests {
 
-    use crate::dataset::{GiveMeSomeCreditDataset, ShoppingDataset};
-    use crate::tree::Split;
-    use crate::scan::{scan, scan_simd_numerical, scan_simd_categorical, scan_mlpack};
+    use rand::{Rng, SeedableRng};
+    use rand::seq::SliceRandom;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::dataset::{AttributeType, Dataset, GiveMeSomeCreditDataset, ShoppingDataset, Task};
+    use crate::schema::{AttributeSpec, RowSample, Schema, SchemaDataset};
+    use crate::split_stats::SplitStats;
+    use crate::tree::{CategorySet, Split};
+    use crate::scan::{scan, scan_mlpack, scan_numerical_with, scan_categorical_with, scan_columns, scan_column_labels,
+        scan_labels, ScanKernel};
+    use crate::columnar::ColumnarSamples;
+    use crate::testing::{synthetic_samples, synthetic_high_cardinality_samples, synthetic_samples_with_missing_values,
+        synthetic_samples_with_classes, synthetic_regression_samples};
+
+    fn assert_same_counts(stats: &SplitStats, other_stats: &SplitStats) {
+        assert_eq!(stats.num_plus_left(), other_stats.num_plus_left());
+        assert_eq!(stats.num_plus_right(), other_stats.num_plus_right());
+        assert_eq!(stats.num_minus_left(), other_stats.num_minus_left());
+        assert_eq!(stats.num_minus_right(), other_stats.num_minus_right());
+    }
 
     #[test]
     fn mlpack_impl() {
         let samples =
             GiveMeSomeCreditDataset::samples_from_csv("datasets/givemesomecredit-train.csv");
 
-        let split = Split::Numerical { attribute_index: 3, cut_off: 5 };
+        let split = Split::Numerical { attribute_index: 3, cut_off: 5, missing_left: false };
 
         let stats = scan(&samples, &split);
         let stats_mlpack = scan_mlpack(&samples, &split);
@@ -400,10 +810,10 @@ mod tests {
         eprintln!("{:?}", stats);
         eprintln!("{:?}", stats_mlpack);
 
-        assert_eq!(stats.num_plus_left, stats_mlpack.num_plus_left);
-        assert_eq!(stats.num_plus_right, stats_mlpack.num_plus_right);
-        assert_eq!(stats.num_minus_left, stats_mlpack.num_minus_left);
-        assert_eq!(stats.num_minus_right, stats_mlpack.num_minus_right);
+        assert_eq!(stats.num_plus_left(), stats_mlpack.num_plus_left());
+        assert_eq!(stats.num_plus_right(), stats_mlpack.num_plus_right());
+        assert_eq!(stats.num_minus_left(), stats_mlpack.num_minus_left());
+        assert_eq!(stats.num_minus_right(), stats_mlpack.num_minus_right());
     }
 
     #[test]
@@ -411,15 +821,14 @@ mod tests {
         let samples =
             GiveMeSomeCreditDataset::samples_from_csv("datasets/givemesomecredit-train.csv");
 
-        let split = Split::Numerical { attribute_index: 3, cut_off: 5 };
+        let split = Split::Numerical { attribute_index: 3, cut_off: 5, missing_left: false };
 
         let stats = scan(&samples, &split);
-        let stats_simd = scan_simd_numerical(&samples, &split);
 
-        assert_eq!(stats.num_plus_left, stats_simd.num_plus_left);
-        assert_eq!(stats.num_plus_right, stats_simd.num_plus_right);
-        assert_eq!(stats.num_minus_left, stats_simd.num_minus_left);
-        assert_eq!(stats.num_minus_right, stats_simd.num_minus_right);
+        for kernel in ScanKernel::supported() {
+            let stats_simd = scan_numerical_with(kernel, &samples, &split);
+            assert_same_counts(&stats, &stats_simd);
+        }
     }
 
     #[test]
@@ -427,24 +836,225 @@ mod tests {
         let samples =
             ShoppingDataset::samples_from_csv("datasets/shopping-train.csv");
 
-        let possible_values = vec![0, 7, 12];
+        let subset: CategorySet = vec![0, 7, 12].into_iter().collect();
+
+        let split = Split::Categorical { attribute_index: 14, subset, missing_left: false };
 
-        let mut subset: u64 = 0;
-        for bit_to_set in possible_values.iter() {
-            subset |= 1_u64 << *bit_to_set as u64
+        let stats = scan(&samples, &split);
+
+        for kernel in ScanKernel::supported() {
+            let stats_simd = scan_categorical_with(kernel, &samples, &split);
+
+            println!("SCAN {:?}", stats);
+            println!("SIMD {:?} {:?}", kernel, stats_simd);
+
+            assert_same_counts(&stats, &stats_simd);
+        }
+    }
+
+    #[test]
+    fn all_kernels_agree_on_random_samples() {
+        let mut rng = XorShiftRng::from_seed([3; 16]);
+
+        // Not a multiple of any batch size, and with byte values above 127
+        let samples: Vec<RowSample> = (0..1003)
+            .map(|record_id| {
+                let label = rng.gen_range(0, 2);
+                RowSample {
+                    record_id,
+                    values: vec![rng.gen_range(0, 256), rng.gen_range(0, 32), rng.gen_range(0, 64)],
+                    label,
+                    target: label as f64,
+                    weight: 1
+                }
+            })
+            .collect();
+
+        assert!(ScanKernel::supported().contains(&ScanKernel::detect()));
+
+        for cut_off in [0, 1, 100, 128, 200, 255].iter() {
+            let split = Split::Numerical { attribute_index: 0, cut_off: *cut_off, missing_left: false };
+            let stats = scan(&samples, &split);
+
+            for kernel in ScanKernel::supported() {
+                assert_same_counts(&stats, &scan_numerical_with(kernel, &samples, &split));
+            }
+        }
+
+        for subset in [0_u64, 1, 0b1010_0110, 1 << 31, 0xFFFF_FFFF].iter() {
+            let split = Split::Categorical { attribute_index: 1, subset: CategorySet::from(*subset), missing_left: false };
+            let stats = scan(&samples, &split);
+
+            for kernel in ScanKernel::supported() {
+                assert_same_counts(&stats, &scan_categorical_with(kernel, &samples, &split));
+            }
+        }
+
+        for subset in [1_u64 << 63, 0xFFFF_FFFF_0000_0000, 0x8000_0001_0000_0002, u64::MAX].iter() {
+            let split = Split::Categorical { attribute_index: 2, subset: CategorySet::from(*subset), missing_left: false };
+            let stats = scan(&samples, &split);
+
+            for kernel in ScanKernel::supported().into_iter().filter(|kernel| kernel.supports_categories(63)) {
+                assert_same_counts(&stats, &scan_categorical_with(kernel, &samples, &split));
+            }
+        }
+
+        assert!(ScanKernel::detect().for_categories(63).supports_categories(63));
+        assert_eq!(ScanKernel::Avx2.for_categories(40), ScanKernel::Avx2);
+        assert_eq!(ScanKernel::Sse2.for_categories(40), ScanKernel::Scalar);
+    }
+
+    #[test]
+    fn high_cardinality_columnar_scans_agree() {
+        let mut rng = XorShiftRng::from_seed([9; 16]);
+
+        let samples: Vec<RowSample> = (0..517)
+            .map(|record_id| {
+                let label = rng.gen_range(0, 2);
+                RowSample { record_id, values: vec![rng.gen_range(0, 64)], label, target: label as f64, weight: 1 }
+            })
+            .collect();
+
+        let attributes = vec![
+            AttributeSpec { name: "country".to_string(), attribute_type: AttributeType::Categorical, min_value: 0, max_value: 63 }
+        ];
+        let schema = Schema {
+            attributes,
+            label_column: "label".to_string(),
+            id_column: None,
+            num_classes: 2,
+            task: Task::Classification,
+            weight_column: None
+        };
+        let dataset = SchemaDataset::from_samples(schema, &samples);
+        let columns = ColumnarSamples::from_samples(&dataset, &samples);
+
+        let rows: Vec<u32> = columns.all_rows().into_iter().rev().collect();
+
+        for subset in [0x0F0F_0F0F_0F0F_0F0F_u64, 1 << 40, 0xFFFF_FFFF_0000_0000].iter() {
+            let split = Split::Categorical { attribute_index: 0, subset: CategorySet::from(*subset), missing_left: false };
+            let stats = scan(&samples, &split);
+
+            for kernel in ScanKernel::supported().into_iter().filter(|kernel| kernel.supports_categories(63)) {
+                assert_same_counts(&stats, &scan_columns(kernel, &columns, &rows, &split));
+            }
+        }
+    }
+
+    #[test]
+    fn columnar_scans_agree_with_sample_scans() {
+        let (dataset, samples) = synthetic_samples(1003);
+        let columns = ColumnarSamples::from_samples(&dataset, &samples);
+
+        // Two thirds of the rows in a scrambled order, like after a few partitioning steps
+        let mut rng = XorShiftRng::from_seed([5; 16]);
+        let mut rows: Vec<u32> = columns.all_rows().into_iter().filter(|row| row % 3 != 0).collect();
+        rows.shuffle(&mut rng);
+
+        let samples_of_rows: Vec<RowSample> = rows.iter().map(|row| samples[*row as usize].clone()).collect();
+
+        let splits = vec![
+            Split::Numerical { attribute_index: 0, cut_off: 0, missing_left: false },
+            Split::Numerical { attribute_index: 0, cut_off: 9, missing_left: false },
+            Split::Numerical { attribute_index: 1, cut_off: 4, missing_left: false },
+            Split::Categorical { attribute_index: 2, subset: CategorySet::from(0b0101), missing_left: false },
+            Split::Categorical { attribute_index: 2, subset: CategorySet::new(), missing_left: false },
+        ];
+
+        for split in &splits {
+            let stats = scan(&samples_of_rows, split);
+
+            for kernel in ScanKernel::supported() {
+                assert_same_counts(&stats, &scan_columns(kernel, &columns, &rows, split));
+            }
         }
+    }
 
-        let split = Split::Categorical { attribute_index: 14, subset };
+    #[test]
+    fn wide_values_agree_across_kernels() {
+        let (dataset, samples) = synthetic_high_cardinality_samples(1003);
+        let columns = ColumnarSamples::from_samples(&dataset, &samples);
+        let rows = columns.all_rows();
+
+        for cut_off in [0, 255, 256, 1000, 2501, 4999, 32768, u16::MAX].iter() {
+            let split = Split::Numerical { attribute_index: 1, cut_off: *cut_off, missing_left: false };
+            let stats = scan(&samples, &split);
+
+            for kernel in ScanKernel::supported() {
+                assert_same_counts(&stats, &scan_numerical_with(kernel, &samples, &split));
+                assert_same_counts(&stats, &scan_columns(kernel, &columns, &rows, &split));
+            }
+        }
 
+        // None of the SIMD kernels handles a thousand categories, so the scalar scan takes over
+        assert_eq!(ScanKernel::detect().for_categories(999), ScanKernel::Scalar);
+
+        let subset: CategorySet = (0..1000).filter(|value| value % 7 == 0 || *value >= 900).collect();
+        let split = Split::Categorical { attribute_index: 0, subset, missing_left: false };
         let stats = scan(&samples, &split);
-        let stats_simd = scan_simd_categorical(&samples, &split);
 
-        println!("SCAN {:?}", stats);
-        println!("SIMD {:?}", stats_simd);
+        assert_same_counts(&stats, &scan_categorical_with(ScanKernel::detect().for_categories(999), &samples, &split));
+        assert_same_counts(&stats, &scan_columns(ScanKernel::detect().for_categories(999), &columns, &rows, &split));
+
+        // The SIMD kernels do not mistake wide values for small ones, and leave subsets which they
+        // cannot look up to the scalar scan
+        let subsets: Vec<CategorySet> = vec![vec![0, 3, 17].into_iter().collect(), vec![0, 999].into_iter().collect()];
+
+        for subset in subsets {
+            let split = Split::Categorical { attribute_index: 0, subset, missing_left: false };
+            let stats = scan(&samples, &split);
 
-        assert_eq!(stats.num_plus_left, stats_simd.num_plus_left);
-        assert_eq!(stats.num_plus_right, stats_simd.num_plus_right);
-        assert_eq!(stats.num_minus_left, stats_simd.num_minus_left);
-        assert_eq!(stats.num_minus_right, stats_simd.num_minus_right);
+            for kernel in ScanKernel::supported() {
+                assert_same_counts(&stats, &scan_categorical_with(kernel, &samples, &split));
+                assert_same_counts(&stats, &scan_columns(kernel, &columns, &rows, &split));
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn kernels_handle_missing_values_in_both_directions() {
+        let (dataset, samples) = synthetic_samples_with_missing_values(1003);
+        let columns = ColumnarSamples::from_samples(&dataset, &samples);
+        let rows = columns.all_rows();
+
+        assert!(columns.has_missing(0) && !columns.has_missing(1) && columns.has_missing(2));
+
+        for missing_left in [false, true] {
+            let splits = vec![
+                Split::Numerical { attribute_index: 0, cut_off: 7, missing_left },
+                Split::Categorical { attribute_index: 2, subset: CategorySet::from(0b0110), missing_left },
+            ];
+
+            for split in &splits {
+                let stats = scan(&samples, split);
+
+                for kernel in ScanKernel::supported() {
+                    match split {
+                        Split::Numerical { .. } =>
+                            assert_same_counts(&stats, &scan_numerical_with(kernel, &samples, split)),
+                        Split::Categorical { .. } =>
+                            assert_same_counts(&stats, &scan_categorical_with(kernel, &samples, split)),
+                    }
+                    assert_same_counts(&stats, &scan_columns(kernel, &columns, &rows, split));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn column_labels_agree_with_the_samples() {
+        let split = Split::Numerical { attribute_index: 0, cut_off: 7, missing_left: false };
+
+        for (dataset, samples) in [synthetic_samples_with_classes(500, 5), synthetic_regression_samples(500)] {
+            let columns = ColumnarSamples::from_samples(&dataset, &samples);
+            let rows: Vec<u32> = columns.all_rows().into_iter().filter(|row| row % 3 != 0).collect();
+            let samples_of_rows: Vec<_> = rows.iter().map(|row| samples[*row as usize].clone()).collect();
+
+            let stats = scan_labels(&samples_of_rows, &split, dataset.task());
+            let column_stats = scan_column_labels(&columns, &rows, &split);
+
+            assert_eq!(stats.left, column_stats.left);
+            assert_eq!(stats.right, column_stats.right);
+        }
+    }
+}
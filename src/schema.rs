@@ -0,0 +1,357 @@
+use std::str::FromStr;
+use std::collections::HashMap;
+
+use crate::dataset::{Dataset, Sample, AttributeType, Task, MISSING};
+use crate::preprocessing::is_missing;
+use crate::split_stats::{MAX_CLASSES, MINUS};
+use crate::bootstrap::MAX_WEIGHT;
+
+#[derive(Eq,PartialEq,Debug,Clone)]
+pub struct AttributeSpec {
+    pub name: String,
+    pub attribute_type: AttributeType,
+    pub min_value: u16,
+    pub max_value: u16,
+}
+
+#[derive(Eq,PartialEq,Debug,Clone)]
+pub struct Schema {
+    pub attributes: Vec<AttributeSpec>,
+    pub label_column: String,
+    pub id_column: Option<String>,
+    pub num_classes: u8,
+    pub task: Task,
+    pub weight_column: Option<String>,
+}
+
+impl Schema {
+
+    // A schema file is tab separated with the header "column type min max". Every row
+    // describes one attribute (type "numerical" or "categorical") with its value range,
+    // exactly one row of type "label" names the column holding the label. Labels are binary unless
+    // the label row declares the range of class indexes, e.g. "0 3" for four classes. An optional row
+    // of type "id" names the column holding the record ids, samples are numbered by their row
+    // otherwise. A row of type "target" instead of the label names a numeric target for regression.
+    // An optional row of type "weight" names a column of positive integer sample weights.
+    // Columns of the data files which are not mentioned in the schema are ignored.
+    pub fn from_file(file: &str) -> Schema {
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .delimiter(b'\t')
+            .flexible(true)
+            .from_path(file)
+            .unwrap();
+
+        let mut attributes = Vec::new();
+        let mut label_column: Option<String> = None;
+        let mut num_classes = 2;
+        let mut task = Task::Classification;
+        let mut id_column: Option<String> = None;
+        let mut weight_column: Option<String> = None;
+
+        for result in reader.records() {
+            let record = result.unwrap();
+
+            let name = record.get(0).unwrap().to_string();
+            let type_name = record.get(1).unwrap();
+
+            let attribute_type = match type_name {
+                "numerical" => AttributeType::Numerical,
+                "categorical" => AttributeType::Categorical,
+                "label" => {
+                    assert!(label_column.is_none(), "Schema {} declares more than one label!", file);
+                    if let Some(max_class) = record.get(3) {
+                        let max_class = u8::from_str(max_class).unwrap();
+                        assert!(
+                            (max_class as usize) < MAX_CLASSES,
+                            "Schema {} declares more than {} classes!", file, MAX_CLASSES
+                        );
+                        num_classes = max_class.max(1) + 1;
+                    }
+                    label_column = Some(name);
+                    continue;
+                },
+                "target" => {
+                    assert!(label_column.is_none(), "Schema {} declares more than one label!", file);
+                    task = Task::Regression;
+                    label_column = Some(name);
+                    continue;
+                },
+                "weight" => {
+                    assert!(weight_column.is_none(), "Schema {} declares more than one weight column!", file);
+                    weight_column = Some(name);
+                    continue;
+                },
+                "id" => {
+                    assert!(id_column.is_none(), "Schema {} declares more than one id column!", file);
+                    id_column = Some(name);
+                    continue;
+                },
+                _ => panic!("Unknown type {} for column {} in schema {}!", type_name, name, file)
+            };
+
+            let min_value = u16::from_str(record.get(2).unwrap()).unwrap();
+            let max_value = u16::from_str(record.get(3).unwrap()).unwrap();
+
+            assert!(min_value <= max_value, "Invalid range for column {} in schema {}!", name, file);
+            assert!(max_value < MISSING, "Range of column {} in schema {} is too large!", name, file);
+
+            attributes.push(AttributeSpec { name, attribute_type, min_value, max_value });
+        }
+
+        assert!(!attributes.is_empty(), "Schema {} does not declare any attributes!", file);
+        assert!(attributes.len() <= u16::MAX as usize, "Schema {} declares too many attributes!", file);
+
+        Schema {
+            attributes,
+            label_column: label_column
+                .unwrap_or_else(|| panic!("Schema {} does not declare a label!", file)),
+            id_column,
+            num_classes,
+            task,
+            weight_column,
+        }
+    }
+
+    pub fn to_file(&self, file: &str) {
+
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(b'\t')
+            .flexible(true)
+            .from_path(file)
+            .unwrap();
+
+        writer.write_record(["column", "type", "min", "max"]).unwrap();
+
+        if let Some(id_column) = &self.id_column {
+            writer.write_record([id_column, "id"]).unwrap();
+        }
+
+        for attribute in &self.attributes {
+            let type_name = match attribute.attribute_type {
+                AttributeType::Numerical => "numerical",
+                AttributeType::Categorical => "categorical",
+            };
+
+            writer.write_record(&[
+                attribute.name.clone(),
+                type_name.to_string(),
+                attribute.min_value.to_string(),
+                attribute.max_value.to_string()
+            ]).unwrap();
+        }
+
+        if let Some(weight_column) = &self.weight_column {
+            writer.write_record([weight_column, "weight"]).unwrap();
+        }
+
+        if self.task == Task::Regression {
+            writer.write_record([&self.label_column, "target"]).unwrap();
+        } else if self.num_classes > 2 {
+            let max_class = (self.num_classes - 1).to_string();
+            writer.write_record([&self.label_column, "label", "0", &max_class]).unwrap();
+        } else {
+            writer.write_record([&self.label_column, "label"]).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    pub fn num_attributes(&self) -> u16 {
+        self.attributes.len() as u16
+    }
+
+    fn attribute(&self, index: u16) -> &AttributeSpec {
+        self.attributes.get(index as usize)
+            .unwrap_or_else(|| panic!("Requested non-existing attribute {}!", index))
+    }
+}
+
+#[derive(PartialEq,Debug,Clone)]
+pub struct RowSample {
+    pub record_id: u64,
+    pub values: Vec<u16>,
+    // Class index, 0 or 1 for binary labels
+    pub label: u8,
+    // Numeric target for regression, the class index otherwise
+    pub target: f64,
+    pub weight: u32,
+}
+
+impl Sample for RowSample {
+
+    fn attribute_value(&self, attribute_index: u16) -> u16 {
+        *self.values.get(attribute_index as usize)
+            .unwrap_or_else(|| panic!("Requested non-existing attribute {}!", attribute_index))
+    }
+
+    fn true_label(&self) -> bool {
+        self.label != MINUS
+    }
+
+    fn class_label(&self) -> u8 {
+        self.label
+    }
+
+    fn target(&self) -> f64 {
+        self.target
+    }
+
+    fn record_id(&self) -> u64 {
+        self.record_id
+    }
+
+    fn weight(&self) -> u32 {
+        self.weight
+    }
+}
+
+pub struct SchemaDataset {
+    pub schema: Schema,
+    pub num_records: u32,
+    pub num_plus: u32,
+}
+
+impl SchemaDataset {
+
+    pub fn from_samples(schema: Schema, samples: &[RowSample]) -> SchemaDataset {
+        let num_plus = samples.iter().filter(|sample| sample.true_label()).count();
+
+        SchemaDataset {
+            schema,
+            num_records: samples.len() as u32,
+            num_plus: num_plus as u32
+        }
+    }
+
+    // Empty cells and cells like "NA" or "?" are read as missing attribute values
+    pub fn samples_from_csv(schema: &Schema, file: &str) -> Vec<RowSample> {
+
+        let mut samples: Vec<RowSample> = Vec::new();
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .delimiter(b'\t')
+            .from_path(file)
+            .unwrap();
+
+        let positions: HashMap<String, usize> = reader.headers().unwrap().iter()
+            .enumerate()
+            .map(|(position, name)| (name.to_string(), position))
+            .collect();
+
+        let position_of = |name: &str| -> usize {
+            *positions.get(name)
+                .unwrap_or_else(|| panic!("Column {} not found in {}!", name, file))
+        };
+
+        let attribute_positions: Vec<usize> = schema.attributes.iter()
+            .map(|attribute| position_of(&attribute.name))
+            .collect();
+
+        let label_position = position_of(&schema.label_column);
+        let id_position = schema.id_column.as_ref().map(|name| position_of(name));
+        let weight_position = schema.weight_column.as_ref().map(|name| position_of(name));
+
+        for (row, result) in reader.records().enumerate() {
+            let record = result.unwrap();
+
+            let values: Vec<u16> = schema.attributes.iter()
+                .zip(attribute_positions.iter())
+                .map(|(attribute, position)| {
+                    let cell = record.get(*position).unwrap();
+
+                    if is_missing(cell) {
+                        return MISSING;
+                    }
+
+                    let value = u16::from_str(cell).unwrap();
+
+                    assert!(
+                        value >= attribute.min_value && value <= attribute.max_value,
+                        "Value {} out of range for column {} in {}!", value, attribute.name, file
+                    );
+
+                    value
+                })
+                .collect();
+
+            let (label, target) = match schema.task {
+                Task::Classification => {
+                    let label = u8::from_str(record.get(label_position).unwrap()).unwrap();
+                    assert!(label < schema.num_classes, "Label {} out of range in {}!", label, file);
+                    (label, label as f64)
+                },
+                Task::Regression => {
+                    let target = f64::from_str(record.get(label_position).unwrap()).unwrap();
+                    assert!(target.is_finite(), "Target {} is not finite in {}!", target, file);
+                    (MINUS, target)
+                },
+            };
+
+            let record_id = match id_position {
+                Some(position) => u64::from_str(record.get(position).unwrap()).unwrap(),
+                None => row as u64,
+            };
+
+            let weight = match weight_position {
+                Some(position) => u32::from_str(record.get(position).unwrap()).unwrap(),
+                None => 1,
+            };
+            assert!(weight > 0, "Sample {} has no weight in {}!", record_id, file);
+            assert!(weight <= MAX_WEIGHT, "Sample {} has a weight above {} in {}!", record_id, MAX_WEIGHT, file);
+
+            samples.push(RowSample { record_id, values, label, target, weight });
+        }
+
+        samples
+    }
+}
+
+impl Dataset for SchemaDataset {
+
+    fn num_records(&self) -> u32 { self.num_records }
+
+    fn num_plus(&self) -> u32 { self.num_plus }
+
+    fn num_attributes(&self) -> u16 { self.schema.num_attributes() }
+
+    fn attribute_range(&self, index: u16) -> (u16, u16) {
+        let attribute = self.schema.attribute(index);
+        (attribute.min_value, attribute.max_value)
+    }
+
+    fn attribute_type(&self, index: u16) -> AttributeType {
+        self.schema.attribute(index).attribute_type
+    }
+
+    fn num_classes(&self) -> u8 { self.schema.num_classes }
+
+    fn task(&self) -> Task { self.schema.task }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::dataset::{AdultDataset, Dataset, Task};
+    use crate::schema::{Schema, SchemaDataset};
+
+    #[test]
+    fn adult_schema_matches_adult_dataset() {
+        let schema = Schema::from_file("datasets/adult-schema.tsv");
+
+        let adult = AdultDataset { num_records: 0, num_plus: 0 };
+        let dataset = SchemaDataset::from_samples(schema, &Vec::new());
+
+        assert_eq!(dataset.schema.label_column, "label");
+        assert_eq!(dataset.schema.id_column, Some("record_id".to_string()));
+        assert_eq!(dataset.num_classes(), 2);
+        assert_eq!(dataset.task(), Task::Classification);
+        assert_eq!(dataset.num_attributes(), adult.num_attributes());
+
+        for index in 0..adult.num_attributes() {
+            assert_eq!(dataset.attribute_range(index), adult.attribute_range(index));
+            assert_eq!(dataset.attribute_type(index), adult.attribute_type(index));
+        }
+    }
+}
@@ -1,10 +1,471 @@
+use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Clone, Copy)]
+use crate::dataset::{Sample, Task};
+
+// Labels are class indexes below MAX_CLASSES, binary datasets use MINUS and PLUS
+pub const MAX_CLASSES: usize = 8;
+pub const MINUS: u8 = 0;
+pub const PLUS: u8 = 1;
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ClassCounts {
+    counts: [u32; MAX_CLASSES],
+}
+
+impl ClassCounts {
+
+    pub fn new() -> ClassCounts {
+        ClassCounts::default()
+    }
+
+    pub fn binary(num_plus: u32, num_minus: u32) -> ClassCounts {
+        let mut counts = [0; MAX_CLASSES];
+        counts[PLUS as usize] = num_plus;
+        counts[MINUS as usize] = num_minus;
+        ClassCounts { counts }
+    }
+
+    pub fn get(&self, class: u8) -> u32 {
+        self.counts[class as usize]
+    }
+
+    pub fn add(&mut self, class: u8) {
+        self.add_many(class, 1);
+    }
+
+    pub fn remove(&mut self, class: u8) {
+        self.remove_many(class, 1);
+    }
+
+    fn add_many(&mut self, class: u8, num: u32) {
+        self.counts[class as usize] += num;
+    }
+
+    fn remove_many(&mut self, class: u8, num: u32) {
+        assert!(self.counts[class as usize] >= num);
+        self.counts[class as usize] -= num;
+    }
+
+    pub fn num_plus(&self) -> u32 {
+        self.get(PLUS)
+    }
+
+    pub fn num_minus(&self) -> u32 {
+        self.get(MINUS)
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+
+    pub fn classes_present(&self) -> impl Iterator<Item=u8> + '_ {
+        (0..MAX_CLASSES as u8).filter(move |class| self.get(*class) > 0)
+    }
+
+    // Holds samples of at most one class
+    pub fn is_pure(&self) -> bool {
+        self.classes_present().count() <= 1
+    }
+
+    // Ties are broken in favor of the smaller class index
+    pub fn majority_class(&self) -> u8 {
+        let mut majority_class = 0;
+        for class in 1..MAX_CLASSES as u8 {
+            if self.get(class) > self.get(majority_class) {
+                majority_class = class;
+            }
+        }
+        majority_class
+    }
+
+    pub fn merged(&self, other: &ClassCounts) -> ClassCounts {
+        let mut counts = self.counts;
+        for (count, other_count) in counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+        ClassCounts { counts }
+    }
+
+    pub fn subtracted(&self, other: &ClassCounts) -> ClassCounts {
+        let mut counts = self.counts;
+        for (count, other_count) in counts.iter_mut().zip(other.counts.iter()) {
+            assert!(*count >= *other_count);
+            *count -= other_count;
+        }
+        ClassCounts { counts }
+    }
+
+    fn is_binary(&self) -> bool {
+        self.counts[2..].iter().all(|count| *count == 0)
+    }
+
+    fn from_counts(counts_of_classes: &[u32]) -> ClassCounts {
+        let mut counts = [0; MAX_CLASSES];
+        counts[..counts_of_classes.len()].copy_from_slice(counts_of_classes);
+        ClassCounts { counts }
+    }
+
+    #[inline(always)]
+    pub fn gini_impurity(&self) -> f64 {
+        // The binary case is computed as before to not change any scores
+        if self.is_binary() {
+            return gini_impurity(self.num_plus(), self.num_plus() + self.num_minus());
+        }
+
+        let total = self.total() as f64;
+        let sum_of_squares: f64 = self.counts.iter()
+            .map(|count| {
+                let p = *count as f64 / total;
+                p * p
+            })
+            .sum();
+
+        1.0 - sum_of_squares
+    }
+
+    // Entropy of the class distribution in bits
+    pub fn entropy(&self) -> f64 {
+        let total = self.total() as f64;
+
+        -self.counts.iter()
+            .filter(|count| **count > 0)
+            .map(|count| {
+                let p = *count as f64 / total;
+                p * p.log2()
+            })
+            .sum::<f64>()
+    }
+}
+
+// Sufficient statistics of numeric targets, min and max are only bounds of the targets once
+// samples are removed. Samples with weight w count like w samples with the same target.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TargetStats {
+    pub count: u32,
+    pub sum: f64,
+    pub sum_of_squares: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for TargetStats {
+    fn default() -> TargetStats {
+        TargetStats {
+            count: 0,
+            sum: 0.0,
+            sum_of_squares: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl TargetStats {
+
+    pub fn new() -> TargetStats {
+        TargetStats::default()
+    }
+
+    pub fn add(&mut self, target: f64) {
+        self.add_many(target, 1);
+    }
+
+    fn add_many(&mut self, target: f64, num: u32) {
+        self.count += num;
+        self.sum += target * num as f64;
+        self.sum_of_squares += target * target * num as f64;
+        self.min = self.min.min(target);
+        self.max = self.max.max(target);
+    }
+
+    pub fn remove(&mut self, target: f64) {
+        self.remove_many(target, 1);
+    }
+
+    fn remove_many(&mut self, target: f64, num: u32) {
+        assert!(self.count >= num);
+        self.count -= num;
+        self.sum -= target * num as f64;
+        self.sum_of_squares -= target * target * num as f64;
+
+        if self.count == 0 {
+            *self = TargetStats::default();
+        } else if self.count == 1 {
+            // The remaining target is known again
+            self.min = self.sum;
+            self.max = self.sum;
+        }
+    }
+
+    // Empty statistics have a mean of zero
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    pub fn variance(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+
+        let mean = self.mean();
+        // Removals can leave tiny negative values due to rounding
+        (self.sum_of_squares / self.count as f64 - mean * mean).max(0.0)
+    }
+
+    // Sum of the squared deviations from the mean
+    pub fn sum_of_squared_deviations(&self) -> f64 {
+        self.variance() * self.count as f64
+    }
+
+    // Upper bound of how much the sum of squared deviations drops when another sample of the given
+    // weight is removed after num_removed units of weight. Removing a sample x of weight w from n
+    // samples with mean m lowers the sum by n * w / (n - w) * (x - m)^2, and every removed unit of
+    // weight moves the mean by at most (max - min) / (n - 1).
+    fn max_drop_of_removal(&self, num_removed: u64, weight: u64) -> f64 {
+        if num_removed + weight >= self.count as u64 {
+            return self.sum_of_squared_deviations();
+        }
+
+        let num_left = (self.count as u64 - num_removed) as f64;
+        let range = self.max - self.min;
+        let mean = self.mean();
+
+        let max_drift_of_mean = num_removed as f64 * range / num_left;
+        let max_deviation = range.min((mean - self.min).max(self.max - mean) + max_drift_of_mean);
+
+        num_left * weight as f64 / (num_left - weight as f64) * max_deviation * max_deviation
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.count <= 1 || self.min == self.max
+    }
+
+    pub fn merged(&self, other: &TargetStats) -> TargetStats {
+        TargetStats {
+            count: self.count + other.count,
+            sum: self.sum + other.sum,
+            sum_of_squares: self.sum_of_squares + other.sum_of_squares,
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LabelStats {
+    Classes(ClassCounts),
+    Targets(TargetStats),
+}
+
+impl LabelStats {
+
+    pub fn empty(task: Task) -> LabelStats {
+        match task {
+            Task::Classification => LabelStats::Classes(ClassCounts::new()),
+            Task::Regression => LabelStats::Targets(TargetStats::new()),
+        }
+    }
+
+    pub fn of_samples<S: Sample>(samples: &[S], task: Task) -> LabelStats {
+        let mut stats = LabelStats::empty(task);
+        for sample in samples {
+            stats.add(sample);
+        }
+        stats
+    }
+
+    // Samples are counted with their weight
+    pub fn add<S: Sample>(&mut self, sample: &S) {
+        match self {
+            LabelStats::Classes(counts) => counts.add_many(sample.class_label(), sample.weight()),
+            LabelStats::Targets(targets) => targets.add_many(sample.target(), sample.weight()),
+        }
+    }
+
+    pub fn remove<S: Sample>(&mut self, sample: &S) {
+        match self {
+            LabelStats::Classes(counts) => counts.remove_many(sample.class_label(), sample.weight()),
+            LabelStats::Targets(targets) => targets.remove_many(sample.target(), sample.weight()),
+        }
+    }
+
+    // Whether the sample could have been counted here, targets are only checked via the count
+    pub fn can_remove<S: Sample>(&self, sample: &S) -> bool {
+        match self {
+            LabelStats::Classes(counts) => counts.get(sample.class_label()) >= sample.weight(),
+            LabelStats::Targets(targets) => targets.count >= sample.weight(),
+        }
+    }
+
+    // Sum of the weights of the samples
+    pub fn total(&self) -> u32 {
+        match self {
+            LabelStats::Classes(counts) => counts.total(),
+            LabelStats::Targets(targets) => targets.count,
+        }
+    }
+
+    // Holds samples of at most one class or a constant target
+    pub fn is_pure(&self) -> bool {
+        match self {
+            LabelStats::Classes(counts) => counts.is_pure(),
+            LabelStats::Targets(targets) => targets.is_constant(),
+        }
+    }
+
+    // Gini impurity for classes, variance for targets
+    pub fn impurity(&self) -> f64 {
+        match self {
+            LabelStats::Classes(counts) => counts.gini_impurity(),
+            LabelStats::Targets(targets) => targets.variance(),
+        }
+    }
+
+    pub fn merged(&self, other: &LabelStats) -> LabelStats {
+        match (self, other) {
+            (LabelStats::Classes(a), LabelStats::Classes(b)) => LabelStats::Classes(a.merged(b)),
+            (LabelStats::Targets(a), LabelStats::Targets(b)) => LabelStats::Targets(a.merged(b)),
+            _ => panic!("Cannot merge class counts with target statistics!"),
+        }
+    }
+
+    pub fn class_counts(&self) -> &ClassCounts {
+        match self {
+            LabelStats::Classes(counts) => counts,
+            LabelStats::Targets(_) => panic!("Regression statistics have no class counts!"),
+        }
+    }
+
+    pub fn class_counts_mut(&mut self) -> &mut ClassCounts {
+        match self {
+            LabelStats::Classes(counts) => counts,
+            LabelStats::Targets(_) => panic!("Regression statistics have no class counts!"),
+        }
+    }
+
+    pub fn target_stats(&self) -> &TargetStats {
+        match self {
+            LabelStats::Targets(targets) => targets,
+            LabelStats::Classes(_) => panic!("Classification statistics have no target statistics!"),
+        }
+    }
+}
+
+// Label statistics as stored in the leaves, of which every tree has many. Binary leaves only keep
+// their two counts and multi-class leaves the counts up to their highest class, instead of the
+// counts of all MAX_CLASSES classes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LeafStats {
+    Binary { num_minus: u32, num_plus: u32 },
+    Classes(Box<[u32]>),
+    Targets(Box<TargetStats>),
+}
+
+impl LeafStats {
+
+    pub fn new(label_stats: &LabelStats) -> LeafStats {
+        match label_stats {
+            LabelStats::Classes(counts) if counts.is_binary() => {
+                LeafStats::Binary { num_minus: counts.num_minus(), num_plus: counts.num_plus() }
+            },
+            LabelStats::Classes(counts) => {
+                let num_classes = counts.classes_present().last().unwrap() as usize + 1;
+                LeafStats::Classes(counts.counts()[..num_classes].into())
+            },
+            LabelStats::Targets(targets) => LeafStats::Targets(Box::new(*targets)),
+        }
+    }
+
+    pub fn label_stats(&self) -> LabelStats {
+        match self {
+            LeafStats::Binary { num_minus, num_plus } => LabelStats::Classes(ClassCounts::binary(*num_plus, *num_minus)),
+            LeafStats::Classes(counts) => LabelStats::Classes(ClassCounts::from_counts(counts)),
+            LeafStats::Targets(targets) => LabelStats::Targets(**targets),
+        }
+    }
+
+    // Updates are rare compared to predictions, so they go through the full label statistics
+    pub fn add<S: Sample>(&mut self, sample: &S) {
+        let mut label_stats = self.label_stats();
+        label_stats.add(sample);
+        *self = LeafStats::new(&label_stats);
+    }
+
+    pub fn remove<S: Sample>(&mut self, sample: &S) {
+        let mut label_stats = self.label_stats();
+        label_stats.remove(sample);
+        *self = LeafStats::new(&label_stats);
+    }
+
+    pub fn can_remove<S: Sample>(&self, sample: &S) -> bool {
+        self.label_stats().can_remove(sample)
+    }
+
+    pub fn total(&self) -> u32 {
+        match self {
+            LeafStats::Binary { num_minus, num_plus } => num_minus + num_plus,
+            LeafStats::Classes(counts) => counts.iter().sum(),
+            LeafStats::Targets(targets) => targets.count,
+        }
+    }
+}
+
+// Criterion by which candidate splits are scored and checked for robustness. Regression always
+// scores splits by their relative variance reduction.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SplitCriterion {
+    Gini,
+    // Information gain in bits
+    Entropy,
+    // Hellinger distance between the class distributions of both sides, which does not depend on
+    // the class priors and therefore suits imbalanced data
+    Hellinger,
+}
+
+impl SplitCriterion {
+
+    // The Hellinger distance is not an impurity decrease, its nodes carry their gini impurity
+    pub fn impurity(&self, label_stats: &LabelStats) -> f64 {
+        match (self, label_stats) {
+            (SplitCriterion::Entropy, LabelStats::Classes(counts)) => counts.entropy(),
+            _ => label_stats.impurity(),
+        }
+    }
+
+    // Score of the split together with the impurities of both sides
+    pub fn score(&self, impurity_before: f64, left: &LabelStats, right: &LabelStats) -> (Option<i64>, f64, f64) {
+        match (self, left, right) {
+            (SplitCriterion::Hellinger, LabelStats::Classes(counts_left), LabelStats::Classes(counts_right)) => {
+                if left.total() == 0 || right.total() == 0 {
+                    return (None, 0.0, 0.0);
+                }
+
+                let distance = hellinger_distance(counts_left, counts_right);
+
+                (Some(to_score(distance)), left.impurity(), right.impurity())
+            },
+            _ => impurity_decrease(*self, impurity_before, left, right),
+        }
+    }
+
+    pub fn score_with_impurity_before(&self, left: &LabelStats, right: &LabelStats) -> (Option<i64>, f64, f64) {
+        let impurity_before = self.impurity(&left.merged(right));
+        self.score(impurity_before, left, right)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct SplitStats {
-    pub num_plus_left: u32,
-    pub num_minus_left: u32,
-    pub num_plus_right: u32,
-    pub num_minus_right: u32,
+    pub left: LabelStats,
+    pub right: LabelStats,
     pub impurity_left: f64,
     pub impurity_right: f64,
     pub score: Option<i64>,
@@ -13,7 +474,19 @@ pub struct SplitStats {
 impl SplitStats {
 
     pub fn fmt(&self) -> String {
-        format!("({},{},{},{})", self.num_plus_left, self.num_minus_left, self.num_plus_right, self.num_minus_right)
+        match (&self.left, &self.right) {
+            (LabelStats::Classes(left), LabelStats::Classes(right)) => {
+                if left.is_binary() && right.is_binary() {
+                    format!("({},{},{},{})", self.num_plus_left(), self.num_minus_left(), self.num_plus_right(), self.num_minus_right())
+                } else {
+                    format!("({:?},{:?})", left.counts(), right.counts())
+                }
+            },
+            (left, right) => {
+                let (left, right) = (left.target_stats(), right.target_stats());
+                format!("({},{:.4},{},{:.4})", left.count, left.mean(), right.count, right.mean())
+            },
+        }
     }
 
     pub fn new(
@@ -22,17 +495,46 @@ impl SplitStats {
         num_plus_right: u32,
         num_minus_right: u32,
     ) -> SplitStats {
+        SplitStats::from_counts(
+            ClassCounts::binary(num_plus_left, num_minus_left),
+            ClassCounts::binary(num_plus_right, num_minus_right)
+        )
+    }
+
+    pub fn from_counts(left: ClassCounts, right: ClassCounts) -> SplitStats {
+        SplitStats::from_label_stats(LabelStats::Classes(left), LabelStats::Classes(right))
+    }
+
+    pub fn from_targets(left: TargetStats, right: TargetStats) -> SplitStats {
+        SplitStats::from_label_stats(LabelStats::Targets(left), LabelStats::Targets(right))
+    }
+
+    pub fn from_label_stats(left: LabelStats, right: LabelStats) -> SplitStats {
         SplitStats {
-            num_plus_left,
-            num_minus_left,
-            num_plus_right,
-            num_minus_right,
+            left,
+            right,
             impurity_left: 0.0,
             impurity_right: 0.0,
             score: None
         }
     }
 
+    pub fn num_plus_left(&self) -> u32 { self.left.class_counts().num_plus() }
+
+    pub fn num_minus_left(&self) -> u32 { self.left.class_counts().num_minus() }
+
+    pub fn num_plus_right(&self) -> u32 { self.right.class_counts().num_plus() }
+
+    pub fn num_minus_right(&self) -> u32 { self.right.class_counts().num_minus() }
+
+    pub fn side(&self, is_left: bool) -> &LabelStats {
+        if is_left { &self.left } else { &self.right }
+    }
+
+    pub fn side_mut(&mut self, is_left: bool) -> &mut LabelStats {
+        if is_left { &mut self.left } else { &mut self.right }
+    }
+
     pub fn has_positive_score(&self) -> bool {
         match self.score {
             Some(the_score) => the_score > 0,
@@ -40,27 +542,16 @@ impl SplitStats {
         }
     }
 
-    pub fn update_score(&mut self, impurity_before: f64) {
-        let (score, impurity_left, impurity_right) = gini(
-            impurity_before,
-            self.num_plus_left,
-            self.num_minus_left,
-            self.num_plus_right,
-            self.num_minus_right
-        );
+    pub fn update_score(&mut self, impurity_before: f64, criterion: SplitCriterion) {
+        let (score, impurity_left, impurity_right) = criterion.score(impurity_before, &self.left, &self.right);
 
         self.score = score;
         self.impurity_left = impurity_left;
         self.impurity_right = impurity_right;
     }
 
-    pub fn update_score_and_impurity_before(&mut self) {
-        let (score, impurity_left, impurity_right) = gini_with_impurity_before(
-            self.num_plus_left,
-            self.num_minus_left,
-            self.num_plus_right,
-            self.num_minus_right
-        );
+    pub fn update_score_and_impurity_before(&mut self, criterion: SplitCriterion) {
+        let (score, impurity_left, impurity_right) = criterion.score_with_impurity_before(&self.left, &self.right);
 
         self.score = score;
         self.impurity_left = impurity_left;
@@ -68,63 +559,290 @@ impl SplitStats {
     }
 }
 
-fn h2(s: &SplitStats, t: &SplitStats, r: u32) -> bool {
-    let threshold = r;
+fn h2(s: &SplitStats, t: &SplitStats, r: usize, max_weight: u32) -> bool {
+    let threshold = r as u64 * max_weight as u64;
+    let exceeds_threshold = |stats: &LabelStats, class: u8| stats.class_counts().get(class) as u64 > threshold;
 
     // We reject these to avoid false positives
-    if s.num_plus_left <= threshold || s.num_plus_right <= threshold ||
-        t.num_plus_left <= threshold || t.num_plus_right <= threshold ||
-        s.num_minus_left <= threshold || s.num_minus_right <= threshold ||
-        t.num_minus_left <= threshold || t.num_minus_right <= threshold {
-
-        false
-    } else {
-        true
+    match s.left.merged(&s.right) {
+        LabelStats::Classes(counts) => counts.classes_present().all(|class| {
+            exceeds_threshold(&s.left, class) && exceeds_threshold(&s.right, class) &&
+                exceeds_threshold(&t.left, class) && exceeds_threshold(&t.right, class)
+        }),
+        LabelStats::Targets(_) => panic!("Regression splits are checked via their variance bound!"),
     }
 }
 
 pub fn is_robust(
     current_champion_stats: &SplitStats,
     current_runnerup_stats: &SplitStats,
-    threshold: usize
+    threshold: usize,
+    criterion: SplitCriterion
+) -> (bool, usize) {
+    is_robust_with_weights(current_champion_stats, current_runnerup_stats, threshold, 1, criterion)
+}
+
+// Every removed sample is assumed to have the maximum weight, so that a removal takes away
+// max_weight from a count, or all of the count if it is smaller. The search still works in
+// removals, which keeps its cost independent of the weights.
+pub fn is_robust_with_weights(
+    current_champion_stats: &SplitStats,
+    current_runnerup_stats: &SplitStats,
+    threshold: usize,
+    max_weight: u32,
+    criterion: SplitCriterion
 ) -> (bool, usize) {
 
     assert!(current_champion_stats.has_positive_score());
     assert!(current_runnerup_stats.has_positive_score());
+    assert!(max_weight > 0);
+
+    if let LabelStats::Targets(_) = current_champion_stats.left {
+        return is_robust_regression(current_champion_stats, current_runnerup_stats, threshold, max_weight);
+    }
 
-    if !h2(current_champion_stats, current_runnerup_stats, threshold as u32) {
+    if !h2(current_champion_stats, current_runnerup_stats, threshold, max_weight) {
         return (false, threshold)
     }
 
-    let (robust_via_heuristic, _) = heuristic(current_champion_stats, current_runnerup_stats, threshold as u32);
+    let champion = Contender::Split(*current_champion_stats);
+    let runnerup = Contender::Split(*current_runnerup_stats);
 
-    if !robust_via_heuristic {
+    if weaken_heavily(&champion, &runnerup, threshold, max_weight, criterion).is_some() {
         return (false, threshold)
     }
 
-    let (is_robust, num_removals,_) = is_robust2(current_champion_stats, current_runnerup_stats, threshold, false);
-
+    let (is_robust, num_removals, _) = is_robust_against(&champion, &runnerup, threshold, max_weight, criterion);
 
     (is_robust, num_removals)
 }
 
+// Regression scores are variance reductions relative to the same parent, so the champion stays
+// ahead as long as the summed squared deviations of its sides stay below those of the runner-up.
+// Removals never increase the sum of the champion, so the split survives every number of removals
+// for which the largest possible drop of the sum of the runner-up keeps it above the champion.
+fn is_robust_regression(
+    champion: &SplitStats,
+    runnerup: &SplitStats,
+    threshold: usize,
+    max_weight: u32
+) -> (bool, usize) {
+    let sum_of_squared_deviations = |stats: &SplitStats| {
+        stats.left.target_stats().sum_of_squared_deviations() +
+            stats.right.target_stats().sum_of_squared_deviations()
+    };
+
+    let champion_deviations = sum_of_squared_deviations(champion);
+    let runnerup_deviations = sum_of_squared_deviations(runnerup);
+    let (runnerup_left, runnerup_right) = (runnerup.left.target_stats(), runnerup.right.target_stats());
+
+    // Emptying a side of the champion leaves it without a score
+    let smallest_side_of_champion = champion.left.total().min(champion.right.total()) as u64;
+    let max_weight = max_weight as u64;
+
+    let mut drop_left = 0.0;
+    let mut drop_right = 0.0;
+
+    for num_removals in 1..=threshold {
+        if num_removals as u64 * max_weight >= smallest_side_of_champion {
+            return (false, num_removals);
+        }
+
+        let num_removed = (num_removals - 1) as u64 * max_weight;
+        drop_left = runnerup_left.sum_of_squared_deviations()
+            .min(drop_left + runnerup_left.max_drop_of_removal(num_removed, max_weight));
+        drop_right = runnerup_right.sum_of_squared_deviations()
+            .min(drop_right + runnerup_right.max_drop_of_removal(num_removed, max_weight));
+
+        if runnerup_deviations - drop_left - drop_right <= champion_deviations {
+            return (false, num_removals);
+        }
+    }
+
+    (true, threshold)
+}
+
+// Number of removals which the score of a split survives without falling below the minimum score.
+// The minimum score takes the place of the runner-up in the robustness search, and every removed
+// sample is assumed to have the maximum weight.
+pub fn removals_until_below(
+    stats: &SplitStats,
+    min_score: i64,
+    max_removals: usize,
+    max_weight: u32,
+    criterion: SplitCriterion
+) -> usize {
+
+    assert!(max_weight > 0);
+
+    if let LabelStats::Targets(_) = stats.left {
+        regression_removals_until_below(stats, min_score, max_removals, max_weight)
+    } else {
+        let threshold = Contender::Threshold(min_score);
+        removals_survived(&Contender::Split(*stats), &threshold, max_removals, max_weight, criterion)
+    }
+}
+
+// Number of removals which a split survives without reaching the minimum score, which bounds the
+// budget of a leaf made because no split had the minimum score
+pub fn removals_until_admissible(
+    stats: &SplitStats,
+    min_score: i64,
+    max_removals: usize,
+    max_weight: u32,
+    criterion: SplitCriterion
+) -> usize {
+
+    assert!(max_weight > 0);
+
+    if let LabelStats::Targets(_) = stats.left {
+        regression_removals_until_admissible(stats, min_score, max_removals, max_weight)
+    } else {
+        // The split overtakes a threshold just below the minimum score once it reaches the minimum
+        let threshold = Contender::Threshold(min_score - 1);
+        removals_survived(&threshold, &Contender::Split(*stats), max_removals, max_weight, criterion)
+    }
+}
+
+// Regression scores are 1 - W / T for the summed squared deviations W of the sides and T of the
+// parent. Removals never increase W, and the part T - W between the sides drops by at most as
+// much as T, which bounds the lowest score after every number of removals.
+fn regression_removals_until_below(
+    stats: &SplitStats,
+    min_score: i64,
+    max_removals: usize,
+    max_weight: u32
+) -> usize {
+    let (left, right) = (stats.left.target_stats(), stats.right.target_stats());
+    let parent = left.merged(right);
+
+    let within = left.sum_of_squared_deviations() + right.sum_of_squared_deviations();
+    let between = parent.sum_of_squared_deviations() - within;
+
+    // Emptying a side leaves the split without a score
+    let smallest_side = left.count.min(right.count) as u64;
+    let max_weight = max_weight as u64;
+
+    let mut drop = 0.0;
+
+    for num_removals in 1..=max_removals {
+        if num_removals as u64 * max_weight >= smallest_side {
+            return num_removals - 1;
+        }
+
+        let num_removed = (num_removals - 1) as u64 * max_weight;
+        drop = parent.sum_of_squared_deviations()
+            .min(drop + parent.max_drop_of_removal(num_removed, max_weight));
+
+        let lowest_between = between - drop;
+
+        if lowest_between <= 0.0 || to_score(lowest_between / (lowest_between + within)) < min_score {
+            return num_removals - 1;
+        }
+    }
+
+    max_removals
+}
+
+// Removals never increase T, so the score 1 - W / T rises by at most the largest drop of W
+// relative to the T before the removals
+fn regression_removals_until_admissible(
+    stats: &SplitStats,
+    min_score: i64,
+    max_removals: usize,
+    max_weight: u32
+) -> usize {
+    let (left, right) = (stats.left.target_stats(), stats.right.target_stats());
+
+    let total = left.merged(right).sum_of_squared_deviations();
+    let within = left.sum_of_squared_deviations() + right.sum_of_squared_deviations();
+
+    // Constant targets stay constant and never allow a split
+    if total <= 0.0 {
+        return max_removals;
+    }
+
+    let max_weight = max_weight as u64;
+
+    let mut drop_left = 0.0;
+    let mut drop_right = 0.0;
+
+    for num_removals in 1..=max_removals {
+        let num_removed = (num_removals - 1) as u64 * max_weight;
+        drop_left = left.sum_of_squared_deviations()
+            .min(drop_left + left.max_drop_of_removal(num_removed, max_weight));
+        drop_right = right.sum_of_squared_deviations()
+            .min(drop_right + right.max_drop_of_removal(num_removed, max_weight));
+
+        if to_score(1.0 - (within - drop_left - drop_right) / total) >= min_score {
+            return num_removals - 1;
+        }
+    }
+
+    max_removals
+}
+
+// Removals which the champion survives. The heuristic limits the search to fewer removals than
+// it needs to break the champion, and the search of is_robust2 looks for earlier breaks.
+fn removals_survived(
+    champion: &Contender,
+    runnerup: &Contender,
+    max_removals: usize,
+    max_weight: u32,
+    criterion: SplitCriterion
+) -> usize {
+
+    if score_difference(champion, runnerup) < 0 {
+        return 0;
+    }
+
+    let max_removals = (1..=max_removals)
+        .find(|num_removals| weaken_heavily(champion, runnerup, *num_removals, max_weight, criterion).is_some())
+        .map_or(max_removals, |num_removals| num_removals - 1);
+
+    let (is_robust, num_removals, _) = is_robust_against(champion, runnerup, max_removals, max_weight, criterion);
+
+    if is_robust {
+        max_removals
+    } else {
+        num_removals - 1
+    }
+}
+
 pub fn is_robust2(
     current_champion_stats: &SplitStats,
     current_runnerup_stats: &SplitStats,
     threshold: usize,
+    criterion: SplitCriterion,
     _dbg: bool,
 ) -> (bool, usize, i64) {
 
     assert!(current_champion_stats.has_positive_score());
     assert!(current_runnerup_stats.has_positive_score());
 
+    is_robust_against(
+        &Contender::Split(*current_champion_stats),
+        &Contender::Split(*current_runnerup_stats),
+        threshold,
+        1,
+        criterion
+    )
+}
+
+fn is_robust_against(
+    current_champion: &Contender,
+    current_runnerup: &Contender,
+    threshold: usize,
+    max_weight: u32,
+    criterion: SplitCriterion
+) -> (bool, usize, i64) {
+
     let mut scratch_space = Vec::with_capacity(8);
 
     let mut candidates = Vec::new();
 
-    let mut frontier = vec![(current_champion_stats.clone(), current_runnerup_stats.clone())];
-    let mut current_minimal_score_diff =
-        current_champion_stats.score.unwrap() - current_runnerup_stats.score.unwrap();
+    let mut frontier = vec![(*current_champion, *current_runnerup)];
+    let mut current_minimal_score_diff = score_difference(current_champion, current_runnerup);
 
     let mut num_removals = 0;
     let mut is_robust = true;
@@ -148,12 +866,13 @@ pub fn is_robust2(
 
         //println!("Frontier {}, {}", frontier.len(), current_minimal_score_diff);
 
-        for (stats_a, stats_b) in &frontier {
+        for (contender_a, contender_b) in &frontier {
             //println!("\tComparison {} vs {}", (stats_a.score - stats_b.score), current_minimal_score_diff);
-            if stats_a.score.unwrap() - stats_b.score.unwrap() <= current_minimal_score_diff {
+            if score_difference(contender_a, contender_b) <= current_minimal_score_diff {
                 scratch_space.clear();
-                let score_diff_found = weaken_split(stats_a, stats_b, &mut scratch_space);
-                candidates.extend(scratch_space.drain(..));
+                let score_diff_found =
+                    weaken_split(contender_a, contender_b, max_weight, criterion, &mut scratch_space);
+                candidates.append(&mut scratch_space);
                 //println!("Candidates {}", candidates.len());
                 if score_diff_found <= current_minimal_score_diff {
                     current_minimal_score_diff = score_diff_found;
@@ -185,107 +904,103 @@ pub fn is_robust2(
     (is_robust, num_removals, current_minimal_score_diff)
 }
 
-fn weaken_split(
-    initial_champion: &SplitStats,
-    initial_runnerup: &SplitStats,
-    weakest_pairs: &mut Vec<(SplitStats, SplitStats)>
-) -> i64 {
-    let truefalse = [true, false];
-
-    let mut score_diff_to_beat =
-        initial_champion.score.unwrap() as f64 - initial_runnerup.score.unwrap() as f64;
-    //
-    // println!("Weaken ({},{},{},{}) vs ({},{},{},{}) with {}",
-    //          initial_champion.num_plus_left,
-    //          initial_champion.num_minus_left,
-    //          initial_champion.num_plus_right,
-    //          initial_champion.num_minus_right,
-    //          initial_runnerup.num_plus_left,
-    //          initial_runnerup.num_minus_left,
-    //          initial_runnerup.num_plus_right,
-    //          initial_runnerup.num_minus_right,
-    //          (score_diff_to_beat * 1_000_000_000_000_f64) as i64
-    // );
+// A split in the robustness search, or a threshold which stands in for a split with exactly the
+// minimum score of the stopping rules. Removals never change the score of a threshold.
+#[derive(Debug, Clone, Copy)]
+enum Contender {
+    Split(SplitStats),
+    Threshold(i64),
+}
 
-    // Stop if we produce a split which is constant on both sides
-    if (initial_runnerup.num_minus_left == 0 && initial_runnerup.num_minus_right == 0) ||
-       (initial_runnerup.num_plus_left == 0 && initial_runnerup.num_minus_right == 0) ||
-       (initial_runnerup.num_minus_left == 0 && initial_runnerup.num_plus_right == 0) ||
-       (initial_runnerup.num_plus_left == 0 && initial_runnerup.num_plus_right == 0) ||
-        initial_runnerup.score.is_none() {
+impl Contender {
 
-        return (score_diff_to_beat * 1_000_000_000_000_f64) as i64
+    fn score(&self) -> Option<i64> {
+        match self {
+            Contender::Split(stats) => stats.score,
+            Contender::Threshold(min_score) => Some(*min_score),
+        }
     }
 
+    // A threshold has a single side, which holds samples of every class
+    fn sides(&self) -> &'static [bool] {
+        match self {
+            Contender::Split(_) => &[true, false],
+            Contender::Threshold(_) => &[true],
+        }
+    }
 
-    for is_plus in truefalse.iter() {
-        for passes_first in truefalse.iter() {
-            for passes_second in truefalse.iter() {
-                let mut champion = initial_champion.clone();
-                let mut runnerup = initial_runnerup.clone();
+    fn class_counts(&self, is_left: bool) -> Option<&ClassCounts> {
+        match self {
+            Contender::Split(stats) => Some(stats.side(is_left).class_counts()),
+            Contender::Threshold(_) => None,
+        }
+    }
 
-                if *is_plus && *passes_first && *passes_second {
-                    if champion.num_plus_left != 0 && runnerup.num_plus_left != 0 {
-                        champion.num_plus_left -= 1;
-                        runnerup.num_plus_left -= 1;
-                    } else {
-                        continue;
-                    }
-                } else if *is_plus && !*passes_first && *passes_second {
-                    if champion.num_plus_right != 0 && runnerup.num_plus_left != 0 {
-                        champion.num_plus_right -= 1;
-                        runnerup.num_plus_left -= 1;
-                    } else {
-                        continue;
-                    }
-                } else if *is_plus && *passes_first && !*passes_second {
-                    if champion.num_plus_left != 0 && runnerup.num_plus_right != 0 {
-                        champion.num_plus_left -= 1;
-                        runnerup.num_plus_right -= 1;
-                    } else {
-                        continue;
-                    }
-                } else if *is_plus && !*passes_first && !*passes_second {
-                    if champion.num_plus_right != 0 && runnerup.num_plus_right != 0 {
-                        champion.num_plus_right -= 1;
-                        runnerup.num_plus_right -= 1;
-                    } else {
-                        continue;
-                    }
-                } else if !*is_plus && *passes_first && *passes_second {
-                    if champion.num_minus_left != 0 && runnerup.num_minus_left != 0 {
-                        champion.num_minus_left -= 1;
-                        runnerup.num_minus_left -= 1;
-                    } else {
-                        continue;
-                    }
-                } else if !*is_plus && !*passes_first && *passes_second {
-                    if champion.num_minus_right != 0 && runnerup.num_minus_left != 0 {
-                        champion.num_minus_right -= 1;
-                        runnerup.num_minus_left -= 1;
-                    } else {
-                        continue;
-                    }
-                } else if !*is_plus && *passes_first && !*passes_second {
-                    if champion.num_minus_left != 0 && runnerup.num_minus_right != 0 {
-                        champion.num_minus_left -= 1;
-                        runnerup.num_minus_right -= 1;
-                    } else {
-                        continue;
-                    }
-                } else if !*is_plus && !*passes_first && !*passes_second {
-                    if champion.num_minus_right != 0 && runnerup.num_minus_right != 0 {
-                        champion.num_minus_right -= 1;
-                        runnerup.num_minus_right -= 1;
-                    } else {
-                        continue;
-                    }
-                }
+    fn count_of(&self, is_left: bool, class: u8) -> u32 {
+        self.class_counts(is_left).map_or(u32::MAX, |counts| counts.get(class))
+    }
+
+    // Takes away the given weight of the class, or all of it if there is less
+    fn without(&self, is_left: bool, class: u8, weight: u64, criterion: SplitCriterion) -> Contender {
+        match self {
+            Contender::Split(stats) => {
+                let mut weakened = *stats;
+                let counts = weakened.side_mut(is_left).class_counts_mut();
+                let num = (counts.get(class) as u64).min(weight) as u32;
+                counts.remove_many(class, num);
+                weakened.update_score_and_impurity_before(criterion);
+                Contender::Split(weakened)
+            },
+            Contender::Threshold(_) => *self,
+        }
+    }
+
+    // Further removals cannot change the score of a split which is constant on both sides
+    fn is_settled(&self) -> bool {
+        match self {
+            Contender::Split(stats) =>
+                (stats.left.is_pure() && stats.right.is_pure()) || stats.score.is_none(),
+            Contender::Threshold(_) => false,
+        }
+    }
+}
+
+// Classes which a single sample on the given sides of both contenders can have
+fn shared_classes(a: &Contender, a_side: bool, b: &Contender, b_side: bool) -> Vec<u8> {
+    match (a.class_counts(a_side), b.class_counts(b_side)) {
+        (Some(a), Some(b)) => a.classes_present().filter(|class| b.get(*class) > 0).collect(),
+        (Some(counts), None) | (None, Some(counts)) => counts.classes_present().collect(),
+        (None, None) => Vec::new(),
+    }
+}
+
+// A split without a score loses against every other contender
+fn score_difference(champion: &Contender, runnerup: &Contender) -> i64 {
+    champion.score().unwrap_or(i64::MIN).saturating_sub(runnerup.score().unwrap_or(i64::MIN))
+}
+
+fn weaken_split(
+    initial_champion: &Contender,
+    initial_runnerup: &Contender,
+    max_weight: u32,
+    criterion: SplitCriterion,
+    weakest_pairs: &mut Vec<(Contender, Contender)>
+) -> i64 {
+
+    let mut score_diff_to_beat = score_difference(initial_champion, initial_runnerup) as f64;
 
-                champion.update_score_and_impurity_before();
-                runnerup.update_score_and_impurity_before();
+    // Stop if we produce a split which is constant on both sides
+    if initial_runnerup.is_settled() {
+        return (score_diff_to_beat * 1_000_000_000_000_f64) as i64
+    }
 
-                let new_score_diff = champion.score.unwrap() as f64 - runnerup.score.unwrap() as f64;
+    for passes_first in initial_champion.sides() {
+        for passes_second in initial_runnerup.sides() {
+            for class in shared_classes(initial_champion, *passes_first, initial_runnerup, *passes_second) {
+                let champion = initial_champion.without(*passes_first, class, max_weight as u64, criterion);
+                let runnerup = initial_runnerup.without(*passes_second, class, max_weight as u64, criterion);
+
+                let new_score_diff = score_difference(&champion, &runnerup) as f64;
 
                 //println!("{} vs {}", score_diff_to_beat, new_score_diff);
 
@@ -302,32 +1017,51 @@ fn weaken_split(
 
     //println!("Returning {} weakest pairs", weakest_pairs.len());
 
-    return (score_diff_to_beat * 1_000_000_000_000_f64) as i64
+    (score_diff_to_beat * 1_000_000_000_000_f64) as i64
 }
 
-pub fn gini_with_impurity_before(
-    num_plus_left: u32,
-    num_minus_left: u32,
-    num_plus_right: u32,
-    num_minus_right: u32,
-) -> (Option<i64>, f64, f64) {
-
-
-    let num_plus = num_plus_left + num_plus_right;
-    let num_minus = num_minus_left + num_minus_right;
-    let num_samples = num_plus + num_minus;
-
-    let p_plus = num_plus as f64 / num_samples as f64;
+pub fn heuristic(
+    s: &SplitStats,
+    t: &SplitStats,
+    r: u32,
+    criterion: SplitCriterion
+) -> (bool, Option<(SplitStats, SplitStats)>) {
+
+    match weaken_heavily(&Contender::Split(*s), &Contender::Split(*t), r as usize, 1, criterion) {
+        Some((Contender::Split(s_weakened), Contender::Split(t_weakened))) =>
+            (false, Some((s_weakened, t_weakened))),
+        _ => (true, None),
+    }
+}
 
-    let impurity_before = 2.0 * p_plus * (1.0 - p_plus);
+// Removes r samples of a shared class from a side of both contenders at once, and returns the
+// weakened contenders if this lets the runner-up overtake the champion
+fn weaken_heavily(
+    s: &Contender,
+    t: &Contender,
+    r: usize,
+    max_weight: u32,
+    criterion: SplitCriterion
+) -> Option<(Contender, Contender)> {
+
+    let removed_weight = r as u64 * max_weight as u64;
+
+    for s_side in s.sides() {
+        for t_side in t.sides() {
+            for class in shared_classes(s, *s_side, t, *t_side) {
+                if s.count_of(*s_side, class) as usize >= r && t.count_of(*t_side, class) as usize >= r {
+                    let s_weakened = s.without(*s_side, class, removed_weight, criterion);
+                    let t_weakened = t.without(*t_side, class, removed_weight, criterion);
+
+                    if t_weakened.score() > s_weakened.score() {
+                        return Some((s_weakened, t_weakened));
+                    }
+                }
+            }
+        }
+    }
 
-    gini(
-        impurity_before,
-        num_plus_left,
-        num_minus_left,
-        num_plus_right,
-        num_minus_right,
-    )
+    None
 }
 
 #[inline(always)]
@@ -336,16 +1070,16 @@ pub fn gini_impurity(num_plus: u32, num_samples: u32) -> f64 {
     2.0 * p_plus * (1.0 - p_plus)
 }
 
-fn gini(
+// Weighted decrease of the gini impurity or the entropy, or of the variance for regression
+fn impurity_decrease(
+    criterion: SplitCriterion,
     impurity_before: f64,
-    num_plus_left: u32,
-    num_minus_left: u32,
-    num_plus_right: u32,
-    num_minus_right: u32
+    left: &LabelStats,
+    right: &LabelStats
 ) -> (Option<i64>, f64, f64) {
 
-    let num_samples_left = num_plus_left + num_minus_left;
-    let num_samples_right = num_plus_right + num_minus_right;
+    let num_samples_left = left.total();
+    let num_samples_right = right.total();
 
     // We don't want such splits
     if num_samples_left == 0 || num_samples_right == 0 {
@@ -353,159 +1087,363 @@ fn gini(
         //return (0, 0.0, 0.0);
     }
 
-    let gini_left = gini_impurity(num_plus_left, num_samples_left);
-    let gini_right = gini_impurity(num_plus_right, num_samples_right);
+    let impurity_left = criterion.impurity(left);
+    let impurity_right = criterion.impurity(right);
 
     let num_samples = num_samples_left + num_samples_right;
 
-    let score = impurity_before -
-        (num_samples_left as f64 / num_samples as f64) * gini_left -
-        (num_samples_right as f64 / num_samples as f64) * gini_right;
+    let mut score = impurity_before -
+        (num_samples_left as f64 / num_samples as f64) * impurity_left -
+        (num_samples_right as f64 / num_samples as f64) * impurity_right;
+
+    // Variance reductions are relative to the variance before, which keeps the fixed point
+    // scores in range independent of the scale of the targets
+    if let LabelStats::Targets(_) = left {
+        if impurity_before <= 0.0 {
+            return (None, impurity_left, impurity_right);
+        }
+        score /= impurity_before;
+    }
+
+    (Some((score * 1_000_000_000_000_f64) as i64), impurity_left, impurity_right)
+}
+
+// Distances between each class and all other classes, averaged over the classes present. For two
+// classes, this is the Hellinger distance of Cieslak and Chawla.
+fn hellinger_distance(left: &ClassCounts, right: &ClassCounts) -> f64 {
+    let counts = left.merged(right);
+    let num_samples = counts.total();
+
+    let mut sum_of_distances = 0.0;
+    let mut num_classes = 0;
 
-    // if score.is_nan() {
-    //     println!("[{},{},{},{}]", num_plus_left, num_minus_left, num_plus_right, num_minus_right);
-    //     println!("{}, {}, {}, {}", score, gini_left, gini_right, impurity_before);
-    //     panic!("Invalid score encountered!");
-    // }
+    for class in counts.classes_present() {
+        let num_of_class = counts.get(class);
+        let num_of_others = num_samples - num_of_class;
 
-    (Some((score * 1_000_000_000_000_f64) as i64), gini_left, gini_right)
+        if num_of_others == 0 {
+            continue;
+        }
+
+        let side_distance = |side: &ClassCounts| {
+            let share_of_class = side.get(class) as f64 / num_of_class as f64;
+            let share_of_others = (side.total() - side.get(class)) as f64 / num_of_others as f64;
+            share_of_class.sqrt() - share_of_others.sqrt()
+        };
+
+        let (distance_left, distance_right) = (side_distance(left), side_distance(right));
+
+        sum_of_distances += (distance_left * distance_left + distance_right * distance_right).sqrt();
+        num_classes += 1;
+    }
+
+    if num_classes == 0 {
+        0.0
+    } else {
+        sum_of_distances / num_classes as f64
+    }
 }
 
 pub fn fmt_score(s: i64) -> f64 {
     s as f64 / 1_000_000_000_000_f64
 }
 
+pub fn to_score(impurity_decrease: f64) -> i64 {
+    (impurity_decrease * 1_000_000_000_000_f64) as i64
+}
 
 
-pub fn heuristic(s: &SplitStats, t: &SplitStats, r: u32) -> (bool, Option<(SplitStats, SplitStats)>) {
+#[cfg(test)]
+mod tests {
 
-    if s.num_plus_left >= r && t.num_plus_right >= r {
-        let mut s_plus_a = s.clone();
-        let mut t_plus_a = t.clone();
+    use crate::split_stats::{ClassCounts, LabelStats, LeafStats, SplitCriterion, SplitStats, TargetStats,
+        is_robust, is_robust_with_weights, removals_until_admissible, removals_until_below, to_score};
 
-        s_plus_a.num_plus_left -= r;
-        t_plus_a.num_plus_right -= r;
+    #[test]
+    fn criteria_score_splits() {
+        assert!((ClassCounts::binary(5, 5).entropy() - 1.0).abs() < 1e-12);
+        assert_eq!(ClassCounts::binary(0, 7).entropy(), 0.0);
 
-        s_plus_a.update_score_and_impurity_before();
-        t_plus_a.update_score_and_impurity_before();
+        let mut separating = SplitStats::new(10, 0, 0, 10);
+        separating.update_score_and_impurity_before(SplitCriterion::Gini);
+        assert_eq!(separating.score, Some(to_score(0.5)));
 
-        if t_plus_a.score > s_plus_a.score {
-            return (false, Some((s_plus_a, t_plus_a)));
-        }
-    }
-    // ---
+        separating.update_score_and_impurity_before(SplitCriterion::Entropy);
+        assert_eq!(separating.score, Some(to_score(1.0)));
 
-    if s.num_plus_right >= r && t.num_plus_left >= r {
-        let mut s_plus_b = s.clone();
-        let mut t_plus_b = t.clone();
+        separating.update_score_and_impurity_before(SplitCriterion::Hellinger);
+        assert_eq!(separating.score, Some(to_score(2.0_f64.sqrt())));
 
-        s_plus_b.num_plus_right -= r;
-        t_plus_b.num_plus_left -= r;
+        // The Hellinger distance ignores the class priors
+        let mut balanced = SplitStats::new(8, 2, 2, 8);
+        let mut imbalanced = SplitStats::new(80, 2, 20, 8);
+        balanced.update_score_and_impurity_before(SplitCriterion::Hellinger);
+        imbalanced.update_score_and_impurity_before(SplitCriterion::Hellinger);
+        assert_eq!(balanced.score, imbalanced.score);
 
-        s_plus_b.update_score_and_impurity_before();
-        t_plus_b.update_score_and_impurity_before();
+        let mut empty_side = SplitStats::new(10, 10, 0, 0);
+        empty_side.update_score_and_impurity_before(SplitCriterion::Entropy);
+        assert_eq!(empty_side.score, None);
+    }
 
-        if t_plus_b.score > s_plus_b.score {
-            return (false, Some((s_plus_b, t_plus_b)));
+    #[test]
+    fn robustness_is_checked_under_the_criterion() {
+        for criterion in [SplitCriterion::Gini, SplitCriterion::Entropy, SplitCriterion::Hellinger].iter() {
+            let mut champion = SplitStats::new(90, 10, 10, 90);
+            let mut close_runnerup = SplitStats::new(88, 12, 12, 88);
+            let mut weak_runnerup = SplitStats::new(60, 40, 40, 60);
+
+            champion.update_score_and_impurity_before(*criterion);
+            close_runnerup.update_score_and_impurity_before(*criterion);
+            weak_runnerup.update_score_and_impurity_before(*criterion);
+
+            assert!(!is_robust(&champion, &close_runnerup, 5, *criterion).0);
+            assert!(is_robust(&champion, &weak_runnerup, 5, *criterion).0);
         }
     }
-    // ---
 
-    if s.num_plus_left >= r && t.num_plus_left >= r {
-        let mut s_plus_c = s.clone();
-        let mut t_plus_c = t.clone();
+    #[test]
+    fn weighted_robustness_is_searched_in_removals() {
+        let scored = |num_plus_left: u32, num_minus_left: u32, num_plus_right: u32, num_minus_right: u32, weight: u32| {
+            let mut stats = SplitStats::new(
+                num_plus_left * weight,
+                num_minus_left * weight,
+                num_plus_right * weight,
+                num_minus_right * weight
+            );
+            stats.update_score_and_impurity_before(SplitCriterion::Gini);
+            stats
+        };
+
+        for runnerup_counts in [(88, 12, 12, 88), (80, 20, 25, 75), (60, 40, 40, 60)].iter() {
+            let (plus_left, minus_left, plus_right, minus_right) = *runnerup_counts;
+
+            let champion = scored(90, 10, 10, 90, 1);
+            let runnerup = scored(plus_left, minus_left, plus_right, minus_right, 1);
+
+            // Removing samples of weight 1000 from counts scaled by 1000 leaves the same scores
+            let heavy_champion = scored(90, 10, 10, 90, 1000);
+            let heavy_runnerup = scored(plus_left, minus_left, plus_right, minus_right, 1000);
+
+            assert_eq!(
+                is_robust_with_weights(&heavy_champion, &heavy_runnerup, 8, 1000, SplitCriterion::Gini),
+                is_robust(&champion, &runnerup, 8, SplitCriterion::Gini)
+            );
+        }
 
-        s_plus_c.num_plus_left -= r;
-        t_plus_c.num_plus_left -= r;
+        // Budgets in units of weight would not fit into the counts
+        let champion = scored(90, 10, 10, 90, 1000);
+        let runnerup = scored(60, 40, 40, 60, 1000);
+        assert!(!is_robust_with_weights(&champion, &runnerup, 10, 1 << 30, SplitCriterion::Gini).0);
+    }
 
-        s_plus_c.update_score_and_impurity_before();
-        t_plus_c.update_score_and_impurity_before();
+    fn regression_split(targets: &[f64], goes_left: &[bool], removed: &[bool]) -> SplitStats {
+        let mut left = TargetStats::new();
+        let mut right = TargetStats::new();
 
-        if t_plus_c.score > s_plus_c.score {
-            return (false, Some((s_plus_c, t_plus_c)));
+        for ((target, is_left), is_removed) in targets.iter().zip(goes_left).zip(removed) {
+            if *is_removed {
+                continue;
+            }
+            if *is_left { left.add(*target) } else { right.add(*target) }
         }
+
+        let mut stats = SplitStats::from_targets(left, right);
+        stats.update_score_and_impurity_before(SplitCriterion::Gini);
+        stats
     }
-    // ---
 
-    if s.num_plus_right >= r && t.num_plus_right >= r {
-        let mut s_plus_d = s.clone();
-        let mut t_plus_d = t.clone();
+    #[test]
+    fn regression_splits_can_be_non_robust() {
+        let targets = [1.0, 1.1, 0.9, 1.0, 5.0, 5.2, 4.8, 5.0];
+        let none_removed = [false; 8];
 
-        s_plus_d.num_plus_right -= r;
-        t_plus_d.num_plus_right -= r;
+        let champion = regression_split(&targets, &[true, true, true, true, false, false, false, false], &none_removed);
+        let close_runnerup = regression_split(&targets, &[true, true, true, false, false, false, false, false], &none_removed);
+        let weak_runnerup = regression_split(&targets, &[true, false, true, false, true, false, true, false], &none_removed);
 
-        s_plus_d.update_score_and_impurity_before();
-        t_plus_d.update_score_and_impurity_before();
+        assert!(champion.score > close_runnerup.score);
+        assert!(!is_robust(&champion, &close_runnerup, 1, SplitCriterion::Gini).0);
+        assert!(is_robust(&champion, &weak_runnerup, 1, SplitCriterion::Gini).0);
 
-        if t_plus_d.score > s_plus_d.score {
-            return (false, Some((s_plus_d, t_plus_d)));
-        }
+        // Removing a single sample of weight two already breaks the split against the weak runner-up
+        assert!(!is_robust_with_weights(&champion, &weak_runnerup, 2, 2, SplitCriterion::Gini).0);
+        assert!(!is_robust(&champion, &weak_runnerup, 4, SplitCriterion::Gini).0);
     }
-    // ---
 
-    if s.num_minus_left >= r && t.num_minus_right >= r {
-        let mut s_minus_a = s.clone();
-        let mut t_minus_a = t.clone();
+    #[test]
+    fn regression_robustness_holds_for_every_removal() {
+        let targets = [0.3, 1.5, 0.7, 9.0, 0.1, 8.4, 9.2, 0.0, 9.6, 1.3, 8.8, 9.9];
+        let num_samples = targets.len();
 
-        s_minus_a.num_minus_left -= r;
-        t_minus_a.num_minus_right -= r;
+        let goes_left = |mask: u32| (0..num_samples).map(|index| mask & (1 << index) != 0).collect::<Vec<_>>();
+        let none_removed = vec![false; num_samples];
 
-        s_minus_a.update_score_and_impurity_before();
-        t_minus_a.update_score_and_impurity_before();
+        let champion_left: Vec<bool> = targets.iter().map(|target| *target < 5.0).collect();
+        let champion = regression_split(&targets, &champion_left, &none_removed);
 
-        if t_minus_a.score > s_minus_a.score {
-            return (false, Some((s_minus_a, t_minus_a)));
-        }
-    }
-    // ---
+        let mut num_robust = 0;
+        let mut num_non_robust = 0;
+
+        for runnerup_mask in 1..(1_u32 << num_samples) - 1 {
+            let runnerup_left = goes_left(runnerup_mask);
+            let runnerup = regression_split(&targets, &runnerup_left, &none_removed);
+
+            if !runnerup.has_positive_score() || champion.score <= runnerup.score {
+                continue;
+            }
 
-    if s.num_minus_right >= r && t.num_minus_left >= r {
-        let mut s_minus_b = s.clone();
-        let mut t_minus_b = t.clone();
+            let (is_robust_split, _) = is_robust(&champion, &runnerup, 2, SplitCriterion::Gini);
 
-        s_minus_b.num_minus_right -= r;
-        t_minus_b.num_minus_left -= r;
+            if !is_robust_split {
+                num_non_robust += 1;
+                continue;
+            }
+
+            num_robust += 1;
+
+            // No removal of up to two samples lets the runner-up catch up
+            for removal_mask in 1..(1_u32 << num_samples) {
+                if removal_mask.count_ones() > 2 {
+                    continue;
+                }
+
+                let removed = goes_left(removal_mask);
+                let champion_after = regression_split(&targets, &champion_left, &removed);
+                let runnerup_after = regression_split(&targets, &runnerup_left, &removed);
+
+                assert!(champion_after.score > runnerup_after.score);
+            }
+        }
 
-        s_minus_b.update_score_and_impurity_before();
-        t_minus_b.update_score_and_impurity_before();
+        assert!(num_robust > 0 && num_non_robust > 0);
+    }
 
-        if t_minus_b.score > s_minus_b.score {
-            return (false, Some((s_minus_b, t_minus_b)));
+    // Every removal of up to the given number of samples from the counts of a binary split
+    fn binary_splits_after_removals(stats: &SplitStats, num_removals: u32) -> Vec<SplitStats> {
+        let mut splits = Vec::new();
+        for plus_left in 0..=stats.num_plus_left() {
+            for minus_left in 0..=stats.num_minus_left() {
+                for plus_right in 0..=stats.num_plus_right() {
+                    for minus_right in 0..=stats.num_minus_right() {
+                        if plus_left + minus_left + plus_right + minus_right <= num_removals {
+                            let mut split = SplitStats::new(
+                                stats.num_plus_left() - plus_left,
+                                stats.num_minus_left() - minus_left,
+                                stats.num_plus_right() - plus_right,
+                                stats.num_minus_right() - minus_right
+                            );
+                            split.update_score_and_impurity_before(SplitCriterion::Gini);
+                            splits.push(split);
+                        }
+                    }
+                }
+            }
         }
+        splits
     }
-    // ---
 
-    if s.num_minus_left >= r && t.num_minus_left >= r {
-        let mut s_minus_c = s.clone();
-        let mut t_minus_c = t.clone();
+    #[test]
+    fn minimum_scores_hold_for_every_removal() {
+        let min_score = to_score(0.05);
+        let max_removals = 4;
 
-        s_minus_c.num_minus_left -= r;
-        t_minus_c.num_minus_left -= r;
+        let mut num_limited = [0, 0];
 
-        s_minus_c.update_score_and_impurity_before();
-        t_minus_c.update_score_and_impurity_before();
+        for counts in 0..7_u32.pow(4) {
+            let mut stats = SplitStats::new(counts % 7, counts / 7 % 7, counts / 49 % 7, counts / 343);
+            stats.update_score_and_impurity_before(SplitCriterion::Gini);
 
-        if t_minus_c.score > s_minus_c.score {
-            return (false, Some((s_minus_c, t_minus_c)));
+            if !stats.has_positive_score() {
+                continue;
+            }
+
+            let is_admissible = stats.score.unwrap() >= min_score;
+
+            let num_removals = if is_admissible {
+                removals_until_below(&stats, min_score, max_removals, 1, SplitCriterion::Gini)
+            } else {
+                removals_until_admissible(&stats, min_score, max_removals, 1, SplitCriterion::Gini)
+            };
+
+            let stays_admissible = |split: &SplitStats| matches!(split.score, Some(score) if score >= min_score);
+
+            for split in binary_splits_after_removals(&stats, num_removals as u32) {
+                assert_eq!(stays_admissible(&split), is_admissible, "{} after {}", stats.fmt(), split.fmt());
+            }
+
+            // A single further removal crosses the minimum score
+            if num_removals < max_removals {
+                assert!(binary_splits_after_removals(&stats, num_removals as u32 + 1).iter()
+                    .any(|split| stays_admissible(split) != is_admissible));
+                num_limited[is_admissible as usize] += 1;
+            }
         }
+
+        assert!(num_limited[0] > 0 && num_limited[1] > 0);
     }
 
-    // ---
+    #[test]
+    fn regression_minimum_scores_hold_for_every_removal() {
+        let targets = [0.3, 1.5, 0.7, 9.0, 0.1, 8.4, 9.2, 0.0, 9.6, 1.3];
+        let num_samples = targets.len();
+        let max_removals = 3;
 
-    if s.num_minus_right >= r && t.num_minus_right >= r {
-        let mut s_minus_d = s.clone();
-        let mut t_minus_d = t.clone();
+        let goes_left = |mask: u32| (0..num_samples).map(|index| mask & (1 << index) != 0).collect::<Vec<_>>();
+        let none_removed = vec![false; num_samples];
 
-        s_minus_d.num_minus_right -= r;
-        t_minus_d.num_minus_right -= r;
+        let mut num_limited = [0, 0];
+
+        for split_mask in 1..(1_u32 << num_samples) - 1 {
+            let split_left = goes_left(split_mask);
+            let stats = regression_split(&targets, &split_left, &none_removed);
+
+            if !stats.has_positive_score() {
+                continue;
+            }
 
-        s_minus_d.update_score_and_impurity_before();
-        t_minus_d.update_score_and_impurity_before();
+            for min_score in [to_score(0.3), to_score(0.8)].iter() {
+                let is_admissible = stats.score.unwrap() >= *min_score;
 
-        if t_minus_d.score > s_minus_d.score {
-            return (false, Some((s_minus_d, t_minus_d)));
+                let num_removals = if is_admissible {
+                    removals_until_below(&stats, *min_score, max_removals, 1, SplitCriterion::Gini)
+                } else {
+                    removals_until_admissible(&stats, *min_score, max_removals, 1, SplitCriterion::Gini)
+                };
+
+                if num_removals < max_removals {
+                    num_limited[is_admissible as usize] += 1;
+                }
+
+                for removal_mask in 1..(1_u32 << num_samples) {
+                    if removal_mask.count_ones() as usize > num_removals {
+                        continue;
+                    }
+
+                    let split = regression_split(&targets, &split_left, &goes_left(removal_mask));
+                    let stays_admissible = matches!(split.score, Some(score) if score >= *min_score);
+
+                    assert_eq!(stays_admissible, is_admissible);
+                }
+            }
         }
+
+        assert!(num_limited[0] > 0 && num_limited[1] > 0);
     }
 
-    return (true, None);
+    #[test]
+    fn leaves_only_keep_the_counts_of_their_classes() {
+        let binary = LabelStats::Classes(ClassCounts::binary(3, 4));
+        assert_eq!(LeafStats::new(&binary), LeafStats::Binary { num_minus: 4, num_plus: 3 });
+        assert_eq!(LeafStats::new(&binary).label_stats(), binary);
+        assert!(bincode::serialize(&LeafStats::new(&binary)).unwrap().len() < bincode::serialize(&binary).unwrap().len());
+
+        let mut counts = ClassCounts::new();
+        counts.add(0);
+        counts.add(3);
+        let multi_class = LabelStats::Classes(counts);
+        assert_eq!(LeafStats::new(&multi_class), LeafStats::Classes(vec![1, 0, 0, 1].into()));
+        assert_eq!(LeafStats::new(&multi_class).label_stats(), multi_class);
+        assert_eq!(LeafStats::new(&multi_class).total(), 2);
+    }
 }
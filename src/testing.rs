@@ -0,0 +1,125 @@
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use crate::dataset::{AttributeType, Task, MISSING};
+use crate::schema::{AttributeSpec, RowSample, Schema, SchemaDataset};
+
+// Small synthetic dataset with a noisy label that depends on the first two attributes
+pub fn synthetic_samples(num_samples: usize) -> (SchemaDataset, Vec<RowSample>) {
+    synthetic_samples_with_classes(num_samples, 2)
+}
+
+// Like synthetic_samples, but the first attribute is missing for every third negative sample and
+// the third one for every seventh sample
+pub fn synthetic_samples_with_missing_values(num_samples: usize) -> (SchemaDataset, Vec<RowSample>) {
+    let (dataset, mut samples) = synthetic_samples(num_samples);
+
+    for sample in samples.iter_mut() {
+        if sample.label == 0 && sample.record_id % 3 == 0 {
+            sample.values[0] = MISSING;
+        }
+        if sample.record_id % 7 == 0 {
+            sample.values[2] = MISSING;
+        }
+    }
+
+    (dataset, samples)
+}
+
+// Like synthetic_samples, but every fifth sample weighs three times as much as the others
+pub fn synthetic_weighted_samples(num_samples: usize) -> (SchemaDataset, Vec<RowSample>) {
+    let (dataset, mut samples) = synthetic_samples(num_samples);
+
+    for sample in samples.iter_mut() {
+        if sample.record_id % 5 == 0 {
+            sample.weight = 3;
+        }
+    }
+
+    (dataset, samples)
+}
+
+// Like synthetic_samples, but the classes partition the range of a + 2b into equally wide bands
+pub fn synthetic_samples_with_classes(num_samples: usize, num_classes: u8) -> (SchemaDataset, Vec<RowSample>) {
+    let mut rng = XorShiftRng::from_seed([7; 16]);
+
+    let samples: Vec<RowSample> = (0..num_samples)
+        .map(|record_id| {
+            let values = vec![rng.gen_range(0, 16), rng.gen_range(0, 8), rng.gen_range(0, 4)];
+            let label = if num_classes == 2 {
+                (values[0] + values[1] * 2 > 12 || rng.gen_range(0, 10) == 0) as u8
+            } else if rng.gen_range(0, 10) == 0 {
+                rng.gen_range(0, num_classes)
+            } else {
+                ((values[0] as u32 + values[1] as u32 * 2) * num_classes as u32 / 30) as u8
+            };
+            RowSample { record_id: record_id as u64, values, label, target: label as f64, weight: 1 }
+        })
+        .collect();
+
+    (synthetic_dataset(num_classes, Task::Classification, &samples), samples)
+}
+
+// Noisy numeric target which grows with the first two attributes and jumps for the third one
+pub fn synthetic_regression_samples(num_samples: usize) -> (SchemaDataset, Vec<RowSample>) {
+    let mut rng = XorShiftRng::from_seed([11; 16]);
+
+    let samples: Vec<RowSample> = (0..num_samples)
+        .map(|record_id| {
+            let values = vec![rng.gen_range(0, 16), rng.gen_range(0, 8), rng.gen_range(0, 4)];
+            let step = if values[2] == 3 { 50.0 } else { 0.0 };
+            let target = values[0] as f64 * 2.0 + values[1] as f64 * 5.0 + step + rng.gen_range(-2.0, 2.0);
+            RowSample { record_id: record_id as u64, values, label: 0, target, weight: 1 }
+        })
+        .collect();
+
+    (synthetic_dataset(2, Task::Regression, &samples), samples)
+}
+
+// Zip codes with a thousand categories and amounts with 5000 bins, the label depends on both
+pub fn synthetic_high_cardinality_samples(num_samples: usize) -> (SchemaDataset, Vec<RowSample>) {
+    let mut rng = XorShiftRng::from_seed([13; 16]);
+
+    let samples: Vec<RowSample> = (0..num_samples)
+        .map(|record_id| {
+            let values = vec![rng.gen_range(0, 1000), rng.gen_range(0, 5000)];
+            let label = (values[0] >= 900 || values[1] > 2500 || rng.gen_range(0, 10) == 0) as u8;
+            RowSample { record_id: record_id as u64, values, label, target: label as f64, weight: 1 }
+        })
+        .collect();
+
+    let attributes = vec![
+        AttributeSpec { name: "zip".to_string(), attribute_type: AttributeType::Categorical, min_value: 0, max_value: 999 },
+        AttributeSpec { name: "amount".to_string(), attribute_type: AttributeType::Numerical, min_value: 0, max_value: 4999 },
+    ];
+
+    let schema = Schema {
+        attributes,
+        label_column: "label".to_string(),
+        id_column: None,
+        num_classes: 2,
+        task: Task::Classification,
+        weight_column: None
+    };
+
+    (SchemaDataset::from_samples(schema, &samples), samples)
+}
+
+fn synthetic_dataset(num_classes: u8, task: Task, samples: &[RowSample]) -> SchemaDataset {
+    let attributes = vec![
+        AttributeSpec { name: "a".to_string(), attribute_type: AttributeType::Numerical, min_value: 0, max_value: 15 },
+        AttributeSpec { name: "b".to_string(), attribute_type: AttributeType::Numerical, min_value: 0, max_value: 7 },
+        AttributeSpec { name: "c".to_string(), attribute_type: AttributeType::Categorical, min_value: 0, max_value: 3 },
+    ];
+
+    let schema = Schema {
+        attributes,
+        label_column: "label".to_string(),
+        id_column: None,
+        num_classes,
+        task,
+        weight_column: None
+    };
+
+    SchemaDataset::from_samples(schema, samples)
+}
@@ -1,45 +1,336 @@
-#![allow(deprecated)] // TODO use rand_xorshift crate to remove this...
-use rand::{Rng, SeedableRng, XorShiftRng};
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
 use rand::seq::SliceRandom;
 use rayon::prelude::*;
 
 use std::marker::Sync;
 use std::borrow::Cow;
+use std::iter::FromIterator;
+use std::fmt;
 use hashbrown::HashMap;
+use serde::{Serialize, Deserialize};
+
+use crate::scan::{scan_columns, scan_column_labels, ScanKernel};
+use crate::columnar::ColumnarSamples;
+use crate::histogram::Histograms;
+use crate::utils::{as_bytes, fingerprint};
+
+use crate::split_stats::{SplitStats, ClassCounts, LabelStats, LeafStats, SplitCriterion, MAX_CLASSES, PLUS,
+    is_robust_with_weights, removals_until_admissible, removals_until_below, to_score};
+use crate::dataset::{Dataset, Sample, AttributeType, Task, MISSING};
+use crate::ledger::DeletionLedger;
+use crate::config::{ConfigError, ForestConfig, Robustness};
+use crate::bootstrap::{Resampled, MAX_WEIGHT, resample};
+
+// Samples with a missing value for the attribute go left if missing_left is set
+#[derive(Eq,PartialEq,Clone,Debug,Serialize,Deserialize)]
+pub enum Split {
+    Numerical { attribute_index: u16, cut_off: u16, missing_left: bool },
+    Categorical { attribute_index: u16, subset: CategorySet, missing_left: bool }
+}
 
-use crate::scan::{scan, scan_simd_numerical, scan_simd_categorical};
-use crate::utils::as_bytes;
 
-use crate::split_stats::{SplitStats, is_robust, gini_impurity};
-use crate::dataset::{Dataset, Sample, AttributeType};
+impl Split {
 
-#[derive(Eq,PartialEq,Clone,Debug)]
-pub enum Split {
-    Numerical { attribute_index: u8, cut_off: u8 },
-    Categorical { attribute_index: u8, subset: u64 }
+    pub fn new_numerical(attribute_index: u16, cut_off: u16) -> Split {
+        Split::Numerical { attribute_index, cut_off, missing_left: false }
+    }
+
+    pub fn new_categorical(attribute_index: u16, subset: CategorySet) -> Split {
+        Split::Categorical { attribute_index, subset, missing_left: false }
+    }
+
+    pub fn attribute_index(&self) -> u16 {
+        match self {
+            Split::Numerical { attribute_index, cut_off: _, missing_left: _ } => *attribute_index,
+            Split::Categorical { attribute_index, subset: _, missing_left: _ } => *attribute_index,
+        }
+    }
+
+    pub fn missing_left(&self) -> bool {
+        match self {
+            Split::Numerical { attribute_index: _, cut_off: _, missing_left } => *missing_left,
+            Split::Categorical { attribute_index: _, subset: _, missing_left } => *missing_left,
+        }
+    }
+
+    // The same split, but with the given direction for missing values
+    pub fn with_missing_left(&self, missing_left: bool) -> Split {
+        match self {
+            Split::Numerical { attribute_index, cut_off, missing_left: _ } =>
+                Split::Numerical { attribute_index: *attribute_index, cut_off: *cut_off, missing_left },
+            Split::Categorical { attribute_index, subset, missing_left: _ } =>
+                Split::Categorical { attribute_index: *attribute_index, subset: subset.clone(), missing_left },
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_left(&self, attribute_value: u16) -> bool {
+        if attribute_value == MISSING {
+            return self.missing_left();
+        }
+
+        match self {
+            Split::Numerical { attribute_index: _, cut_off, missing_left: _ } => attribute_value < *cut_off,
+            Split::Categorical { attribute_index: _, subset, missing_left: _ } => subset.contains(attribute_value),
+        }
+    }
+}
+
+// Subset of the values of a categorical attribute, bit i % 64 of word i / 64 is set if value i
+// is in the subset. Trailing empty words are never stored, so equal sets have equal words.
+#[derive(Eq,PartialEq,Clone,Debug,Default,Serialize,Deserialize)]
+pub struct CategorySet {
+    words: Vec<u64>,
 }
 
+impl CategorySet {
 
-impl Split {
+    pub fn new() -> CategorySet {
+        CategorySet { words: Vec::new() }
+    }
+
+    pub fn insert(&mut self, value: u16) {
+        let word = value as usize / 64;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1_u64 << (value % 64);
+    }
 
-    pub fn new_numerical(attribute_index: u8, cut_off: u8) -> Split {
-        Split::Numerical { attribute_index, cut_off }
+    #[inline(always)]
+    pub fn contains(&self, value: u16) -> bool {
+        match self.words.get(value as usize / 64) {
+            Some(word) => *word & (1_u64 << (value % 64)) != 0,
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    // Largest value in the subset
+    pub fn max_value(&self) -> Option<u16> {
+        self.words.last()
+            .map(|word| ((self.words.len() - 1) * 64 + 63 - word.leading_zeros() as usize) as u16)
+    }
+
+    // Bits of the values below 64, the SIMD scans only handle such values
+    pub fn lower_bits(&self) -> u64 {
+        self.words.first().cloned().unwrap_or(0)
+    }
+}
+
+impl From<u64> for CategorySet {
+    fn from(bits: u64) -> CategorySet {
+        if bits == 0 {
+            CategorySet::new()
+        } else {
+            CategorySet { words: vec![bits] }
+        }
+    }
+}
+
+impl FromIterator<u16> for CategorySet {
+    fn from_iter<I: IntoIterator<Item=u16>>(values: I) -> CategorySet {
+        let mut subset = CategorySet::new();
+        for value in values {
+            subset.insert(value);
+        }
+        subset
+    }
+}
+
+#[derive(Eq,PartialEq,Clone,Copy,Debug)]
+pub enum ForgetError {
+    // No sample with this record id was used for training
+    NotInTrainingSet,
+    // The record id is already in the deletion ledger
+    AlreadyForgotten,
+    // The record id is known, but the values, label or weight differ from the training sample
+    SampleMismatch,
+    // The record id is known, but the trees do not hold a sample with these values and label
+    CounterUnderflow,
+}
+
+impl fmt::Display for ForgetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ForgetError::NotInTrainingSet => write!(f, "Sample is not part of the training set"),
+            ForgetError::AlreadyForgotten => write!(f, "Sample has already been forgotten"),
+            ForgetError::SampleMismatch => write!(f, "Sample differs from the training sample with this record id"),
+            ForgetError::CounterUnderflow => write!(f, "Forgetting the sample would underflow a counter"),
+        }
+    }
+}
+
+impl std::error::Error for ForgetError {}
+
+#[derive(Eq,PartialEq,Clone,Copy,Debug)]
+pub enum AddError {
+    // A sample with this record id is already part of the training set
+    AlreadyInTrainingSet,
+    // The record id is in the deletion ledger and must not be learned again
+    Forgotten,
+    // The value of the attribute with this index is outside of its range in the training data
+    InvalidValue(u16),
+    // The class is unknown or the regression target is not finite
+    InvalidLabel,
+    // The weight is zero or above the maximum weight
+    InvalidWeight,
+}
+
+impl fmt::Display for AddError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddError::AlreadyInTrainingSet => write!(f, "Sample is already part of the training set"),
+            AddError::Forgotten => write!(f, "Sample has been forgotten before"),
+            AddError::InvalidValue(attribute_index) => write!(f, "Value of attribute {} is out of range", attribute_index),
+            AddError::InvalidLabel => write!(f, "Label of the sample is invalid"),
+            AddError::InvalidWeight => write!(f, "Weight of the sample must be between 1 and {}", MAX_WEIGHT),
+        }
     }
+}
+
+impl std::error::Error for AddError {}
+
+#[derive(Eq,PartialEq,Clone,Copy,Debug)]
+pub enum ClassWeightsError {
+    // Regression forests have no classes to weigh
+    Regression,
+    // Custom weights need exactly one weight per class
+    WrongNumberOfWeights,
+    InvalidWeight,
+}
 
-    pub fn new_categorical(attribute_index: u8, subset: u64) -> Split {
-        Split::Categorical { attribute_index, subset }
+impl fmt::Display for ClassWeightsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClassWeightsError::Regression => write!(f, "Class weights require classification"),
+            ClassWeightsError::WrongNumberOfWeights => write!(f, "Expected one weight per class"),
+            ClassWeightsError::InvalidWeight => write!(f, "Class weights must be positive and finite"),
+        }
     }
+}
+
+impl std::error::Error for ClassWeightsError {}
 
-    pub fn attribute_index(&self) -> u8 {
+#[derive(Eq,PartialEq,Clone,Copy,Debug)]
+pub enum FitError {
+    InvalidConfig(ConfigError),
+    // Two samples have this record id
+    DuplicateRecordId(u64),
+    // The record id is in the deletion ledger and must not be learned again
+    Forgotten(u64),
+}
+
+impl fmt::Display for FitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Split::Numerical { attribute_index, cut_off: _ } => *attribute_index,
-            Split::Categorical { attribute_index, subset: _ } => *attribute_index,
+            FitError::InvalidConfig(error) => write!(f, "Invalid forest config: {}", error),
+            FitError::DuplicateRecordId(record_id) => write!(f, "Duplicate record id {}", record_id),
+            FitError::Forgotten(record_id) => write!(f, "Record {} has been forgotten and cannot be used for training", record_id),
         }
     }
 }
 
+impl std::error::Error for FitError {}
+
+// Trees, fingerprints per record id and label statistics of a fitted forest
+type FittedTrees = (Vec<Tree>, HashMap<u64, u64>, LabelStats);
+
+impl From<ConfigError> for FitError {
+    fn from(error: ConfigError) -> FitError {
+        FitError::InvalidConfig(error)
+    }
+}
+
+#[derive(Eq,PartialEq,Clone,Copy,Debug)]
+pub enum AddOutcome {
+    // The forest is still identical to a forest fitted on all samples
+    Robust,
+    // Some budget is exceeded, the exhausted subtrees have to be rebuilt to restore the guarantee
+    NeedsRebuild,
+}
+
+const PREDICTION_BLOCK_SIZE: usize = 256;
+
+// Weights of the classes in the leaves, which are applied to the counts at prediction time. The
+// counts themselves stay unweighted, so that forgetting and adding samples keeps working as before.
+#[derive(PartialEq,Clone,Debug,Serialize,Deserialize)]
+pub enum ClassWeights {
+    Uniform,
+    // Inversely proportional to the class frequencies of the remaining training samples
+    Balanced,
+    // One weight per class
+    Custom(Vec<f64>),
+}
+
+// Number of removals or additions which a node can absorb while the tree stays identical to a
+// tree retrained on the updated samples. The target robustness is the one the subtree was fitted
+// with, the node may absorb fewer updates if dropped alternatives or stopping rules require it.
+#[derive(Eq,PartialEq,Clone,Copy,Debug,Serialize,Deserialize)]
+pub struct Budget {
+    pub target_robustness: u32,
+    pub max_updates: u32,
+    pub num_updates: u32,
+}
+
+impl Budget {
+
+    fn new(target_robustness: usize) -> Budget {
+        Budget::limited(target_robustness, target_robustness)
+    }
+
+    fn limited(target_robustness: usize, max_updates: usize) -> Budget {
+        assert!(max_updates <= target_robustness);
+        Budget { target_robustness: target_robustness as u32, max_updates: max_updates as u32, num_updates: 0 }
+    }
+
+    pub fn remaining(&self) -> u32 {
+        self.max_updates.saturating_sub(self.num_updates)
+    }
+
+    // Updates have used up the budget, so that no further ones are covered by the robustness
+    // guarantee. Stopping rules can leave a node without budget, which retraining it before any
+    // update would not change.
+    pub fn is_exhausted(&self) -> bool {
+        self.num_updates > 0 && self.num_updates >= self.max_updates
+    }
+
+    // More updates have been applied than the guarantee covers
+    pub fn is_exceeded(&self) -> bool {
+        self.num_updates > self.max_updates
+    }
+
+    // The last update broke the guarantee, regardless of the remaining updates
+    fn invalidate(&mut self) {
+        self.max_updates = 0;
+    }
+}
+
+#[derive(Serialize,Deserialize)]
 pub struct ExtremelyRandomizedTrees {
     pub trees: Vec<Tree>,
+    // Fingerprints of the training samples which have not been forgotten yet, by record id
+    record_ids: HashMap<u64, u64>,
+    // Ranges of the attributes in the training data, added samples have to stay within them
+    attribute_ranges: Vec<(u16, u16)>,
+    ledger: DeletionLedger,
+    config: ForestConfig,
+    num_classes: u8,
+    task: Task,
+    // Label statistics of the remaining training samples
+    label_stats: LabelStats,
+    class_weights: ClassWeights,
+    // The forest predicts the positive label if the share of trees voting for it exceeds the
+    // threshold. Each tree votes at a fixed threshold of 0.5 on its leaf probability.
+    decision_threshold: f64,
 }
 
 impl ExtremelyRandomizedTrees {
@@ -55,17 +346,10 @@ impl ExtremelyRandomizedTrees {
         where D: Dataset + Sync, S: Sample + Sync
     {
 
-        let epsilon = 1.0 / 1000.0;
+        let config = ForestConfig { seed, num_trees, min_leaf_size, max_tries_per_split, ..ForestConfig::default() };
 
-        ExtremelyRandomizedTrees::fit_with_epsilon(
-            dataset,
-            samples,
-            seed,
-            num_trees,
-            min_leaf_size,
-            max_tries_per_split,
-            epsilon
-        )
+        ExtremelyRandomizedTrees::fit_with_config(dataset, samples, config)
+            .unwrap_or_else(|error| panic!("Cannot fit the forest: {}!", error))
     }
 
     pub fn fit_with_epsilon<D, S>(
@@ -79,796 +363,3036 @@ impl ExtremelyRandomizedTrees {
     ) -> ExtremelyRandomizedTrees
         where D: Dataset + Sync, S: Sample + Sync
     {
+        let config = ForestConfig {
+            seed,
+            num_trees,
+            min_leaf_size,
+            max_tries_per_split,
+            robustness: Robustness::Epsilon(epsilon),
+            ..ForestConfig::default()
+        };
+
+        ExtremelyRandomizedTrees::fit_with_config(dataset, samples, config)
+            .unwrap_or_else(|error| panic!("Cannot fit the forest: {}!", error))
+    }
+
+    pub fn fit_with_config<D, S>(
+        dataset: &D,
+        samples: Vec<S>,
+        config: ForestConfig
+    ) -> Result<ExtremelyRandomizedTrees, FitError>
+        where D: Dataset + Sync, S: Sample + Sync
+    {
+        config.validate()?;
+
+        let (trees, record_ids, label_stats) = ExtremelyRandomizedTrees::fit_trees(dataset, samples, &config)?;
+
+        Ok(ExtremelyRandomizedTrees {
+            trees,
+            record_ids,
+            attribute_ranges: (0..dataset.num_attributes())
+                .map(|attribute_index| dataset.attribute_range(attribute_index))
+                .collect(),
+            ledger: DeletionLedger::new(),
+            config,
+            num_classes: dataset.num_classes(),
+            task: dataset.task(),
+            label_stats,
+            class_weights: ClassWeights::Uniform,
+            decision_threshold: 0.5,
+        })
+    }
+
+    fn fit_trees<D, S>(
+        dataset: &D,
+        samples: Vec<S>,
+        config: &ForestConfig
+    ) -> Result<FittedTrees, FitError>
+        where D: Dataset + Sync, S: Sample + Sync
+    {
+        match config.num_threads {
+            Some(num_threads) => rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap()
+                .install(|| ExtremelyRandomizedTrees::fit_trees_in_current_pool(dataset, &samples, config)),
+            None => ExtremelyRandomizedTrees::fit_trees_in_current_pool(dataset, &samples, config),
+        }
+    }
+
+    fn fit_trees_in_current_pool<D, S>(
+        dataset: &D,
+        samples: &[S],
+        config: &ForestConfig
+    ) -> Result<FittedTrees, FitError>
+        where D: Dataset + Sync, S: Sample + Sync
+    {
+        let num_attributes_to_try_per_split = config.max_features.resolve(dataset.num_attributes() as usize);
 
-        let num_attributes_to_try_per_split =
-            (dataset.num_attributes() as f64).sqrt().round() as usize;
+        let target_robustness = config.robustness.target_robustness(dataset.num_records() as usize);
 
-        let target_robustness = ((dataset.num_records() as f64) * epsilon).round() as usize;
+        let mut record_ids = HashMap::with_capacity(samples.len());
+        for sample in samples {
+            let previous = record_ids.insert(sample.record_id(), fingerprint(sample, dataset.num_attributes()));
+            if previous.is_some() {
+                return Err(FitError::DuplicateRecordId(sample.record_id()));
+            }
+        }
 
         // eprintln!(
         //     "Fitting {} trees on {} records with num_attributes_to_try_per_split={}, \
         //      target_robustness={}, max_tries_per_split={}",
-        //     num_trees,
+        //     config.num_trees,
         //     dataset.num_records(),
         //     num_attributes_to_try_per_split,
         //     target_robustness,
-        //     max_tries_per_split
+        //     config.max_tries_per_split
         // );
 
-        let trees: Vec<Tree> = (0..num_trees)
+        // All trees share the columnar samples and only permute their own row indexes, unless
+        // every tree is fitted on its own bootstrap sample
+        let columns = if config.bootstrap {
+            None
+        } else {
+            Some(ColumnarSamples::from_samples(dataset, samples))
+        };
+
+        let trees: Vec<Tree> = (0..config.num_trees)
             .into_par_iter()
-            .map(|tree_index| Tree::fit(
-                dataset,
-                samples.clone().as_mut_slice(),
-                seed,
-                tree_index as u64,
-                min_leaf_size,
-                num_attributes_to_try_per_split,
-                target_robustness,
-                max_tries_per_split
-            ))
+            .map(|tree_index| {
+                let bootstrap_columns;
+                let tree_columns = match &columns {
+                    Some(columns) => columns,
+                    None => {
+                        bootstrap_columns = ColumnarSamples::from_samples(dataset, &resample(config, tree_index as u64, samples));
+                        &bootstrap_columns
+                    }
+                };
+
+                Tree::fit(
+                    dataset,
+                    tree_columns,
+                    tree_columns.all_rows().as_mut_slice(),
+                    tree_index as u64,
+                    config,
+                    num_attributes_to_try_per_split,
+                    target_robustness
+                )
+            })
             .collect();
 
-        ExtremelyRandomizedTrees { trees }
+        let label_stats = LabelStats::of_samples(samples, dataset.task());
+
+        Ok((trees, record_ids, label_stats))
     }
 
-    pub fn predict<S>(
-        &self,
-        sample: &S
-    ) -> bool
-        where S: Sample + Sync
+    fn check_not_forgotten<S: Sample>(&self, samples: &[S]) -> Result<(), FitError> {
+        match samples.iter().find(|sample| self.ledger.contains(sample.record_id())) {
+            Some(sample) => Err(FitError::Forgotten(sample.record_id())),
+            None => Ok(()),
+        }
+    }
+
+    // Refits all trees with the original parameters, the forgotten records stay in the ledger. The
+    // forest is left unchanged on errors.
+    pub fn retrain<D, S>(&mut self, dataset: &D, samples: Vec<S>) -> Result<(), FitError>
+        where D: Dataset + Sync, S: Sample + Sync
     {
-        let num_plus: usize = self.trees
-            .par_iter()
-            .filter(|tree| tree.predict(sample))
-            .count();
+        self.check_not_forgotten(&samples)?;
 
-        num_plus * 2 > self.trees.len()
-    }
+        let (trees, record_ids, label_stats) = ExtremelyRandomizedTrees::fit_trees(dataset, samples, &self.config)?;
 
-    pub fn forget<S>(&mut self, sample: &S) where S: Sample + Sync {
-        self.trees.par_iter_mut().for_each(|tree| { tree.forget(sample); })
-    }
-}
+        self.trees = trees;
+        self.record_ids = record_ids;
+        self.label_stats = label_stats;
 
+        Ok(())
+    }
 
-#[derive(Eq,PartialEq,Debug)]
-enum TreeElement {
-    Node { split: Split },
-    Leaf { num_samples: u32, num_plus: u32 }
-}
+    // Returns whether the forest had to be retrained
+    pub fn retrain_if_needed<D, S>(&mut self, dataset: &D, samples: Vec<S>) -> Result<bool, FitError>
+        where D: Dataset + Sync, S: Sample + Sync
+    {
+        let needs_retraining = self.needs_retraining();
+        if needs_retraining {
+            self.retrain(dataset, samples)?;
+        }
+        Ok(needs_retraining)
+    }
 
-pub struct Tree {
-    index: usize,
-    rng: XorShiftRng,
-    tree_elements: HashMap<u64, TreeElement>,
-    pub alternative_subtrees: HashMap<u64, Vec<AlternativeTree>>,
-    min_leaf_size: usize,
-    num_attributes_to_try_per_split: usize,
-    max_tries_per_split: usize,
-    pub num_robust_nodes: usize,
-    pub num_non_robust_nodes: usize,
-}
-pub struct AlternativeTree {
-    split: Split,
-    split_stats: SplitStats,
-    pub tree: Tree,
-}
+    // Number of further updates which are guaranteed to leave the forest identical to a
+    // retrained one, None if no tree contains a split
+    pub fn remaining_budget(&self) -> Option<u32> {
+        self.trees.iter().filter_map(|tree| tree.remaining_budget()).min()
+    }
 
-fn cmp(stats_a: &SplitStats, stats_b: &SplitStats) -> bool {
-    stats_a.num_minus_left == stats_b.num_minus_left &&
-        stats_a.num_minus_right == stats_b.num_minus_right &&
-        stats_a.num_plus_left == stats_b.num_plus_left &&
-        stats_a.num_plus_right == stats_b.num_plus_right
-}
+    // Some tree has absorbed more updates than its robustness guarantee covers
+    pub fn needs_retraining(&self) -> bool {
+        self.trees.iter().any(|tree| tree.is_budget_exceeded())
+    }
 
-impl Tree {
+    // Rebuilds the subtrees below all nodes with an exhausted budget from the remaining training
+    // samples, returns the number of rebuilt subtrees
+    pub fn retrain_exhausted<D, S>(&mut self, dataset: &D, samples: &[S]) -> Result<usize, FitError>
+        where D: Dataset + Sync, S: Sample + Sync
+    {
+        self.check_not_forgotten(samples)?;
 
-    fn fit<D: Dataset, S: Sample>(
-        dataset: &D,
-        samples: &mut [S],
-        seed: u64,
-        tree_index: u64,
-        min_leaf_size: usize,
-        num_attributes_to_try_per_split: usize,
-        target_robustness: usize,
-        max_tries_per_split: usize
-    ) -> Tree {
+        // Subtrees below an exhausted node are rebuilt together with it
+        let subtree_roots_per_tree: Vec<Vec<NodeId>> = self.trees.iter()
+            .map(|tree| tree.exhausted_nodes())
+            .collect();
 
-        let rng = XorShiftRng::from_seed(as_bytes(seed, tree_index));
+        if subtree_roots_per_tree.iter().all(|subtree_roots| subtree_roots.is_empty()) {
+            return Ok(0);
+        }
 
-        let mut tree = Tree {
-            index: tree_index as usize,
-            rng,
-            tree_elements: HashMap::new(),
-            alternative_subtrees: HashMap::new(),
-            min_leaf_size,
-            num_attributes_to_try_per_split,
-            max_tries_per_split,
-            num_robust_nodes: 0,
-            num_non_robust_nodes: 0
+        let config = self.config;
+        let columns = if config.bootstrap {
+            None
+        } else {
+            Some(ColumnarSamples::from_samples(dataset, samples))
         };
 
-        let gini_initial = gini_impurity(dataset.num_plus(), dataset.num_records());
+        let num_rebuilt = self.trees
+            .par_iter_mut()
+            .zip(subtree_roots_per_tree)
+            .filter(|(_, subtree_roots)| !subtree_roots.is_empty())
+            .map(|(tree, subtree_roots)| {
+                let bootstrap_columns;
+                let tree_columns = match &columns {
+                    Some(columns) => columns,
+                    None => {
+                        bootstrap_columns = ColumnarSamples::from_samples(dataset, &resample(&config, tree.index as u64, samples));
+                        &bootstrap_columns
+                    }
+                };
 
-        let mut constant_attribute_indexes: Cow<[u8]> = Cow::from(Vec::new());
+                let rows_per_subtree = tree.rows_reaching(tree_columns, &subtree_roots);
 
-        tree.determine_split(
-            gini_initial,
-            target_robustness,
-            samples,
-            dataset,
-            1,
-            0,
-            &mut constant_attribute_indexes
-        );
+                for (node_id, mut rows_of_node) in subtree_roots.iter().zip(rows_per_subtree) {
+                    tree.retrain_rows(dataset, *node_id, tree_columns, &mut rows_of_node);
+                }
 
-        return tree;
-    }
+                tree.compact();
 
-    fn leaf(num_samples: u32, num_plus: u32) -> TreeElement {
-        TreeElement::Leaf { num_samples, num_plus }
-    }
+                subtree_roots.len()
+            })
+            .sum();
 
-    fn node(split: Split) -> TreeElement {
-        TreeElement::Node { split }
+        Ok(num_rebuilt)
     }
 
-    fn predict<S: Sample>(&self, sample: &S) -> bool {
-
-        let mut current_tree = self;
-        let mut element_id = 1;
+    pub fn set_class_weights(&mut self, class_weights: ClassWeights) -> Result<(), ClassWeightsError> {
+        if self.task == Task::Regression && class_weights != ClassWeights::Uniform {
+            return Err(ClassWeightsError::Regression);
+        }
 
-        loop {
+        if let ClassWeights::Custom(weights) = &class_weights {
+            if weights.len() != self.num_classes as usize {
+                return Err(ClassWeightsError::WrongNumberOfWeights);
+            }
+            if !weights.iter().all(|weight| weight.is_finite() && *weight > 0.0) {
+                return Err(ClassWeightsError::InvalidWeight);
+            }
+        }
 
-            let element = current_tree.tree_elements.get(&element_id);
+        self.class_weights = class_weights;
+        Ok(())
+    }
 
-            match element {
+    pub fn class_weights(&self) -> &ClassWeights {
+        &self.class_weights
+    }
 
-                Some(TreeElement::Node { split }) => {
-                    if sample.is_left_of(split) {
-                        element_id = element_id * 2;
-                    } else {
-                        element_id = (element_id * 2) + 1;
-                    }
-                }
+    // Thresholds the share of positive tree votes, not the averaged probabilities of predict_proba,
+    // the individual trees keep voting for the label with the larger weighted leaf count
+    pub fn set_decision_threshold(&mut self, decision_threshold: f64) {
+        assert!(decision_threshold > 0.0 && decision_threshold < 1.0, "Decision threshold must be in (0, 1)!");
+        self.decision_threshold = decision_threshold;
+    }
 
-                Some(TreeElement::Leaf { num_samples, num_plus }) => {
-                    return (*num_plus * 2) > *num_samples;
-                }
+    pub fn decision_threshold(&self) -> f64 {
+        self.decision_threshold
+    }
 
-                None => {
-                    let alternative_trees =
-                        current_tree.alternative_subtrees.get(&element_id).unwrap();
-                    // First tree in this list is the current best one by convention
-                    current_tree = &alternative_trees.first().unwrap().tree;
+    // Weight per class which the leaves apply to their counts. Balanced weights follow the class
+    // frequencies after forgets and additions, classes without samples keep a weight of one.
+    // All class and label predictions go through here.
+    fn leaf_weights(&self) -> LeafWeights {
+        assert_eq!(self.task, Task::Classification, "Labels can only be predicted for classification, use predict_value for regression!");
+
+        let mut class_weights = [1.0; MAX_CLASSES];
+
+        match &self.class_weights {
+            ClassWeights::Uniform => {},
+            ClassWeights::Balanced => {
+                let counts = self.label_stats.class_counts();
+                for class in 0..self.num_classes {
+                    if counts.get(class) > 0 {
+                        class_weights[class as usize] =
+                            counts.total() as f64 / (self.num_classes as f64 * counts.get(class) as f64);
+                    }
                 }
-            }
+            },
+            ClassWeights::Custom(weights) => class_weights[..weights.len()].copy_from_slice(weights),
         }
+
+        LeafWeights { class_weights }
     }
 
-    fn forget<S: Sample>(&mut self, sample: &S) {
-        Tree::forget_from(self, sample, 1);
+    // The decision threshold applies to the share of trees which vote for the positive label, each
+    // tree votes positive if its leaf probability exceeds 0.5
+    pub fn predict<S>(
+        &self,
+        sample: &S
+    ) -> bool
+        where S: Sample + Sync
+    {
+        let (num_plus, num_minus) = self.predict_votes(sample);
+        self.exceeds_decision_threshold(num_plus, num_plus + num_minus)
     }
 
-    fn forget_from<S: Sample>(tree: &mut Tree, sample: &S, element_id_to_start: u64) {
+    fn exceeds_decision_threshold(&self, num_plus: usize, num_votes: usize) -> bool {
+        num_plus as f64 > self.decision_threshold * num_votes as f64
+    }
 
-        let mut element_id = element_id_to_start;
+    // Number of trees which vote for the positive and for the negative label, independent of the
+    // decision threshold
+    pub fn predict_votes<S>(&self, sample: &S) -> (usize, usize) where S: Sample + Sync {
+        let leaf_weights = self.leaf_weights();
+        let num_plus: usize = self.trees
+            .par_iter()
+            .filter(|tree| tree.predict(sample, &leaf_weights))
+            .count();
 
-        loop {
+        (num_plus, self.trees.len() - num_plus)
+    }
 
-            let element = tree.tree_elements.get(&element_id);
+    // Probability of the positive label, averaged over the leaves the sample ends up in
+    pub fn predict_proba<S>(&self, sample: &S) -> f64 where S: Sample + Sync {
+        let leaf_weights = self.leaf_weights();
+        let sum_of_probabilities: f64 = self.trees
+            .par_iter()
+            .map(|tree| tree.predict_proba(sample, &leaf_weights))
+            .sum();
 
-            match element {
+        sum_of_probabilities / self.trees.len() as f64
+    }
 
-                Some(TreeElement::Node { split }) => {
+    // Majority vote of the trees, ties are broken in favor of the smaller class index
+    pub fn predict_class<S>(&self, sample: &S) -> u8 where S: Sample + Sync {
+        let leaf_weights = self.leaf_weights();
+        let votes = self.trees
+            .par_iter()
+            .map(|tree| {
+                let mut votes = ClassCounts::new();
+                votes.add(tree.predict_class(sample, &leaf_weights));
+                votes
+            })
+            .reduce(ClassCounts::new, |votes_a, votes_b| votes_a.merged(&votes_b));
+
+        votes.majority_class()
+    }
 
-                    if sample.is_left_of(split) {
-                        element_id = element_id * 2;
-                    } else {
-                        element_id = (element_id * 2) + 1;
-                    }
+    // Class probabilities, averaged over the leaves the sample ends up in
+    pub fn predict_class_proba<S>(&self, sample: &S) -> Vec<f64> where S: Sample + Sync {
+        let leaf_weights = self.leaf_weights();
+        let sum_of_probabilities = self.trees
+            .par_iter()
+            .map(|tree| tree.predict_class_proba(sample, &leaf_weights))
+            .reduce(|| [0.0; MAX_CLASSES], |mut sums, probabilities| {
+                for (sum, probability) in sums.iter_mut().zip(probabilities.iter()) {
+                    *sum += probability;
                 }
+                sums
+            });
 
-                Some(TreeElement::Leaf { num_samples, num_plus }) => {
+        sum_of_probabilities[..self.num_classes as usize].iter()
+            .map(|sum| sum / self.trees.len() as f64)
+            .collect()
+    }
 
-                    assert!(*num_samples != 0);
-                    let new_num_samples = num_samples - 1;
+    pub fn num_classes(&self) -> u8 {
+        self.num_classes
+    }
 
-                    let new_num_plus = if sample.true_label() {
-                        assert!(*num_plus != 0);
-                        *num_plus - 1
-                    } else {
-                        *num_plus
-                    };
+    // Share of the training samples which are classified correctly by the trees whose bootstrap
+    // sample does not contain them, with the same votes as predict and predict_class. None if a
+    // sample is not a remaining training sample, or if every sample is contained in all bootstrap
+    // samples.
+    pub fn oob_accuracy<S>(&self, samples: &[S]) -> Option<f64> where S: Sample + Sync {
+        assert!(self.config.bootstrap, "Out-of-bag estimates require bootstrap samples!");
+        assert_eq!(self.task, Task::Classification, "Out-of-bag accuracy requires classification!");
 
-                    let updated_leaf = Tree::leaf(new_num_samples, new_num_plus);
-                    tree.tree_elements.insert(element_id, updated_leaf);
-                    break;
-                }
+        if samples.iter().any(|sample| !self.record_ids.contains_key(&sample.record_id())) {
+            return None;
+        }
 
-                None => {
+        let leaf_weights = self.leaf_weights();
+
+        let (num_correct, num_evaluated) = samples
+            .par_iter()
+            .filter_map(|sample| {
+                let mut votes = ClassCounts::new();
+
+                for tree in &self.trees {
+                    if Resampled::new(&self.config, tree.index as u64, sample).multiplicity() == 0 {
+                        votes.add(tree.predict_class(sample, &leaf_weights));
+                    }
+                }
+
+                if votes.total() == 0 {
+                    return None;
+                }
+
+                // Binary forests apply the decision threshold to the share of positive votes
+                let is_correct = if self.num_classes == 2 {
+                    let num_plus = votes.get(PLUS) as usize;
+                    self.exceeds_decision_threshold(num_plus, votes.total() as usize) == sample.true_label()
+                } else {
+                    votes.majority_class() == sample.class_label()
+                };
+
+                Some((is_correct as usize, 1))
+            })
+            .reduce(|| (0, 0), |(correct_a, evaluated_a), (correct_b, evaluated_b)| {
+                (correct_a + correct_b, evaluated_a + evaluated_b)
+            });
+
+        if num_evaluated == 0 {
+            None
+        } else {
+            Some(num_correct as f64 / num_evaluated as f64)
+        }
+    }
+
+    // Mean target of the leaves the sample ends up in, averaged over the trees
+    pub fn predict_value<S>(&self, sample: &S) -> f64 where S: Sample + Sync {
+        assert_eq!(self.task, Task::Regression, "Values can only be predicted for regression!");
+
+        let sum_of_values: f64 = self.trees
+            .par_iter()
+            .map(|tree| tree.predict_value(sample))
+            .sum();
+
+        sum_of_values / self.trees.len() as f64
+    }
+
+    pub fn task(&self) -> Task {
+        self.task
+    }
+
+    // Same votes and decision threshold as predict
+    pub fn predict_batch<S>(&self, samples: &[S]) -> Vec<bool> where S: Sample + Sync {
+        let num_trees = self.trees.len();
+        let leaf_weights = self.leaf_weights();
+
+        self.accumulate_batch(samples, |num_plus: &mut usize, tree, sample| {
+            if tree.predict(sample, &leaf_weights) {
+                *num_plus += 1;
+            }
+        })
+        .into_iter()
+        .map(|num_plus| self.exceeds_decision_threshold(num_plus, num_trees))
+        .collect()
+    }
+
+    pub fn predict_proba_batch<S>(&self, samples: &[S]) -> Vec<f64> where S: Sample + Sync {
+        let num_trees = self.trees.len() as f64;
+        let leaf_weights = self.leaf_weights();
+
+        self.accumulate_batch(samples, |sum_of_probabilities: &mut f64, tree, sample| {
+            *sum_of_probabilities += tree.predict_proba(sample, &leaf_weights);
+        })
+        .into_iter()
+        .map(|sum_of_probabilities| sum_of_probabilities / num_trees)
+        .collect()
+    }
+
+    // Blocks of samples are processed in parallel, within a block we pass all samples through
+    // one tree after the other, so that the elements of the tree stay in the cache
+    fn accumulate_batch<S, T, F>(&self, samples: &[S], accumulate: F) -> Vec<T>
+        where S: Sample + Sync, T: Default + Clone + Send, F: Fn(&mut T, &Tree, &S) + Sync
+    {
+        samples
+            .par_chunks(PREDICTION_BLOCK_SIZE)
+            .map(|block| {
+                let mut accumulators = vec![T::default(); block.len()];
+
+                for tree in &self.trees {
+                    for (accumulator, sample) in accumulators.iter_mut().zip(block) {
+                        accumulate(accumulator, tree, sample);
+                    }
+                }
+
+                accumulators
+            })
+            .collect::<Vec<Vec<T>>>()
+            .concat()
+    }
+
+    // The model is only changed if the sample can be forgotten from all trees
+    pub fn forget<S>(&mut self, sample: &S) -> Result<(), ForgetError> where S: Sample + Sync {
+
+        let record_id = sample.record_id();
+
+        if self.ledger.contains(record_id) {
+            return Err(ForgetError::AlreadyForgotten);
+        }
+
+        match self.record_ids.get(&record_id) {
+            None => return Err(ForgetError::NotInTrainingSet),
+            Some(known) if *known != fingerprint(sample, self.num_attributes()) => {
+                return Err(ForgetError::SampleMismatch)
+            },
+            _ => {},
+        }
+
+        // Trees whose bootstrap sample does not contain the record stay unchanged
+        let config = self.config;
+
+        let can_forget = self.trees.par_iter().all(|tree| {
+            let resampled = Resampled::new(&config, tree.index as u64, sample);
+            resampled.multiplicity() == 0 || tree.can_forget(&resampled)
+        });
+
+        if !can_forget {
+            return Err(ForgetError::CounterUnderflow);
+        }
+
+        self.trees.par_iter_mut().for_each(|tree| {
+            let resampled = Resampled::new(&config, tree.index as u64, sample);
+            if resampled.multiplicity() > 0 {
+                tree.forget(&resampled);
+            }
+        });
+
+        self.label_stats.remove(sample);
+        self.record_ids.remove(&record_id);
+        self.ledger.record(record_id);
+
+        Ok(())
+    }
+
+    // Learns a new training sample, the inverse of forget
+    pub fn add<S>(&mut self, sample: &S) -> Result<AddOutcome, AddError> where S: Sample + Sync {
+
+        let record_id = sample.record_id();
+
+        if self.ledger.contains(record_id) {
+            return Err(AddError::Forgotten);
+        }
+
+        if self.record_ids.contains_key(&record_id) {
+            return Err(AddError::AlreadyInTrainingSet);
+        }
+
+        self.validate_sample(sample)?;
+        self.record_ids.insert(record_id, fingerprint(sample, self.num_attributes()));
+
+        let config = self.config;
+
+        self.trees.par_iter_mut().for_each(|tree| {
+            let resampled = Resampled::new(&config, tree.index as u64, sample);
+            if resampled.multiplicity() > 0 {
+                Tree::add_from(tree, &resampled, 0);
+            }
+        });
+        self.label_stats.add(sample);
+
+        if self.needs_retraining() {
+            Ok(AddOutcome::NeedsRebuild)
+        } else {
+            Ok(AddOutcome::Robust)
+        }
+    }
+
+    fn validate_sample<S: Sample>(&self, sample: &S) -> Result<(), AddError> {
+        for (attribute_index, (min_value, max_value)) in self.attribute_ranges.iter().enumerate() {
+            let value = sample.attribute_value(attribute_index as u16);
+            if value != MISSING && (value < *min_value || value > *max_value) {
+                return Err(AddError::InvalidValue(attribute_index as u16));
+            }
+        }
+
+        let is_valid_label = match self.task {
+            Task::Classification => sample.class_label() < self.num_classes,
+            Task::Regression => sample.target().is_finite(),
+        };
+
+        if !is_valid_label {
+            return Err(AddError::InvalidLabel);
+        }
+
+        if sample.weight() == 0 || sample.weight() > MAX_WEIGHT {
+            return Err(AddError::InvalidWeight);
+        }
+
+        Ok(())
+    }
+
+    fn num_attributes(&self) -> u16 {
+        self.attribute_ranges.len() as u16
+    }
+
+    pub fn config(&self) -> &ForestConfig {
+        &self.config
+    }
+
+    pub fn ledger(&self) -> &DeletionLedger {
+        &self.ledger
+    }
+
+    pub fn contains_record(&self, record_id: u64) -> bool {
+        self.record_ids.contains_key(&record_id)
+    }
+
+    pub(crate) fn validate_class_weights(&self) -> Result<(), String> {
+        if !(self.decision_threshold > 0.0 && self.decision_threshold < 1.0) {
+            return Err(format!("Decision threshold {} is not in (0, 1)", self.decision_threshold));
+        }
+
+        match &self.class_weights {
+            ClassWeights::Custom(weights) if weights.len() != self.num_classes as usize =>
+                Err(format!("Expected {} class weights, found {}", self.num_classes, weights.len())),
+            ClassWeights::Custom(weights) if !weights.iter().all(|weight| weight.is_finite() && *weight > 0.0) =>
+                Err("Class weights must be positive".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn validate_records(&self) -> Result<(), String> {
+        self.ledger.validate()?;
+
+        match self.ledger.entries().iter().find(|entry| self.record_ids.contains_key(&entry.record_id)) {
+            Some(entry) => Err(format!("Record {} is both forgotten and part of the model", entry.record_id)),
+            None => Ok(()),
+        }
+    }
+}
+
+
+// Index of an element in the arena of a tree
+pub type NodeId = u32;
+
+pub const ROOT: NodeId = 0;
+
+// All elements carry the budget of updates they can absorb. Leaves need a budget as well, as an
+// added sample can make a retrain split them.
+#[derive(PartialEq,Clone,Debug,Serialize,Deserialize)]
+enum TreeElement {
+    Node { split: Split, left_child: NodeId, right_child: NodeId, budget: Budget },
+    Leaf { label_stats: LeafStats, budget: Budget },
+    // Position of a non-robust split, the subtrees of its alternative splits are stored in the
+    // alternative_subtrees of the tree
+    Alternatives { alternatives: u32, budget: Budget },
+}
+
+// The elements are stored in an arena in depth-first order, with the root at the front. Children
+// and alternatives are referenced by index, so that the depth of a tree is not limited.
+#[derive(Serialize,Deserialize)]
+pub struct Tree {
+    index: usize,
+    rng: XorShiftRng,
+    tree_elements: Vec<TreeElement>,
+    pub alternative_subtrees: Vec<Vec<AlternativeTree>>,
+    min_leaf_size: usize,
+    min_samples_split: usize,
+    max_depth: Option<usize>,
+    min_impurity_decrease: f64,
+    split_criterion: SplitCriterion,
+    num_attributes_to_try_per_split: usize,
+    max_tries_per_split: usize,
+    max_alternatives: Option<usize>,
+    pub num_robust_nodes: usize,
+    pub num_non_robust_nodes: usize,
+    // Detected once, deserialized trees detect it again
+    #[serde(skip, default = "ScanKernel::detect")]
+    scan_kernel: ScanKernel,
+}
+
+// Class weights of the forest, resolved once per prediction
+struct LeafWeights {
+    class_weights: [f64; MAX_CLASSES],
+}
+
+impl LeafWeights {
+
+    fn weighted_counts(&self, class_counts: &ClassCounts) -> [f64; MAX_CLASSES] {
+        let mut weighted_counts = [0.0; MAX_CLASSES];
+        for (class, weighted_count) in weighted_counts.iter_mut().enumerate() {
+            *weighted_count = class_counts.get(class as u8) as f64 * self.class_weights[class];
+        }
+        weighted_counts
+    }
+}
+
+#[derive(Serialize,Deserialize)]
+pub struct AlternativeTree {
+    split: Split,
+    split_stats: SplitStats,
+    pub tree: Tree,
+}
+
+fn cmp(stats_a: &SplitStats, stats_b: &SplitStats) -> bool {
+    stats_a.left == stats_b.left && stats_a.right == stats_b.right
+}
+
+impl Tree {
+
+    fn fit<D: Dataset>(
+        dataset: &D,
+        columns: &ColumnarSamples,
+        rows: &mut [u32],
+        tree_index: u64,
+        config: &ForestConfig,
+        num_attributes_to_try_per_split: usize,
+        target_robustness: usize
+    ) -> Tree {
+
+        let rng = XorShiftRng::from_seed(as_bytes(config.seed, tree_index));
+
+        let mut tree = Tree {
+            index: tree_index as usize,
+            rng,
+            tree_elements: Vec::new(),
+            alternative_subtrees: Vec::new(),
+            min_leaf_size: config.min_leaf_size,
+            min_samples_split: config.min_samples_split,
+            max_depth: config.max_depth,
+            min_impurity_decrease: config.min_impurity_decrease,
+            split_criterion: config.split_criterion,
+            num_attributes_to_try_per_split,
+            max_tries_per_split: config.max_tries_per_split,
+            max_alternatives: config.max_alternatives,
+            num_robust_nodes: 0,
+            num_non_robust_nodes: 0,
+            scan_kernel: ScanKernel::detect()
+        };
+
+        let label_stats = columns.label_stats(rows);
+
+        if tree.is_leaf(rows.len(), &label_stats, 0) {
+            tree.push(Tree::leaf(label_stats, Budget::new(target_robustness)));
+            return tree;
+        }
+
+        let impurity_initial = tree.split_criterion.impurity(&label_stats);
+
+        let mut constant_attribute_indexes: Cow<[u16]> = Cow::from(Vec::new());
+        let mut histograms = Histograms::new(dataset.num_attributes());
+
+        tree.determine_split(
+            impurity_initial,
+            target_robustness,
+            columns,
+            rows,
+            dataset,
+            0,
+            0,
+            &mut constant_attribute_indexes,
+            &mut histograms
+        );
+
+        tree
+    }
+
+    // Whether a node at the given depth with these rows is not split any further
+    fn is_leaf(&self, num_rows: usize, label_stats: &LabelStats, depth: usize) -> bool {
+        num_rows <= self.min_leaf_size ||
+            num_rows < self.min_samples_split ||
+            matches!(self.max_depth, Some(max_depth) if depth >= max_depth) ||
+            label_stats.is_pure()
+    }
+
+    // Whether the split decreases the impurity enough to be made
+    fn is_admissible(&self, stats: &SplitStats) -> bool {
+        stats.has_positive_score() && stats.score.unwrap() >= to_score(self.min_impurity_decrease)
+    }
+
+    // Updates which a node absorbs before retraining it could stop at one of the stopping rules.
+    // Nodes need more rows than the minimum leaf size and at least the minimum number of samples
+    // to split, so a node absorbs as many removals as it has rows beyond the larger of both.
+    fn budget_of_split(
+        &self,
+        target_robustness: usize,
+        num_rows: usize,
+        split_stats: &SplitStats,
+        max_weight: u32
+    ) -> usize {
+        let min_rows = self.min_samples_split.max(self.min_leaf_size + 1);
+        let mut budget = target_robustness.min(num_rows.saturating_sub(min_rows));
+
+        if self.min_impurity_decrease > 0.0 {
+            let min_score = to_score(self.min_impurity_decrease);
+            budget = removals_until_below(split_stats, min_score, budget, max_weight, self.split_criterion);
+        }
+
+        budget
+    }
+
+    // Updates which a leaf absorbs before retraining it could find a split with the minimum score
+    // among the candidates which stopped it
+    fn budget_of_leaf(&self, target_robustness: usize, split_stats: &[SplitStats], max_weight: u32) -> usize {
+        if self.min_impurity_decrease <= 0.0 {
+            return target_robustness;
+        }
+
+        let min_score = to_score(self.min_impurity_decrease);
+
+        split_stats.iter()
+            .filter(|stats| stats.has_positive_score())
+            .map(|stats| removals_until_admissible(stats, min_score, target_robustness, max_weight, self.split_criterion))
+            .min()
+            .unwrap_or(target_robustness)
+    }
+
+    fn leaf(label_stats: LabelStats, budget: Budget) -> TreeElement {
+        TreeElement::Leaf { label_stats: LeafStats::new(&label_stats), budget }
+    }
+
+    // The children are set once they have been built
+    fn node(split: Split, budget: Budget) -> TreeElement {
+        TreeElement::Node { split, left_child: ROOT, right_child: ROOT, budget }
+    }
+
+    fn push(&mut self, element: TreeElement) -> NodeId {
+        assert!(self.tree_elements.len() < NodeId::MAX as usize, "Too many elements in tree {}!", self.index);
+        self.tree_elements.push(element);
+        (self.tree_elements.len() - 1) as NodeId
+    }
+
+    fn set_children(&mut self, node_id: NodeId, left: NodeId, right: NodeId) {
+        match &mut self.tree_elements[node_id as usize] {
+            TreeElement::Node { split: _, left_child, right_child, budget: _ } => {
+                *left_child = left;
+                *right_child = right;
+            }
+            _ => panic!("Element {} of tree {} is not a node!", node_id, self.index),
+        }
+    }
+
+    // Leaves vote for the label with the larger weighted count, the forest's decision threshold
+    // only applies to the share of these votes
+    fn predict<S: Sample>(&self, sample: &S, leaf_weights: &LeafWeights) -> bool {
+        self.predict_proba(sample, leaf_weights) > 0.5
+    }
+
+    // Leaves from which all samples have been forgotten predict the negative label
+    fn predict_proba<S: Sample>(&self, sample: &S, leaf_weights: &LeafWeights) -> f64 {
+        self.predict_class_proba(sample, leaf_weights)[PLUS as usize]
+    }
+
+    // Ties are broken in favor of the smaller class index
+    fn predict_class<S: Sample>(&self, sample: &S, leaf_weights: &LeafWeights) -> u8 {
+        let weighted_counts = leaf_weights.weighted_counts(self.leaf_stats(sample).class_counts());
+
+        let mut predicted_class = 0;
+        for class in 1..MAX_CLASSES {
+            if weighted_counts[class] > weighted_counts[predicted_class] {
+                predicted_class = class;
+            }
+        }
+        predicted_class as u8
+    }
+
+    fn predict_class_proba<S: Sample>(&self, sample: &S, leaf_weights: &LeafWeights) -> [f64; MAX_CLASSES] {
+        let mut probabilities = leaf_weights.weighted_counts(self.leaf_stats(sample).class_counts());
+        let total: f64 = probabilities.iter().sum();
+
+        if total > 0.0 {
+            for probability in probabilities.iter_mut() {
+                *probability /= total;
+            }
+        }
+
+        probabilities
+    }
+
+    // Leaves from which all samples have been forgotten predict zero
+    fn predict_value<S: Sample>(&self, sample: &S) -> f64 {
+        self.leaf_stats(sample).target_stats().mean()
+    }
+
+    fn leaf_stats<S: Sample>(&self, sample: &S) -> LabelStats {
+
+        let mut current_tree = self;
+        let mut node_id = ROOT;
+
+        loop {
+
+            match &current_tree.tree_elements[node_id as usize] {
+
+                TreeElement::Node { split, left_child, right_child, budget: _ } => {
+                    node_id = if sample.is_left_of(split) { *left_child } else { *right_child };
+                }
+
+                TreeElement::Leaf { label_stats, .. } => {
+                    return label_stats.label_stats();
+                }
+
+                TreeElement::Alternatives { alternatives, budget: _ } => {
+                    let alternative_trees = &current_tree.alternative_subtrees[*alternatives as usize];
+                    // First tree in this list is the current best one by convention
+                    current_tree = &alternative_trees.first().unwrap().tree;
+                    node_id = ROOT;
+                }
+            }
+        }
+    }
+
+    fn forget<S: Sample>(&mut self, sample: &S) {
+        Tree::forget_from(self, sample);
+    }
+
+    pub fn budget(&self, node_id: NodeId) -> Option<&Budget> {
+        match self.tree_elements.get(node_id as usize) {
+            Some(TreeElement::Node { budget, .. }) |
+            Some(TreeElement::Leaf { budget, .. }) |
+            Some(TreeElement::Alternatives { budget, .. }) => Some(budget),
+            None => None,
+        }
+    }
+
+    fn budgets(&self) -> impl Iterator<Item=&Budget> {
+        self.tree_elements.iter().map(|element| match element {
+            TreeElement::Node { budget, .. } |
+            TreeElement::Leaf { budget, .. } |
+            TreeElement::Alternatives { budget, .. } => budget,
+        })
+    }
+
+    // Alternative subtrees are fitted with a lower target robustness, as they only become the
+    // current best one after some removals. We therefore only account for the budgets of the
+    // current best subtrees, which determine the predictions.
+    fn current_subtrees(&self) -> impl Iterator<Item=&Tree> {
+        self.alternative_subtrees.iter()
+            .map(|alternative_trees| &alternative_trees.first().unwrap().tree)
+    }
+
+    // Smallest remaining budget of all nodes which determine the predictions
+    pub fn remaining_budget(&self) -> Option<u32> {
+        let alternative_budgets = self.current_subtrees()
+            .filter_map(|tree| tree.remaining_budget());
+
+        self.budgets()
+            .map(|budget| budget.remaining())
+            .chain(alternative_budgets)
+            .min()
+    }
+
+    // Topmost elements below which the tree must be rebuilt to restore the robustness guarantee.
+    // An exhausted node in the current subtree is reported by the position of its alternatives.
+    pub fn exhausted_nodes(&self) -> Vec<NodeId> {
+        let mut exhausted_node_ids = Vec::new();
+        let mut node_ids = vec![ROOT];
+
+        while let Some(node_id) = node_ids.pop() {
+            match &self.tree_elements[node_id as usize] {
+                TreeElement::Node { split: _, left_child, right_child, budget } => {
+                    if budget.is_exhausted() {
+                        exhausted_node_ids.push(node_id);
+                    } else {
+                        node_ids.push(*right_child);
+                        node_ids.push(*left_child);
+                    }
+                }
+                TreeElement::Alternatives { alternatives, budget } => {
+                    let current_tree = &self.alternative_subtrees[*alternatives as usize].first().unwrap().tree;
+                    if budget.is_exhausted() || !current_tree.exhausted_nodes().is_empty() {
+                        exhausted_node_ids.push(node_id);
+                    }
+                }
+                TreeElement::Leaf { label_stats: _, budget } => {
+                    if budget.is_exhausted() {
+                        exhausted_node_ids.push(node_id);
+                    }
+                }
+            }
+        }
+
+        exhausted_node_ids
+    }
+
+    // Whether the sample is routed to the element, only follows the nodes of this tree
+    pub fn reaches<S: Sample>(&self, sample: &S, node_id: NodeId) -> bool {
+        let mut current_id = ROOT;
+
+        while current_id != node_id {
+            match &self.tree_elements[current_id as usize] {
+                TreeElement::Node { split, left_child, right_child, budget: _ } => {
+                    current_id = if sample.is_left_of(split) { *left_child } else { *right_child };
+                }
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    // Rows which are routed to each of the elements, none of which may lie below another one.
+    // Every row only passes the nodes of this tree once.
+    fn rows_reaching(&self, columns: &ColumnarSamples, node_ids: &[NodeId]) -> Vec<Vec<u32>> {
+        let mut position_of_element = vec![None; self.tree_elements.len()];
+        for (position, node_id) in node_ids.iter().enumerate() {
+            position_of_element[*node_id as usize] = Some(position);
+        }
+
+        let mut rows_per_element = vec![Vec::new(); node_ids.len()];
+
+        for row in 0..columns.len() as u32 {
+            let sample = columns.row(row);
+            let mut current_id = ROOT;
+
+            loop {
+                if let Some(position) = position_of_element[current_id as usize] {
+                    rows_per_element[position].push(row);
+                    break;
+                }
+
+                match &self.tree_elements[current_id as usize] {
+                    TreeElement::Node { split, left_child, right_child, budget: _ } => {
+                        current_id = if sample.is_left_of(split) { *left_child } else { *right_child };
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        rows_per_element
+    }
+
+    // Replaces the subtree below the element with a subtree fitted on the given samples, which
+    // must be the remaining training samples that reach the element. The new nodes get the
+    // target robustness which the element was originally fitted with. The ids of the elements
+    // change, as the arena is compacted afterwards.
+    pub fn retrain_subtree<D: Dataset, S: Sample>(&mut self, dataset: &D, node_id: NodeId, samples: &[S]) {
+        let columns = ColumnarSamples::from_samples(dataset, samples);
+        self.retrain_rows(dataset, node_id, &columns, &mut columns.all_rows());
+        self.compact();
+    }
+
+    // The new subtree is appended to the arena and its root is moved to the position of the
+    // element, so that the ids of all other reachable elements stay valid until compaction
+    fn retrain_rows<D: Dataset>(&mut self, dataset: &D, node_id: NodeId, columns: &ColumnarSamples, rows: &mut [u32]) {
+
+        let target_robustness = self.budget(node_id)
+            .unwrap_or_else(|| panic!("Element {} is neither a node nor has alternatives!", node_id))
+            .target_robustness as usize;
+
+        let label_stats = columns.label_stats(rows);
+        let depth = self.depth_of(node_id);
+
+        let new_node_id = if self.is_leaf(rows.len(), &label_stats, depth) {
+            self.push(Tree::leaf(label_stats, Budget::new(target_robustness)))
+        } else {
+            let impurity_before = self.split_criterion.impurity(&label_stats);
+
+            self.determine_split(
+                impurity_before,
+                target_robustness,
+                columns,
+                rows,
+                dataset,
+                depth,
+                0,
+                &mut Cow::from(Vec::new()),
+                &mut Histograms::new(dataset.num_attributes())
+            )
+        };
+
+        self.tree_elements.swap(node_id as usize, new_node_id as usize);
+
+        // The counts are taken from the arena, as the roots of alternative subtrees are not counted
+        // when they are fitted
+        let (num_nodes, num_alternatives) = self.count_below(ROOT);
+        self.num_robust_nodes = num_nodes;
+        self.num_non_robust_nodes = num_alternatives;
+    }
+
+    fn depth_of(&self, node_id: NodeId) -> usize {
+        let mut node_ids = vec![(ROOT, 0)];
+
+        while let Some((current_id, depth)) = node_ids.pop() {
+            if current_id == node_id {
+                return depth;
+            }
+
+            if let TreeElement::Node { split: _, left_child, right_child, budget: _ } = &self.tree_elements[current_id as usize] {
+                node_ids.push((*left_child, depth + 1));
+                node_ids.push((*right_child, depth + 1));
+            }
+        }
+
+        panic!("Element {} is not part of tree {}!", node_id, self.index)
+    }
+
+    // Number of nodes and of positions with alternatives in the subtree below the element
+    fn count_below(&self, node_id: NodeId) -> (usize, usize) {
+        let mut num_nodes = 0;
+        let mut num_alternatives = 0;
+        let mut node_ids = vec![node_id];
+
+        while let Some(node_id) = node_ids.pop() {
+            match &self.tree_elements[node_id as usize] {
+                TreeElement::Node { split: _, left_child, right_child, budget: _ } => {
+                    num_nodes += 1;
+                    node_ids.push(*left_child);
+                    node_ids.push(*right_child);
+                }
+                TreeElement::Alternatives { .. } => num_alternatives += 1,
+                TreeElement::Leaf { .. } => {}
+            }
+        }
+
+        (num_nodes, num_alternatives)
+    }
+
+    // Drops the elements and alternatives which are no longer reachable after retraining, and
+    // restores the depth-first order of the arena
+    fn compact(&mut self) {
+        let elements = std::mem::take(&mut self.tree_elements);
+        let mut alternative_subtrees: Vec<Option<Vec<AlternativeTree>>> =
+            std::mem::take(&mut self.alternative_subtrees).into_iter().map(Some).collect();
+
+        self.copy_from(&elements, &mut alternative_subtrees, ROOT);
+    }
+
+    fn copy_from(
+        &mut self,
+        elements: &[TreeElement],
+        alternative_subtrees: &mut [Option<Vec<AlternativeTree>>],
+        node_id: NodeId
+    ) -> NodeId {
+
+        match &elements[node_id as usize] {
+            TreeElement::Node { split, left_child, right_child, budget } => {
+                let copied_node_id = self.push(TreeElement::Node {
+                    split: split.clone(),
+                    left_child: ROOT,
+                    right_child: ROOT,
+                    budget: *budget
+                });
+
+                let copied_left_child = self.copy_from(elements, alternative_subtrees, *left_child);
+                let copied_right_child = self.copy_from(elements, alternative_subtrees, *right_child);
+                self.set_children(copied_node_id, copied_left_child, copied_right_child);
+
+                copied_node_id
+            }
+            TreeElement::Leaf { label_stats, budget } => {
+                self.push(TreeElement::Leaf { label_stats: label_stats.clone(), budget: *budget })
+            }
+            TreeElement::Alternatives { alternatives, budget } => {
+                let alternative_trees = alternative_subtrees[*alternatives as usize].take().unwrap();
+                self.alternative_subtrees.push(alternative_trees);
+
+                self.push(TreeElement::Alternatives {
+                    alternatives: (self.alternative_subtrees.len() - 1) as u32,
+                    budget: *budget
+                })
+            }
+        }
+    }
+
+    fn is_budget_exceeded(&self) -> bool {
+        self.budgets().any(|budget| budget.is_exceeded()) ||
+            self.current_subtrees().any(|tree| tree.is_budget_exceeded())
+    }
+
+    // Dry run of forget_from, which checks that no counter would drop below zero
+    fn can_forget<S: Sample>(&self, sample: &S) -> bool {
+
+        let mut node_id = ROOT;
+
+        loop {
+
+            match &self.tree_elements[node_id as usize] {
+
+                TreeElement::Node { split, left_child, right_child, budget: _ } => {
+                    node_id = if sample.is_left_of(split) { *left_child } else { *right_child };
+                }
+
+                TreeElement::Leaf { label_stats, .. } => {
+                    return label_stats.can_remove(sample);
+                }
+
+                TreeElement::Alternatives { alternatives, budget: _ } => {
+                    let alternative_trees = &self.alternative_subtrees[*alternatives as usize];
+
+                    for alternative_tree in alternative_trees {
+                        let can_remove = alternative_tree.split_stats
+                            .side(sample.is_left_of(&alternative_tree.split))
+                            .can_remove(sample);
+
+                        if !can_remove || !alternative_tree.tree.can_forget(sample) {
+                            return false;
+                        }
+                    }
+
+                    return true;
+                }
+            }
+        }
+    }
+
+    fn forget_from<S: Sample>(tree: &mut Tree, sample: &S) {
+
+        let split_criterion = tree.split_criterion;
+        let mut node_id = ROOT;
+
+        loop {
+
+            match &mut tree.tree_elements[node_id as usize] {
+
+                TreeElement::Node { split, left_child, right_child, budget } => {
+
+                    budget.num_updates += 1;
+
+                    node_id = if sample.is_left_of(split) { *left_child } else { *right_child };
+                }
+
+                TreeElement::Leaf { label_stats, budget } => {
+                    budget.num_updates += 1;
+                    label_stats.remove(sample);
+                    break;
+                }
+
+                TreeElement::Alternatives { alternatives, budget } => {
+
+                    budget.num_updates += 1;
 
                     // First we have to update the split stats
-                    let alternative_trees =
-                        &mut *tree.alternative_subtrees.get_mut(&element_id).unwrap();
+                    let alternative_trees = &mut tree.alternative_subtrees[*alternatives as usize];
+
+                    alternative_trees.iter_mut().for_each(|alternative_tree| {
+                        let stats = &mut alternative_tree.split_stats;
+
+                        stats.side_mut(sample.is_left_of(&alternative_tree.split))
+                            .remove(sample);
+
+                        stats.update_score_and_impurity_before(split_criterion);
+                    });
+
+                    // TODO alternative_trees could be a heap, but it probably does not matter
+                    // Make sure the split with the highest score is in the first position
+                    //let current_best = alternative_trees.first().unwrap().split_stats.clone();
+                    alternative_trees.sort_by(|tree_a, tree_b| {
+                        tree_b.split_stats.score.cmp(&tree_a.split_stats.score)
+                    });
+                    //let other_best = &alternative_trees.first().unwrap().split_stats;
+
+                    //if !cmp(&other_best, &current_best) {
+                    //    println!("Subtree order changed")
+                    //}
+
+                    // Afterwards, we invoke the forgetting procedure on the alternative trees
+                    alternative_trees.iter_mut().for_each(|alternative_tree| {
+                        Tree::forget_from(&mut alternative_tree.tree, sample);
+                    });
+
+                    break;
+                }
+            }
+        }
+    }
+
+    // The depth is the one of the root of the tree, which is not zero for alternative subtrees
+    fn add_from<S: Sample>(tree: &mut Tree, sample: &S, depth: usize) {
+
+        let split_criterion = tree.split_criterion;
+        let mut node_id = ROOT;
+        let mut depth = depth;
+
+        loop {
+
+            match &mut tree.tree_elements[node_id as usize] {
+
+                TreeElement::Node { split, left_child, right_child, budget } => {
+
+                    budget.num_updates += 1;
+
+                    node_id = if sample.is_left_of(split) { *left_child } else { *right_child };
+                    depth += 1;
+                }
+
+                TreeElement::Leaf { label_stats, budget } => {
+                    budget.num_updates += 1;
+                    label_stats.add(sample);
+
+                    // A retrain would split the leaf if the stopping rules no longer hold. The
+                    // weighted total bounds the number of rows from above.
+                    let label_stats = label_stats.label_stats();
+                    if !tree.is_leaf(label_stats.total() as usize, &label_stats, depth) {
+                        if let TreeElement::Leaf { budget, .. } = &mut tree.tree_elements[node_id as usize] {
+                            budget.invalidate();
+                        }
+                    }
+                    break;
+                }
+
+                TreeElement::Alternatives { alternatives, budget } => {
+
+                    budget.num_updates += 1;
+
+                    let alternative_trees = &mut tree.alternative_subtrees[*alternatives as usize];
+
+                    alternative_trees.iter_mut().for_each(|alternative_tree| {
+                        let stats = &mut alternative_tree.split_stats;
+
+                        stats.side_mut(sample.is_left_of(&alternative_tree.split))
+                            .add(sample);
+
+                        stats.update_score_and_impurity_before(split_criterion);
+                    });
+
+                    // Make sure the split with the highest score is in the first position
+                    alternative_trees.sort_by(|tree_a, tree_b| {
+                        tree_b.split_stats.score.cmp(&tree_a.split_stats.score)
+                    });
+
+                    alternative_trees.iter_mut().for_each(|alternative_tree| {
+                        Tree::add_from(&mut alternative_tree.tree, sample, depth);
+                    });
+
+                    break;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn forget_from2<S: Sample>(tree: &mut Tree, sample: &S) -> (usize, usize) {
+
+        let split_criterion = tree.split_criterion;
+        let mut num_variants_hit = 0;
+        let mut num_variants_changed = 0;
+
+        let mut node_id = ROOT;
+
+        loop {
+
+            match &mut tree.tree_elements[node_id as usize] {
+
+                TreeElement::Node { split, left_child, right_child, budget: _ } => {
+                    node_id = if sample.is_left_of(split) { *left_child } else { *right_child };
+                }
+
+                TreeElement::Leaf { label_stats, .. } => {
+                    label_stats.remove(sample);
+                    break;
+                }
+
+                TreeElement::Alternatives { alternatives, budget: _ } => {
+                    // We hit a non-robust node
+                    //eprintln!("Hit a non-robust node!");
+                    num_variants_hit += 1;
+
+                    // First we have to update the split stats
+                    let alternative_trees = &mut tree.alternative_subtrees[*alternatives as usize];
+
+                    alternative_trees.iter_mut().for_each(|alternative_tree| {
+                        let stats = &mut alternative_tree.split_stats;
+
+                        stats.side_mut(sample.is_left_of(&alternative_tree.split))
+                            .remove(sample);
+
+                        stats.update_score_and_impurity_before(split_criterion);
+                    });
+
+                    // TODO alternative_trees could be a heap, but it probably does not matter
+                    // Make sure the split with the highest score is in the first position
+                    let current_best = alternative_trees.first().unwrap().split_stats;
+                    alternative_trees.sort_by(|tree_a, tree_b| {
+                        tree_b.split_stats.score.cmp(&tree_a.split_stats.score)
+                    });
+                    let other_best = &alternative_trees.first().unwrap().split_stats;
+
+                    if !cmp(other_best, &current_best) {
+                        //println!("Subtree order changed")
+                        num_variants_changed += 1;
+                    }
+
+                    // Afterwards, we invoke the forgetting procedure on the alternative trees
+                    alternative_trees.iter_mut().for_each(|alternative_tree| {
+                        let (alt_hit, alt_changed) = Tree::forget_from2(&mut alternative_tree.tree, sample);
+                        num_variants_hit += alt_hit;
+                        num_variants_changed += alt_changed;
+                    });
+
+                    break;
+                }
+            }
+        }
+
+        (num_variants_hit, num_variants_changed)
+    }
+
+    // Checks the structural invariants of a deserialized tree: every element and every list of
+    // alternatives is reachable from the root exactly once
+    pub(crate) fn validate(&self) -> Result<(), String> {
+
+        let mut is_reached = vec![false; self.tree_elements.len()];
+        let mut is_alternative_reached = vec![false; self.alternative_subtrees.len()];
+        let mut node_ids = vec![ROOT];
+
+        while let Some(node_id) = node_ids.pop() {
+
+            match is_reached.get_mut(node_id as usize) {
+                Some(false) => is_reached[node_id as usize] = true,
+                Some(true) => return Err(format!("Tree {} reaches element {} twice", self.index, node_id)),
+                None => return Err(format!("Tree {} has no element {}", self.index, node_id)),
+            }
+
+            match &self.tree_elements[node_id as usize] {
+
+                TreeElement::Node { split: _, left_child, right_child, budget: _ } => {
+                    node_ids.push(*left_child);
+                    node_ids.push(*right_child);
+                }
+
+                TreeElement::Leaf { .. } => {}
+
+                TreeElement::Alternatives { alternatives, budget: _ } => {
+                    let alternative_trees = self.alternative_subtrees.get(*alternatives as usize)
+                        .filter(|alternative_trees| !alternative_trees.is_empty())
+                        .ok_or_else(|| format!("Tree {} has no alternatives {}", self.index, alternatives))?;
+
+                    if is_alternative_reached[*alternatives as usize] {
+                        return Err(format!("Tree {} reaches alternatives {} twice", self.index, alternatives));
+                    }
+                    is_alternative_reached[*alternatives as usize] = true;
+
+                    for alternative_tree in alternative_trees {
+                        match alternative_tree.tree.tree_elements.first() {
+                            Some(TreeElement::Node { split, .. }) if *split == alternative_tree.split => {
+                                alternative_tree.tree.validate()?;
+                            }
+                            _ => return Err(format!(
+                                "Alternative subtree in tree {} does not start with its split at element {}",
+                                self.index,
+                                node_id
+                            )),
+                        }
+                    }
+                }
+            }
+        }
+
+        if is_reached.contains(&false) || is_alternative_reached.contains(&false) {
+            return Err(format!("Tree {} has unreachable elements", self.index));
+        }
+
+        Ok(())
+    }
+
+    fn generate_candidate_splits<D: Dataset>(
+        &mut self,
+        dataset: &D,
+        constant_attribute_indexes: &[u16]
+    ) -> Vec<Split> {
+
+        let mut attribute_indexes: Vec<u16> = (0..dataset.num_attributes())
+            // TODO This searches linearly, but does it matter here?
+            .filter(|attribute_index| !constant_attribute_indexes.contains(attribute_index))
+            .collect();
+
+        attribute_indexes.shuffle(&mut self.rng);
+
+
+        // TODO can we allocate once and reuse the vec somehow?
+        let split_candidates: Vec<Split> = attribute_indexes.iter()
+            .take(self.num_attributes_to_try_per_split)
+            .map(|attribute_index| generate_random_split(&mut self.rng, dataset, *attribute_index))
+            .collect();
+
+        split_candidates
+    }
+
+
+    #[allow(clippy::too_many_arguments)]
+    fn determine_split<D: Dataset>(
+        &mut self,
+        impurity_before: f64,
+        target_robustness: usize,
+        columns: &ColumnarSamples,
+        rows: &mut [u32],
+        dataset: &D,
+        depth: usize,
+        num_tries: usize,
+        constant_attribute_indexes: &mut Cow<[u16]>,
+        histograms: &mut Histograms
+    ) -> NodeId {
+        assert!(rows.len() > self.min_leaf_size);
+
+        // All attributes are constant, we create a leaf now
+        if constant_attribute_indexes.len() == dataset.num_attributes() as usize {
+
+            let leaf = Tree::leaf(columns.label_stats(rows), Budget::new(target_robustness));
+
+            return self.push(leaf);
+        }
+
+        let mut candidate_splits = self.generate_candidate_splits(dataset, constant_attribute_indexes);
+
+        let split_stats = compute_split_stats(
+            impurity_before,
+            columns,
+            rows,
+            dataset,
+            &mut candidate_splits,
+            histograms,
+            self.split_criterion,
+            self.scan_kernel
+        );
+
+        let maybe_best_split_stats = split_stats.iter().enumerate()
+            .filter(|(_, stats)| self.is_admissible(stats))
+            .max_by(|(_, stats1), (_, stats2)| stats1.score.cmp(&stats2.score));
+
+        if maybe_best_split_stats.is_none() {
+            if num_tries < self.max_tries_per_split {
+                return self.determine_split(
+                    impurity_before,
+                    target_robustness,
+                    columns,
+                    rows,
+                    dataset,
+                    depth,
+                    num_tries + 1,
+                    constant_attribute_indexes,
+                    histograms
+                );
+            } else {
+
+                // We only need stats that are indepent of the split
+                let some_stats = split_stats.first().unwrap();
+                let budget = self.budget_of_leaf(target_robustness, &split_stats, columns.max_weight());
+
+                let leaf = Tree::leaf(
+                    some_stats.left.merged(&some_stats.right),
+                    Budget::limited(target_robustness, budget)
+                );
+
+                return self.push(leaf);
+            }
+        }
+
+        let (index_of_best_stats, best_split_stats) = maybe_best_split_stats.unwrap();
+
+        assert!(self.is_admissible(best_split_stats));
+
+        let best_split_candidate = candidate_splits.get(index_of_best_stats).unwrap();
+
+        let mut at_least_one_non_robust = false;
+        let mut _num_removals_required = 0;
+        let max_weight = columns.max_weight();
+
+        for (index, stats) in split_stats.iter().enumerate() {
+            // We only check splits that make sense! Splits without the minimum score could still
+            // overtake the best split after some removals, so we check them as well.
+            if index != index_of_best_stats && stats.has_positive_score() {
+
+                let (is_robust_split, num_removals_evaluated) =
+                    is_robust_with_weights(best_split_stats, stats, target_robustness, max_weight, self.split_criterion);
+
+                if !is_robust_split {
+                    at_least_one_non_robust = true;
+                    _num_removals_required = num_removals_evaluated;
+                    break;
+                }
+            }
+        }
+
+        if at_least_one_non_robust && num_tries < self.max_tries_per_split {
+            //println!("Non-robust split found, retrying...");
+            self.determine_split(
+                impurity_before,
+                target_robustness,
+                columns,
+                rows,
+                dataset,
+                depth,
+                num_tries + 1,
+                constant_attribute_indexes,
+                histograms
+            )
+        } else {
+
+            if at_least_one_non_robust {
+
+                let non_robust_splits: Vec<(usize, usize)> = split_stats.iter()
+                    .enumerate()
+                    .filter(|(index, stats)| *index != index_of_best_stats && stats.has_positive_score())
+                    .filter_map(|(index, stats)| {
+                        let (is_robust_split, num_removals_required_to_break_split) =
+                            is_robust_with_weights(best_split_stats, stats, target_robustness, max_weight, self.split_criterion);
+
+                        if is_robust_split {
+                            None
+                        } else {
+                            Some((index, num_removals_required_to_break_split))
+                        }
+                    })
+                    .collect();
+
+                self.num_non_robust_nodes += 1;
+                // TODO REVISION log this out
+                //println!("\tVariant,{},{},{},{}", target_robustness, samples.len(), current_id, self.index);
+                // eprintln!(
+                //     "Non-robust split ({}) on {} records with {} alternatives \
+                //     for element_id {} in tree {}.",
+                //     _num_removals_required,
+                //     samples.len(),
+                //     alternative_splits.len(),
+                //     current_id,
+                //     self.index
+                // );
+
+                // Splits without the minimum score cannot be alternatives, the position absorbs
+                // fewer removals than the first of them needs to overtake the best split
+                let mut budget_of_position = target_robustness;
+                let mut alternative_splits = Vec::with_capacity(non_robust_splits.len());
+
+                for (index, num_removals_required) in non_robust_splits {
+                    if self.is_admissible(&split_stats[index]) {
+                        alternative_splits.push((index, num_removals_required));
+                    } else {
+                        budget_of_position = budget_of_position.min(num_removals_required.saturating_sub(1));
+                    }
+                }
+
+                // Only the alternatives which take over after the fewest removals are kept, the
+                // position then absorbs fewer removals than the first dropped one needs
+
+                if let Some(max_alternatives) = self.max_alternatives {
+                    let num_kept = max_alternatives - 1;
+
+                    if alternative_splits.len() > num_kept {
+                        alternative_splits.sort_by_key(|(_, num_removals_required)| *num_removals_required);
+
+                        let (_, num_removals_of_first_dropped) = alternative_splits[num_kept];
+                        budget_of_position = budget_of_position.min(num_removals_of_first_dropped.saturating_sub(1));
+
+                        alternative_splits.truncate(num_kept);
+                    }
+                }
+
+                alternative_splits.push((index_of_best_stats, 0));
+
+                let mut alternative_trees: Vec<AlternativeTree> =
+                    Vec::with_capacity(alternative_splits.len());
+
+                for (index, num_removals_required_to_break_split) in alternative_splits {
+
+                    let alternative_target_robustness =
+                        target_robustness - num_removals_required_to_break_split;
+
+
+                    let mut copy_of_rows = rows.to_vec();
+
+                    let replacement_tree = Tree {
+                        index: self.index,
+                        rng: self.rng.clone(),
+                        tree_elements: Vec::new(),
+                        alternative_subtrees: Vec::new(),
+                        min_leaf_size: self.min_leaf_size,
+                        min_samples_split: self.min_samples_split,
+                        max_depth: self.max_depth,
+                        min_impurity_decrease: self.min_impurity_decrease,
+                        split_criterion: self.split_criterion,
+                        num_attributes_to_try_per_split: self.num_attributes_to_try_per_split,
+                        max_tries_per_split: self.max_tries_per_split,
+                        max_alternatives: self.max_alternatives,
+                        num_robust_nodes: 0,
+                        num_non_robust_nodes: 0,
+                        scan_kernel: self.scan_kernel
+                    };
+
+                    let alternative_candidate_split = candidate_splits.get(index).unwrap();
+                    let alternative_split_stats = split_stats.get(index).unwrap();
+
+                    let mut alternative_tree = AlternativeTree {
+                        split: alternative_candidate_split.clone(),
+                        split_stats: *alternative_split_stats,
+                        tree: replacement_tree
+                    };
+
+                    alternative_tree.tree.split_and_continue(
+                        alternative_target_robustness,
+                        columns,
+                        copy_of_rows.as_mut_slice(),
+                        dataset,
+                        depth,
+                        &mut constant_attribute_indexes.clone(),
+                        alternative_candidate_split,
+                        alternative_split_stats,
+                        histograms
+                    );
+
+                    alternative_trees.push(alternative_tree);
+                }
+
+                // TODO alternative_trees could be a heap, but it probably does not matter
+                // Make sure the split with the highest score is in the first position
+                alternative_trees.sort_by(|tree_a, tree_b| {
+                    tree_b.split_stats.score.cmp(&tree_a.split_stats.score)
+                });
+
+                self.alternative_subtrees.push(alternative_trees);
+
+                self.push(TreeElement::Alternatives {
+                    alternatives: (self.alternative_subtrees.len() - 1) as u32,
+                    budget: Budget::limited(target_robustness, budget_of_position)
+                })
+
+            } else {
+
+                self.num_robust_nodes += 1;
+                //println!("\tRobust,{},{},{},{}", target_robustness, samples.len(), current_id, self.index);
+
+                self.split_and_continue(
+                    target_robustness,
+                    columns,
+                    rows,
+                    dataset,
+                    depth,
+                    constant_attribute_indexes,
+                    best_split_candidate,
+                    best_split_stats,
+                    histograms
+                )
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn split_and_continue<D: Dataset>(
+        &mut self,
+        target_robustness: usize,
+        columns: &ColumnarSamples,
+        rows: &mut [u32],
+        dataset: &D,
+        depth: usize,
+        constant_attribute_indexes: &mut Cow<[u16]>,
+        best_split: &Split,
+        best_split_stats: &SplitStats,
+        histograms: &Histograms
+    ) -> NodeId {
+
+        let (rows_left, constant_on_the_left, rows_right, constant_on_the_right) =
+            split(columns, rows, best_split);
+
+        let is_leaf_left = self.is_leaf(rows_left.len(), &best_split_stats.left, depth + 1);
+        let is_leaf_right = self.is_leaf(rows_right.len(), &best_split_stats.right, depth + 1);
+
+        // The histograms of the children are only needed if at least one of them is split further
+        let (mut histograms_left, mut histograms_right) = if is_leaf_left && is_leaf_right {
+            (Histograms::new(dataset.num_attributes()), Histograms::new(dataset.num_attributes()))
+        } else {
+            histograms.for_children(dataset, columns, rows_left, rows_right)
+        };
+
+        let num_rows = rows_left.len() + rows_right.len();
+        let max_updates = self.budget_of_split(target_robustness, num_rows, best_split_stats, columns.max_weight());
+
+        let node = Tree::node(best_split.clone(), Budget::limited(target_robustness, max_updates));
+
+        let node_id = self.push(node);
+
+        let left_child_id = if is_leaf_left {
+            //println!("Building leaf for {} records", record_ids_left.len());
+
+            let leaf = Tree::leaf(best_split_stats.left, Budget::new(target_robustness));
+
+            self.push(leaf)
+
+        } else {
+
+            let mut constant_attribute_indexes_left = constant_attribute_indexes.clone();
+            if constant_on_the_left {
+                //println!("Constant attribute found in {} records", record_ids_left.len());
+                let attribute_index = best_split.attribute_index();
+                constant_attribute_indexes_left.to_mut().push(attribute_index);
+            }
+
+            self.determine_split(
+                best_split_stats.impurity_left,
+                target_robustness,
+                columns,
+                rows_left,
+                dataset,
+                depth + 1,
+                0,
+                &mut constant_attribute_indexes_left,
+                &mut histograms_left
+            )
+        };
+
+        let right_child_id = if is_leaf_right {
+            //println!("Building leaf for {} records", record_ids_right.len());
+
+            let leaf = Tree::leaf(best_split_stats.right, Budget::new(target_robustness));
+
+            self.push(leaf)
+
+        } else {
+
+            let mut constant_attribute_indexes_right = constant_attribute_indexes.clone();
+            if constant_on_the_right {
+                //println!("Constant attribute found in {} records", record_ids_right.len());
+                let attribute_index = best_split.attribute_index();
+                constant_attribute_indexes_right.to_mut().push(attribute_index);
+            }
 
-                    alternative_trees.iter_mut().for_each(|alternative_tree| {
-                        let stats = &mut alternative_tree.split_stats;
+            self.determine_split(
+                best_split_stats.impurity_right,
+                target_robustness,
+                columns,
+                rows_right,
+                dataset,
+                depth + 1,
+                0,
+                &mut constant_attribute_indexes_right,
+                &mut histograms_right
+            )
+        };
 
-                        if sample.is_left_of(&alternative_tree.split) {
-                            if sample.true_label() {
-                                assert!(stats.num_plus_left != 0);
-                                stats.num_plus_left -= 1;
-                            } else {
-                                assert!(stats.num_minus_left != 0);
-                                stats.num_minus_left -= 1;
-                            }
-                        } else {
-                            if sample.true_label() {
-                                assert!(stats.num_plus_right != 0);
-                                stats.num_plus_right -= 1;
-                            } else {
-                                assert!(stats.num_minus_right != 0);
-                                stats.num_minus_right -= 1;
-                            }
-                        }
+        self.set_children(node_id, left_child_id, right_child_id);
 
-                        stats.update_score_and_impurity_before();
-                    });
+        node_id
+    }
+}
 
-                    // TODO alternative_trees could be a heap, but it probably does not matter
-                    // Make sure the split with the highest score is in the first position
-                    //let current_best = alternative_trees.first().unwrap().split_stats.clone();
-                    alternative_trees.sort_by(|tree_a, tree_b| {
-                        tree_b.split_stats.score.cmp(&tree_a.split_stats.score)
-                    });
-                    //let other_best = &alternative_trees.first().unwrap().split_stats;
+// Candidates on attributes with missing values are also tried with the missing values sent to the
+// other side. The flipped variants are appended as candidates of their own, so that the robustness
+// checks also cover removals which change the better direction. Variants which no row with a
+// missing value reaches are left out, as they are the same split.
+#[allow(clippy::too_many_arguments)]
+fn compute_split_stats<D: Dataset>(
+    impurity_before: f64,
+    columns: &ColumnarSamples,
+    rows: &[u32],
+    dataset: &D,
+    candidate_splits: &mut Vec<Split>,
+    histograms: &mut Histograms,
+    criterion: SplitCriterion,
+    scan_kernel: ScanKernel,
+) -> Vec<SplitStats> {
 
-                    //if !cmp(&other_best, &current_best) {
-                    //    println!("Subtree order changed")
-                    //}
+    let mut all_stats: Vec<SplitStats> = Vec::with_capacity(candidate_splits.len());
+    let mut flipped_candidates = Vec::new();
 
-                    // Afterwards, we invoke the forgetting procedure on the alternative trees
-                    alternative_trees.iter_mut().for_each(|alternative_tree| {
-                        Tree::forget_from(&mut alternative_tree.tree, sample, element_id);
-                    });
+    for candidate in candidate_splits.iter() {
 
-                    break;
-                }
+        let mut stats = split_stats_of(columns, rows, dataset, candidate, histograms, scan_kernel);
+        stats.update_score(impurity_before, criterion);
+
+        if columns.has_missing(candidate.attribute_index()) {
+            let flipped_candidate = candidate.with_missing_left(!candidate.missing_left());
+
+            let mut flipped_stats = split_stats_of(columns, rows, dataset, &flipped_candidate, histograms, scan_kernel);
+
+            if flipped_stats.left != stats.left || flipped_stats.right != stats.right {
+                flipped_stats.update_score(impurity_before, criterion);
+                flipped_candidates.push((flipped_candidate, flipped_stats));
             }
         }
+
+        all_stats.push(stats);
     }
 
-    pub(crate) fn forget_from2<S: Sample>(tree: &mut Tree, sample: &S, element_id_to_start: u64) -> (usize, usize) {
+    for (flipped_candidate, flipped_stats) in flipped_candidates {
+        candidate_splits.push(flipped_candidate);
+        all_stats.push(flipped_stats);
+    }
 
-        let mut num_variants_hit = 0;
-        let mut num_variants_changed = 0;
+    all_stats
+}
 
-        let mut element_id = element_id_to_start;
+fn split_stats_of<D: Dataset>(
+    columns: &ColumnarSamples,
+    rows: &[u32],
+    dataset: &D,
+    candidate: &Split,
+    histograms: &mut Histograms,
+    scan_kernel: ScanKernel,
+) -> SplitStats {
+
+    // The SIMD scans only count unweighted binary labels and cannot handle missing values
+    let is_binary = dataset.task() == Task::Classification && dataset.num_classes() <= 2;
+    let has_missing = columns.has_missing(candidate.attribute_index());
+    let is_weighted = columns.is_weighted();
+
+    let attribute_range = dataset.attribute_range(candidate.attribute_index());
+
+    match candidate {
+        _ if Histograms::pays_off(attribute_range, rows.len()) => {
+            histograms.get_or_build(dataset, columns, rows, candidate.attribute_index())
+                .split_stats(candidate)
+        },
+        _ if !is_binary || has_missing || is_weighted => {
+            scan_column_labels(columns, rows, candidate)
+        },
+        Split::Numerical { attribute_index: _, cut_off: _, missing_left: _ } => {
+            scan_columns(scan_kernel, columns, rows, candidate)
+        },
+        Split::Categorical { attribute_index: _, subset: _, missing_left: _ } => {
+            let (_, max_value) = attribute_range;
+            scan_columns(scan_kernel.for_categories(max_value), columns, rows, candidate)
+        },
+    }
+}
 
-        loop {
+// Partitions the rows into those left and right of the split
+fn split<'a>(
+    columns: &ColumnarSamples,
+    rows: &'a mut [u32],
+    split: &Split
+) -> (&'a mut [u32], bool, &'a mut [u32], bool) {
+
+    let column = columns.column(split.attribute_index());
 
-            let element = tree.tree_elements.get(&element_id);
+    let mut cursor = 0;
+    let mut cursor_end = rows.len();
 
-            match element {
+    let mut constant_on_the_left = true;
+    let mut first_value_on_the_left: Option<u16> = None;
+    let mut constant_on_the_right = true;
+    let mut first_value_on_the_right: Option<u16> = None;
 
-                Some(TreeElement::Node { split }) => {
+    loop {
 
-                    if sample.is_left_of(split) {
-                        element_id = element_id * 2;
-                    } else {
-                        element_id = (element_id * 2) + 1;
-                    }
+        let attribute_value: u16 = column[rows[cursor] as usize];
+
+        if split.is_left(attribute_value) {
+
+            if constant_on_the_left {
+                match first_value_on_the_left {
+                    None => first_value_on_the_left = Some(attribute_value),
+                    Some(value) if value != attribute_value => constant_on_the_left = false,
+                    _ => {}
                 }
+            }
 
-                Some(TreeElement::Leaf { num_samples, num_plus }) => {
+            cursor += 1;
+        } else {
 
-                    let new_num_samples = num_samples - 1;
-                    let new_num_plus = if sample.true_label() {
-                        *num_plus - 1
-                    } else {
-                        *num_plus
-                    };
+            if constant_on_the_right {
+                match first_value_on_the_right {
+                    None => first_value_on_the_right = Some(attribute_value),
+                    Some(value) if value != attribute_value => constant_on_the_right = false,
+                    _ => {}
+                }
+            }
 
-                    let updated_leaf = Tree::leaf(new_num_samples, new_num_plus);
-                    tree.tree_elements.insert(element_id, updated_leaf);
-                    break;
+            cursor_end -= 1;
+            rows.swap(cursor, cursor_end);
+        }
+
+        if cursor == cursor_end {
+            break;
+        }
+    }
+
+    let (rows_left, rows_right) = rows.split_at_mut(cursor);
+
+    (rows_left, constant_on_the_left, rows_right, constant_on_the_right)
+}
+
+fn generate_random_split<D: Dataset>(
+    rng: &mut XorShiftRng,
+    dataset: &D,
+    attribute_index: u16
+) -> Split {
+    match dataset.attribute_type(attribute_index) {
+        AttributeType::Numerical => {
+            let (min_value, max_value) = dataset.attribute_range(attribute_index);
+
+            let random_cut_off = rng.gen_range(min_value as u32, max_value as u32 + 1) as u16;
+
+            Split::new_numerical(attribute_index, random_cut_off)
+        },
+        AttributeType::Categorical => {
+            let (_, cardinality) = dataset.attribute_range(attribute_index);
+
+            let how_many = rng.gen_range(0, cardinality as u32 + 1);
+
+            // Floyd's algorithm draws a uniformly random subset of the values 0..=cardinality
+            // without materializing them
+            let num_values = cardinality as u32 + 1;
+            let mut subset = CategorySet::new();
+
+            for upper in (num_values - how_many)..num_values {
+                let value = rng.gen_range(0, upper + 1) as u16;
+                if subset.contains(value) {
+                    subset.insert(upper as u16);
+                } else {
+                    subset.insert(value);
                 }
+            }
 
-                None => {
-                    // We hit a non-robust node
-                    //eprintln!("Hit a non-robust node!");
-                    num_variants_hit += 1;
+            Split::new_categorical(attribute_index, subset)
+        }
+    }
+}
 
-                    // First we have to update the split stats
-                    let alternative_trees =
-                        &mut *tree.alternative_subtrees.get_mut(&element_id).unwrap();
+#[cfg(test)]
+mod tests {
+
+    use crate::testing::{synthetic_samples, synthetic_samples_with_classes, synthetic_regression_samples,
+        synthetic_high_cardinality_samples, synthetic_samples_with_missing_values, synthetic_weighted_samples};
+    use crate::dataset::{Dataset, Sample, MISSING};
+    use crate::tree::{ExtremelyRandomizedTrees, ForgetError, FitError, AddError, AddOutcome, TreeElement, NodeId, ROOT,
+        AlternativeTree, Budget, CategorySet, ClassWeights, ClassWeightsError, Split, Tree, compute_split_stats};
+    use crate::columnar::ColumnarSamples;
+    use crate::histogram::Histograms;
+    use crate::scan::ScanKernel;
+    use crate::config::{ConfigError, ForestConfig, MaxFeatures};
+    use crate::schema::{RowSample, SchemaDataset};
+    use crate::bootstrap::{multiplicity, MAX_WEIGHT};
+    use crate::split_stats::{LabelStats, SplitCriterion, SplitStats, to_score};
+
+    #[test]
+    fn forgetting_fails_gracefully() {
+        let (dataset, samples) = synthetic_samples(1000);
+
+        let mut trees = ExtremelyRandomizedTrees::fit_with_epsilon(&dataset, samples.clone(), 42, 10, 2, 5, 0.01);
+
+        let sample = samples.first().unwrap();
+        assert_eq!(trees.forget(sample), Ok(()));
+
+        let model_before = trees.to_bytes();
+        assert_eq!(trees.forget(sample), Err(ForgetError::AlreadyForgotten));
+        assert_eq!(model_before, trees.to_bytes());
+
+        // A different record with identical values is still part of the training set
+        let mut duplicate = sample.clone();
+        duplicate.record_id = 1000;
+        assert_eq!(trees.forget(&duplicate), Err(ForgetError::NotInTrainingSet));
+        assert_eq!(model_before, trees.to_bytes());
+
+        // Values outside of the attribute ranges cannot belong to a training sample
+        let corrupted_sample = RowSample { record_id: 1, values: vec![255, 255, 0], label: 0, target: 0.0, weight: 1 };
+        assert_eq!(trees.forget(&corrupted_sample), Err(ForgetError::SampleMismatch));
+        assert_eq!(model_before, trees.to_bytes());
+
+        // Neither can a known record id with a different label or weight
+        let mut relabeled = samples[1].clone();
+        relabeled.label = 1 - relabeled.label;
+        assert_eq!(trees.forget(&relabeled), Err(ForgetError::SampleMismatch));
+
+        let mut reweighted = samples[1].clone();
+        reweighted.weight = 2;
+        assert_eq!(trees.forget(&reweighted), Err(ForgetError::SampleMismatch));
+        assert_eq!(model_before, trees.to_bytes());
+        assert_eq!(trees.forget(&samples[1]), Ok(()));
+    }
 
-                    alternative_trees.iter_mut().for_each(|alternative_tree| {
-                        let stats = &mut alternative_tree.split_stats;
+    #[test]
+    fn invalid_record_ids_fail_gracefully() {
+        let (dataset, samples) = synthetic_samples(500);
+        let config = ForestConfig::builder().seed(42).num_trees(3).build().unwrap();
 
-                        if sample.is_left_of(&alternative_tree.split) {
-                            if sample.true_label() {
-                                stats.num_plus_left -= 1;
-                            } else {
-                                stats.num_minus_left -= 1;
-                            }
-                        } else {
-                            if sample.true_label() {
-                                stats.num_plus_right -= 1;
-                            } else {
-                                stats.num_minus_right -= 1;
-                            }
-                        }
+        let mut duplicated_samples = samples.clone();
+        duplicated_samples.push(samples[7].clone());
+        let duplicated_trees = ExtremelyRandomizedTrees::fit_with_config(&dataset, duplicated_samples.clone(), config);
+        assert_eq!(duplicated_trees.err(), Some(FitError::DuplicateRecordId(samples[7].record_id)));
 
-                        stats.update_score_and_impurity_before();
-                    });
+        let mut trees = ExtremelyRandomizedTrees::fit_with_config(&dataset, samples.clone(), config).unwrap();
+        assert_eq!(trees.forget(&samples[3]), Ok(()));
+        let model_before = trees.to_bytes();
 
-                    // TODO alternative_trees could be a heap, but it probably does not matter
-                    // Make sure the split with the highest score is in the first position
-                    let current_best = alternative_trees.first().unwrap().split_stats.clone();
-                    alternative_trees.sort_by(|tree_a, tree_b| {
-                        tree_b.split_stats.score.cmp(&tree_a.split_stats.score)
-                    });
-                    let other_best = &alternative_trees.first().unwrap().split_stats;
+        // Forgotten records must not come back, and the forest stays untouched on errors
+        assert_eq!(trees.retrain(&dataset, samples.clone()), Err(FitError::Forgotten(samples[3].record_id)));
+        assert_eq!(trees.retrain_exhausted(&dataset, &samples), Err(FitError::Forgotten(samples[3].record_id)));
+        assert_eq!(trees.retrain(&dataset, duplicated_samples[4..].to_vec()),
+            Err(FitError::DuplicateRecordId(samples[7].record_id)));
+        assert_eq!(model_before, trees.to_bytes());
 
-                    if !cmp(&other_best, &current_best) {
-                        //println!("Subtree order changed")
-                        num_variants_changed += 1;
-                    }
+        assert_eq!(trees.retrain(&dataset, samples[4..].to_vec()), Ok(()));
+    }
 
-                    // Afterwards, we invoke the forgetting procedure on the alternative trees
-                    alternative_trees.iter_mut().for_each(|alternative_tree| {
-                        let (alt_hit, alt_changed) = Tree::forget_from2(&mut alternative_tree.tree, sample, element_id);
-                        num_variants_hit += alt_hit;
-                        num_variants_changed += alt_changed;
-                    });
+    #[test]
+    fn ledger_records_forgotten_samples_in_order() {
+        let (dataset, samples) = synthetic_samples(500);
 
-                    break;
+        let mut trees = ExtremelyRandomizedTrees::fit(&dataset, samples.clone(), 42, 5, 2, 5);
+
+        for sample in samples.iter().rev().take(3) {
+            trees.forget(sample).unwrap();
+        }
+
+        let forgotten: Vec<u64> = trees.ledger().entries().iter().map(|entry| entry.record_id).collect();
+        assert_eq!(forgotten, vec![499, 498, 497]);
+
+        assert!(!trees.contains_record(499));
+        assert!(trees.contains_record(0));
+        assert!(trees.ledger().get(0).is_none());
+
+        let entries = trees.ledger().entries();
+        assert!(entries.windows(2).all(|pair| pair[0].forgotten_at <= pair[1].forgotten_at));
+    }
+
+    #[test]
+    fn small_datasets_absorb_a_first_removal() {
+        let (dataset, samples) = synthetic_samples(40);
+
+        // 40 * 0.001 rounds to zero removals
+        let mut trees = ExtremelyRandomizedTrees::fit_with_epsilon(&dataset, samples.clone(), 42, 10, 2, 5, 0.001);
+
+        trees.forget(&samples[3]).unwrap();
+        assert!(!trees.needs_retraining());
+
+        trees.forget(&samples[4]).unwrap();
+        assert!(trees.needs_retraining());
+    }
+
+    #[test]
+    fn budget_is_consumed_until_retraining() {
+        let (dataset, samples) = synthetic_samples(1000);
+
+        let mut trees = ExtremelyRandomizedTrees::fit_with_epsilon(&dataset, samples.clone(), 42, 5, 2, 5, 0.005);
+
+        let remaining_at_roots = |trees: &ExtremelyRandomizedTrees| -> Vec<u32> {
+            trees.trees.iter().map(|tree| tree.budget(ROOT).unwrap().remaining()).collect()
+        };
+
+        // Every forgotten sample passes through the root of each tree
+        assert_eq!(remaining_at_roots(&trees), vec![5; 5]);
+        assert!(trees.trees.iter().all(|tree| tree.budget(ROOT).unwrap().target_robustness == 5));
+
+        for sample in samples.iter().take(5) {
+            trees.forget(sample).unwrap();
+        }
+
+        assert_eq!(trees.remaining_budget(), Some(0));
+        assert!(trees.trees.iter().all(|tree| tree.exhausted_nodes() == vec![ROOT]));
+        assert!(!trees.needs_retraining());
+
+        trees.forget(&samples[5]).unwrap();
+        assert!(trees.needs_retraining());
+
+        let remaining_samples: Vec<RowSample> = samples[6..].to_vec();
+        let remaining_dataset = SchemaDataset::from_samples(dataset.schema.clone(), &remaining_samples);
+
+        assert!(trees.retrain_if_needed(&remaining_dataset, remaining_samples.clone()).unwrap());
+        assert!(!trees.needs_retraining());
+        assert_eq!(remaining_at_roots(&trees), vec![5; 5]);
+        assert_eq!(trees.ledger().len(), 6);
+        assert_eq!(trees.forget(&samples[0]), Err(ForgetError::AlreadyForgotten));
+
+        assert!(!trees.retrain_if_needed(&remaining_dataset, remaining_samples).unwrap());
+    }
+
+    // Returns the number of samples below the element, and checks that no node absorbs more
+    // removals than it has samples beyond the minimum leaf size
+    fn assert_budgets_within_rows(tree: &Tree, node_id: NodeId, min_leaf_size: usize, num_limited: &mut usize) -> usize {
+        match &tree.tree_elements[node_id as usize] {
+            TreeElement::Node { split: _, left_child, right_child, budget } => {
+                let num_rows = assert_budgets_within_rows(tree, *left_child, min_leaf_size, num_limited) +
+                    assert_budgets_within_rows(tree, *right_child, min_leaf_size, num_limited);
+
+                assert!(budget.max_updates as usize <= num_rows - (min_leaf_size + 1));
+                if budget.max_updates < budget.target_robustness {
+                    *num_limited += 1;
                 }
+                num_rows
+            }
+            TreeElement::Leaf { label_stats, .. } => label_stats.total() as usize,
+            TreeElement::Alternatives { alternatives, budget: _ } => {
+                let current_tree = &tree.alternative_subtrees[*alternatives as usize][0].tree;
+                assert_budgets_within_rows(current_tree, ROOT, min_leaf_size, num_limited)
             }
         }
+    }
 
-        (num_variants_hit, num_variants_changed)
+    #[test]
+    fn small_nodes_only_absorb_removals_beyond_the_minimum_leaf_size() {
+        let (dataset, samples) = synthetic_samples(1000);
+
+        let trees = ExtremelyRandomizedTrees::fit_with_epsilon(&dataset, samples, 42, 5, 2, 5, 0.005);
+
+        let mut num_limited = 0;
+        for tree in &trees.trees {
+            assert_budgets_within_rows(tree, ROOT, 2, &mut num_limited);
+        }
+
+        assert!(num_limited > 0);
+
+        // Nodes without budget are not rebuilt before an update reaches them
+        assert_eq!(trees.remaining_budget(), Some(0));
+        assert!(trees.trees.iter().all(|tree| tree.exhausted_nodes().is_empty()));
     }
 
-    fn generate_candidate_splits<D: Dataset>(
-        &mut self,
-        dataset: &D,
-        constant_attribute_indexes: &Cow<[u8]>
-    ) -> Vec<Split> {
+    #[test]
+    fn retraining_exhausted_subtrees_restores_budgets() {
+        let (dataset, samples) = synthetic_samples(1000);
 
-        let mut attribute_indexes: Vec<u8> = (0..dataset.num_attributes())
-            // TODO This searches linearly, but does it matter here?
-            .filter(|attribute_index| !constant_attribute_indexes.contains(attribute_index))
+        let mut trees = ExtremelyRandomizedTrees::fit_with_epsilon(&dataset, samples.clone(), 42, 5, 2, 5, 0.005);
+
+        for sample in samples.iter().take(6) {
+            trees.forget(sample).unwrap();
+        }
+        assert!(trees.needs_retraining());
+
+        let remaining_samples = &samples[6..];
+
+        // The roots are exhausted, so every tree is rebuilt from the root
+        assert_eq!(trees.retrain_exhausted(&dataset, remaining_samples).unwrap(), 5);
+        assert!(!trees.needs_retraining());
+        assert!(trees.trees.iter().all(|tree| tree.budget(ROOT).unwrap().remaining() == 5));
+        assert_eq!(trees.retrain_exhausted(&dataset, remaining_samples).unwrap(), 0);
+
+        for tree in &trees.trees {
+            assert!(tree.validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn retraining_a_subtree_keeps_the_rest_of_the_tree() {
+        let (dataset, samples) = synthetic_samples(1000);
+
+        let mut trees = ExtremelyRandomizedTrees::fit_with_epsilon(&dataset, samples.clone(), 42, 5, 2, 5, 0.005);
+
+        let tree = trees.trees.iter_mut()
+            .find(|tree| matches!(tree.tree_elements[ROOT as usize], TreeElement::Node { .. }))
+            .unwrap();
+
+        let children_of_root = |tree: &Tree| match &tree.tree_elements[ROOT as usize] {
+            TreeElement::Node { split: _, left_child, right_child, budget: _ } => (*left_child, *right_child),
+            _ => panic!("The root is not a node!"),
+        };
+
+        let (left_child, right_child) = children_of_root(tree);
+        let root = preorder(tree, ROOT).first().cloned();
+        let right_subtree = preorder(tree, right_child);
+
+        let samples_of_node: Vec<RowSample> = samples.iter()
+            .filter(|sample| tree.reaches(*sample, left_child))
+            .cloned()
             .collect();
+        let num_samples_of_node = samples_of_node.len() as u32;
 
-        attribute_indexes.shuffle(&mut self.rng);
+        tree.retrain_subtree(&dataset, left_child, &samples_of_node);
 
+        assert!(tree.validate().is_ok());
 
-        // TODO can we allocate once and reuse the vec somehow?
-        let split_candidates: Vec<Split> = attribute_indexes.iter()
-            .take(self.num_attributes_to_try_per_split)
-            .map(|attribute_index| generate_random_split(&mut self.rng, dataset, *attribute_index))
+        let (left_child, right_child) = children_of_root(tree);
+        assert_eq!(root, preorder(tree, ROOT).first().cloned());
+        assert_eq!(right_subtree, preorder(tree, right_child));
+
+        let left_subtree = preorder(tree, left_child);
+        let num_samples_below: u32 = left_subtree.iter()
+            .map(|element| match element {
+                TreeElement::Leaf { label_stats, .. } => label_stats.total(),
+                _ => 0,
+            })
+            .sum();
+
+        if left_subtree.iter().all(|element| !matches!(element, TreeElement::Alternatives { .. })) {
+            assert_eq!(num_samples_below, num_samples_of_node);
+        }
+
+        assert_eq!((tree.num_robust_nodes, tree.num_non_robust_nodes), tree.count_below(ROOT));
+    }
+
+    #[test]
+    fn retraining_an_alternative_subtree_keeps_its_node_counts() {
+        let (dataset, samples) = synthetic_samples(1000);
+
+        let mut trees = ExtremelyRandomizedTrees::fit_with_epsilon(&dataset, samples.clone(), 42, 5, 2, 5, 0.01);
+
+        let mut num_retrained = 0;
+        for tree in trees.trees.iter_mut() {
+            let positions: Vec<(NodeId, usize)> = tree.tree_elements.iter().enumerate()
+                .filter_map(|(node_id, element)| match element {
+                    TreeElement::Alternatives { alternatives, .. } => Some((node_id as NodeId, *alternatives as usize)),
+                    _ => None,
+                })
+                .collect();
+
+            for (node_id, alternatives) in positions {
+                let samples_of_position: Vec<RowSample> = samples.iter()
+                    .filter(|sample| tree.reaches(*sample, node_id))
+                    .cloned()
+                    .collect();
+
+                // The root of an alternative subtree was not counted when it was fitted
+                let alternative_tree = &mut tree.alternative_subtrees[alternatives][0].tree;
+                alternative_tree.retrain_subtree(&dataset, ROOT, &samples_of_position);
+
+                assert!(alternative_tree.validate().is_ok());
+                assert_eq!(
+                    (alternative_tree.num_robust_nodes, alternative_tree.num_non_robust_nodes),
+                    alternative_tree.count_below(ROOT)
+                );
+                num_retrained += 1;
+            }
+        }
+
+        assert!(num_retrained > 0);
+    }
+
+    // Elements of the subtree in depth-first order, without the ids of children and alternatives
+    fn preorder(tree: &Tree, node_id: NodeId) -> Vec<TreeElement> {
+        let mut elements = Vec::new();
+        let mut node_ids = vec![node_id];
+
+        while let Some(node_id) = node_ids.pop() {
+            match &tree.tree_elements[node_id as usize] {
+                TreeElement::Node { split, left_child, right_child, budget } => {
+                    node_ids.push(*right_child);
+                    node_ids.push(*left_child);
+                    elements.push(TreeElement::Node { split: split.clone(), left_child: ROOT, right_child: ROOT, budget: *budget });
+                }
+                TreeElement::Alternatives { alternatives: _, budget } => {
+                    elements.push(TreeElement::Alternatives { alternatives: 0, budget: *budget });
+                }
+                element => elements.push(element.clone()),
+            }
+        }
+
+        elements
+    }
+
+    #[test]
+    fn deep_trees_are_not_limited_by_element_ids() {
+        let (dataset, samples) = synthetic_samples(300);
+        let mut trees = ExtremelyRandomizedTrees::fit(&dataset, samples.clone(), 42, 1, 2, 5);
+
+        // A chain of 100 nodes which only send the samples with a huge first value to the right
+        let tree = &mut trees.trees[0];
+        let leaf_stats = match tree.tree_elements.iter().find(|element| matches!(element, TreeElement::Leaf { .. })) {
+            Some(TreeElement::Leaf { label_stats, .. }) => label_stats.label_stats(),
+            _ => panic!("No leaf found!"),
+        };
+
+        tree.tree_elements.clear();
+        tree.alternative_subtrees.clear();
+
+        for depth in 0..100 {
+            tree.push(Tree::node(Split::new_numerical(0, 1000 + depth), Budget::limited(10, 10)));
+        }
+        let first_leaf = tree.push(Tree::leaf(leaf_stats, Budget::new(10)));
+        for node_id in 0..100 {
+            let left = if node_id == 99 { first_leaf } else { node_id + 1 };
+            let right = tree.push(Tree::leaf(leaf_stats, Budget::new(10)));
+            tree.set_children(node_id, left, right);
+        }
+
+        assert!(tree.validate().is_ok());
+        assert!(tree.reaches(&samples[0], first_leaf));
+        assert_eq!(tree.leaf_stats(&samples[0]), leaf_stats);
+
+        let restored = ExtremelyRandomizedTrees::from_bytes(&trees.to_bytes()).unwrap();
+        assert_eq!(restored.trees[0].leaf_stats(&samples[0]), leaf_stats);
+    }
+
+    #[test]
+    fn added_samples_can_be_forgotten_again() {
+        let (_, samples) = synthetic_samples(1000);
+        let (training_samples, new_samples) = samples.split_at(900);
+
+        let dataset = SchemaDataset::from_samples(synthetic_samples(0).0.schema, training_samples);
+        let mut trees = ExtremelyRandomizedTrees::fit_with_epsilon(&dataset, training_samples.to_vec(), 42, 5, 2, 5, 0.01);
+
+        assert_eq!(trees.add(&new_samples[0]), Ok(AddOutcome::Robust));
+        assert_eq!(trees.add(&new_samples[0]), Err(AddError::AlreadyInTrainingSet));
+        assert_eq!(trees.add(&training_samples[0]), Err(AddError::AlreadyInTrainingSet));
+
+        assert_eq!(trees.forget(&new_samples[0]), Ok(()));
+        assert_eq!(trees.add(&new_samples[0]), Err(AddError::Forgotten));
+
+        // The roots absorb every update, so their budget of 9 is exceeded by the tenth update at
+        // the latest. Updates which make a retrain split a leaf need a rebuild earlier.
+        let outcomes: Vec<AddOutcome> = new_samples[1..10].iter()
+            .map(|sample| trees.add(sample).unwrap())
             .collect();
 
-        split_candidates
+        let first_rebuild = outcomes.iter().position(|outcome| *outcome == AddOutcome::NeedsRebuild).unwrap();
+        assert!(first_rebuild <= 7);
+        assert!(outcomes[first_rebuild..].iter().all(|outcome| *outcome == AddOutcome::NeedsRebuild));
+
+        let mut current_samples = training_samples.to_vec();
+        current_samples.extend_from_slice(&new_samples[1..10]);
+
+        trees.retrain_exhausted(&dataset, &current_samples).unwrap();
+        assert!(!trees.needs_retraining());
     }
 
+    #[test]
+    fn adding_to_a_leaf_which_a_retrain_would_split_needs_a_rebuild() {
+        let (dataset, samples) = synthetic_samples(1000);
 
-    fn determine_split<D: Dataset, S: Sample>(
-        &mut self,
-        impurity_before: f64,
-        target_robustness: usize,
-        samples: &mut [S],
-        dataset: &D,
-        current_id: u64,
-        num_tries: usize,
-        constant_attribute_indexes: &mut Cow<[u8]>
-    ) {
-        assert!(samples.len() > self.min_leaf_size);
+        let mut trees = ExtremelyRandomizedTrees::fit_with_epsilon(&dataset, samples.clone(), 42, 5, 2, 5, 0.05);
 
-        // All attributes are constant, we create a leaf now
-        if constant_attribute_indexes.len() == dataset.num_attributes() as usize {
+        // A pure leaf with more than min_leaf_size samples only stopped because it is pure
+        let sample = samples.iter()
+            .find(|sample| {
+                let leaf_stats = trees.trees[0].leaf_stats(*sample);
+                leaf_stats.is_pure() && leaf_stats.total() > 2
+            })
+            .unwrap();
+
+        let mut relabeled = sample.clone();
+        relabeled.record_id = 1000;
+        relabeled.label = 1 - relabeled.label;
+
+        assert_eq!(trees.add(&relabeled), Ok(AddOutcome::NeedsRebuild));
+        assert!(!trees.trees[0].exhausted_nodes().is_empty());
+
+        let mut current_samples = samples.clone();
+        current_samples.push(relabeled);
+
+        trees.retrain_exhausted(&dataset, &current_samples).unwrap();
+        assert!(!trees.needs_retraining());
+    }
+
+    #[test]
+    fn invalid_samples_are_not_added() {
+        let (dataset, samples) = synthetic_samples(500);
+
+        let mut trees = ExtremelyRandomizedTrees::fit(&dataset, samples.clone(), 42, 5, 2, 5);
+        let model_before = trees.to_bytes();
+
+        let mut sample = samples[0].clone();
+        sample.record_id = 500;
+
+        let mut out_of_range = sample.clone();
+        out_of_range.values[1] = dataset.attribute_range(1).1 + 1;
+        assert_eq!(trees.add(&out_of_range), Err(AddError::InvalidValue(1)));
+
+        let mut unknown_class = sample.clone();
+        unknown_class.label = 2;
+        assert_eq!(trees.add(&unknown_class), Err(AddError::InvalidLabel));
+
+        let mut weightless = sample.clone();
+        weightless.weight = 0;
+        assert_eq!(trees.add(&weightless), Err(AddError::InvalidWeight));
+
+        let mut too_heavy = sample.clone();
+        too_heavy.weight = MAX_WEIGHT + 1;
+        assert_eq!(trees.add(&too_heavy), Err(AddError::InvalidWeight));
+
+        assert_eq!(model_before, trees.to_bytes());
+        assert!(!trees.contains_record(500));
+
+        sample.values[1] = MISSING;
+        assert!(trees.add(&sample).is_ok());
+    }
 
-            let num_plus = samples.iter().filter(|sample| sample.true_label()).count();
-            let num_samples = samples.len();
+    #[test]
+    fn batch_predictions_match_single_predictions() {
+        let (dataset, samples) = synthetic_samples(2000);
+        let (training_samples, test_samples) = samples.split_at(1000);
 
-            let leaf = Tree::leaf(num_samples as u32, num_plus as u32);
+        let trees = ExtremelyRandomizedTrees::fit(&dataset, training_samples.to_vec(), 42, 10, 2, 5);
 
-            self.tree_elements.insert(current_id, leaf);
+        let predictions = trees.predict_batch(test_samples);
+        let probabilities = trees.predict_proba_batch(test_samples);
 
-            return;
-        }
+        assert_eq!(predictions.len(), test_samples.len());
 
-        let candidate_splits = self.generate_candidate_splits(dataset, &constant_attribute_indexes);
+        for ((sample, prediction), probability) in test_samples.iter().zip(predictions).zip(probabilities) {
+            assert_eq!(prediction, trees.predict(sample));
+            assert!((probability - trees.predict_proba(sample)).abs() < 1e-12);
+        }
+    }
 
-        let split_stats = compute_split_stats(
-            impurity_before,
-            &samples,
-            dataset,
-            &candidate_splits
-        );
+    #[test]
+    fn multiclass_forest_learns_and_forgets() {
+        let (dataset, samples) = synthetic_samples_with_classes(2000, 3);
+        let (training_samples, test_samples) = samples.split_at(1500);
 
-        let maybe_best_split_stats = split_stats.iter().enumerate()
-            .filter(|(_, stats)| stats.has_positive_score())
-            .max_by(|(_, stats1), (_, stats2)| stats1.score.cmp(&stats2.score));
+        let mut trees = ExtremelyRandomizedTrees::fit(&dataset, training_samples.to_vec(), 42, 10, 2, 5);
 
-        if maybe_best_split_stats.is_none() {
-            if num_tries < self.max_tries_per_split {
-                self.determine_split(
-                    impurity_before,
-                    target_robustness,
-                    samples,
-                    dataset,
-                    current_id,
-                    num_tries + 1,
-                    constant_attribute_indexes
-                );
+        assert_eq!(trees.num_classes(), 3);
 
-                return;
-            } else {
+        let num_correct = test_samples.iter()
+            .filter(|sample| trees.predict_class(*sample) == sample.label)
+            .count();
 
-                // We only need stats that are indepent of the split
-                let some_stats = split_stats.first().unwrap();
-                let num_plus = some_stats.num_plus_left + some_stats.num_plus_right;
-                let num_samples = some_stats.num_minus_left + some_stats.num_minus_right + num_plus;
+        assert!(num_correct as f64 / test_samples.len() as f64 > 0.7);
 
-                let leaf = Tree::leaf(num_samples, num_plus);
+        for sample in test_samples.iter().take(20) {
+            let probabilities = trees.predict_class_proba(sample);
+            assert_eq!(probabilities.len(), 3);
+            assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        }
 
-                self.tree_elements.insert(current_id, leaf);
+        for sample in training_samples.iter().take(10) {
+            assert_eq!(trees.forget(sample), Ok(()));
+        }
 
-                return;
-            }
+        let restored = ExtremelyRandomizedTrees::from_bytes(&trees.to_bytes()).unwrap();
+        for sample in test_samples {
+            assert_eq!(trees.predict_class(sample), restored.predict_class(sample));
         }
+    }
 
-        let (index_of_best_stats, best_split_stats) = maybe_best_split_stats.unwrap();
+    #[test]
+    fn alternatives_are_scored_with_the_split_criterion() {
+        let (dataset, samples) = synthetic_samples_with_classes(2000, 3);
+        let (training_samples, test_samples) = samples.split_at(1500);
 
-        assert!(best_split_stats.has_positive_score());
+        for criterion in [SplitCriterion::Gini, SplitCriterion::Entropy, SplitCriterion::Hellinger].iter() {
+            let config = ForestConfig::builder()
+                .seed(41)
+                .num_trees(5)
+                .split_criterion(*criterion)
+                .target_robustness(10)
+                .build()
+                .unwrap();
 
-        let best_split_candidate = candidate_splits.get(index_of_best_stats).unwrap();
+            let mut trees = ExtremelyRandomizedTrees::fit_with_config(&dataset, training_samples.to_vec(), config).unwrap();
 
-        let mut at_least_one_non_robust = false;
-        let mut _num_removals_required = 0;
+            let num_correct = test_samples.iter()
+                .filter(|sample| trees.predict_class(*sample) == sample.label)
+                .count();
 
-        for (index, stats) in split_stats.iter().enumerate() {
-            // We only check splits that make sense!
-            if index != index_of_best_stats && stats.has_positive_score() {
+            assert!(num_correct as f64 / test_samples.len() as f64 > 0.7);
 
-                let (is_robust_split, num_removals_evaluated) =
-                    is_robust(best_split_stats, stats, target_robustness);
+            assert!(trees.trees.iter().any(|tree| !tree.alternative_subtrees.is_empty()));
 
-                if !is_robust_split {
-                    at_least_one_non_robust = true;
-                    _num_removals_required = num_removals_evaluated;
-                    break;
+            for sample in training_samples.iter().take(15) {
+                assert_eq!(trees.forget(sample), Ok(()));
+            }
+
+            for tree in &trees.trees {
+                for alternative_trees in all_alternatives(tree) {
+                    for alternative_tree in alternative_trees {
+                        let stats = &alternative_tree.split_stats;
+                        let (score, _, _) = criterion.score_with_impurity_before(&stats.left, &stats.right);
+                        assert_eq!(stats.score, score);
+                    }
                 }
             }
         }
+    }
 
-        if at_least_one_non_robust && num_tries < self.max_tries_per_split {
-            //println!("Non-robust split found, retrying...");
-            self.determine_split(
-                impurity_before,
-                target_robustness,
-                samples,
-                dataset,
-                current_id,
-                num_tries + 1,
-                constant_attribute_indexes
-            );
-        } else {
+    #[test]
+    fn regression_forest_learns_and_forgets() {
+        let (dataset, samples) = synthetic_regression_samples(2000);
+        let (training_samples, test_samples) = samples.split_at(1500);
 
-            if at_least_one_non_robust {
+        let mut trees = ExtremelyRandomizedTrees::fit(&dataset, training_samples.to_vec(), 42, 10, 2, 5);
 
-                let mut alternative_splits: Vec<(usize, usize)> = split_stats.iter()
-                    .enumerate()
-                    .filter(|(index, stats)| *index != index_of_best_stats && stats.has_positive_score())
-                    .filter_map(|(index, stats)| {
-                        let (is_robust_split, num_removals_required_to_break_split) =
-                            is_robust(best_split_stats, stats, target_robustness);
+        let mean = training_samples.iter().map(|sample| sample.target).sum::<f64>() / 1500.0;
 
-                        if is_robust_split {
-                            None
-                        } else {
-                            Some((index, num_removals_required_to_break_split))
-                        }
-                    })
-                    .collect();
+        assert_eq!(trees.set_class_weights(ClassWeights::Balanced), Err(ClassWeightsError::Regression));
 
-                self.num_non_robust_nodes += 1;
-                // TODO REVISION log this out
-                //println!("\tVariant,{},{},{},{}", target_robustness, samples.len(), current_id, self.index);
-                // eprintln!(
-                //     "Non-robust split ({}) on {} records with {} alternatives \
-                //     for element_id {} in tree {}.",
-                //     _num_removals_required,
-                //     samples.len(),
-                //     alternative_splits.len(),
-                //     current_id,
-                //     self.index
-                // );
+        let error_of = |trees: &ExtremelyRandomizedTrees| -> f64 {
+            test_samples.iter()
+                .map(|sample| (trees.predict_value(sample) - sample.target).abs())
+                .sum::<f64>() / test_samples.len() as f64
+        };
+        let error_of_mean = test_samples.iter()
+            .map(|sample| (mean - sample.target).abs())
+            .sum::<f64>() / test_samples.len() as f64;
 
-                alternative_splits.push((index_of_best_stats, 0));
+        assert!(error_of(&trees) < error_of_mean / 2.0);
 
-                let mut alternative_trees: Vec<AlternativeTree> =
-                    Vec::with_capacity(alternative_splits.len());
+        for sample in training_samples.iter().take(20) {
+            assert_eq!(trees.forget(sample), Ok(()));
+        }
 
-                for (index, num_removals_required_to_break_split) in alternative_splits {
+        assert!(trees.trees.iter().all(|tree| tree.validate().is_ok()));
+        assert!(error_of(&trees) < error_of_mean / 2.0);
 
-                    let alternative_target_robustness =
-                        target_robustness - num_removals_required_to_break_split;
+        let restored = ExtremelyRandomizedTrees::from_bytes(&trees.to_bytes()).unwrap();
+        for sample in test_samples {
+            assert_eq!(trees.predict_value(sample), restored.predict_value(sample));
+        }
+    }
 
+    #[test]
+    #[should_panic(expected = "use predict_value for regression")]
+    fn regression_forests_do_not_predict_labels() {
+        let (dataset, samples) = synthetic_regression_samples(200);
+        let trees = ExtremelyRandomizedTrees::fit(&dataset, samples.clone(), 42, 2, 2, 5);
 
-                    let mut copy_of_samples = samples.to_vec();
+        trees.predict_batch(&samples);
+    }
 
-                    let replacement_tree = Tree {
-                        index: self.index,
-                        rng: self.rng.clone(),
-                        tree_elements: HashMap::new(),
-                        alternative_subtrees: HashMap::new(),
-                        min_leaf_size: self.min_leaf_size,
-                        num_attributes_to_try_per_split: self.num_attributes_to_try_per_split,
-                        max_tries_per_split: self.max_tries_per_split,
-                        num_robust_nodes: 0,
-                        num_non_robust_nodes: 0
-                    };
+    #[test]
+    fn category_sets_hold_arbitrary_values() {
+        let subset: CategorySet = vec![3, 64, 1000, 3].into_iter().collect();
 
-                    let alternative_candidate_split = candidate_splits.get(index).unwrap();
-                    let alternative_split_stats = split_stats.get(index).unwrap();
+        assert_eq!(subset.len(), 3);
+        assert!(subset.contains(3) && subset.contains(64) && subset.contains(1000));
+        assert!(!subset.contains(4) && !subset.contains(999) && !subset.contains(u16::MAX));
+        assert_eq!(subset.max_value(), Some(1000));
+        assert_eq!(subset.lower_bits(), 1 << 3);
 
-                    let mut alternative_tree = AlternativeTree {
-                        split: alternative_candidate_split.clone(),
-                        split_stats: alternative_split_stats.clone(),
-                        tree: replacement_tree
-                    };
+        assert_eq!(CategorySet::from(0b1001), vec![0, 3].into_iter().collect());
+        assert_eq!(CategorySet::from(0), CategorySet::new());
+        assert!(CategorySet::new().is_empty());
+        assert_eq!(CategorySet::new().max_value(), None);
+    }
 
-                    alternative_tree.tree.split_and_continue(
-                        alternative_target_robustness,
-                        copy_of_samples.as_mut_slice(),
-                        dataset,
-                        current_id,
-                        &mut constant_attribute_indexes.clone(),
-                        alternative_candidate_split,
-                        alternative_split_stats
-                    );
+    #[test]
+    fn forest_learns_high_cardinality_attributes() {
+        let (dataset, samples) = synthetic_high_cardinality_samples(3000);
+        let (training_samples, test_samples) = samples.split_at(2500);
 
-                    alternative_trees.push(alternative_tree);
-                }
+        let mut trees = ExtremelyRandomizedTrees::fit(&dataset, training_samples.to_vec(), 42, 10, 2, 5);
 
-                // TODO alternative_trees could be a heap, but it probably does not matter
-                // Make sure the split with the highest score is in the first position
-                alternative_trees.sort_by(|tree_a, tree_b| {
-                    tree_b.split_stats.score.cmp(&tree_a.split_stats.score)
-                });
+        let accuracy_of = |trees: &ExtremelyRandomizedTrees| -> f64 {
+            let num_correct = test_samples.iter()
+                .filter(|sample| trees.predict(*sample) == (sample.label == 1))
+                .count();
+            num_correct as f64 / test_samples.len() as f64
+        };
 
-                self.alternative_subtrees.insert(current_id, alternative_trees);
+        assert!(accuracy_of(&trees) > 0.8);
 
-            } else {
+        let uses_wide_values = trees.trees.iter()
+            .flat_map(|tree| tree.tree_elements.iter())
+            .any(|element| match element {
+                TreeElement::Node { split: Split::Categorical { attribute_index: _, subset, missing_left: _ }, .. } =>
+                    subset.max_value().unwrap_or(0) >= 64,
+                TreeElement::Node { split: Split::Numerical { attribute_index: _, cut_off, missing_left: _ }, .. } => *cut_off > 255,
+                _ => false,
+            });
+        assert!(uses_wide_values);
 
-                self.num_robust_nodes += 1;
-                //println!("\tRobust,{},{},{},{}", target_robustness, samples.len(), current_id, self.index);
+        for sample in training_samples.iter().take(20) {
+            assert_eq!(trees.forget(sample), Ok(()));
+        }
 
-                self.split_and_continue(
-                    target_robustness,
-                    samples,
-                    dataset,
-                    current_id,
-                    constant_attribute_indexes,
-                    best_split_candidate,
-                    best_split_stats
-                );
-            }
+        assert!(trees.trees.iter().all(|tree| tree.validate().is_ok()));
+
+        let restored = ExtremelyRandomizedTrees::from_bytes(&trees.to_bytes()).unwrap();
+        for sample in test_samples {
+            assert_eq!(trees.predict_proba(sample), restored.predict_proba(sample));
         }
     }
 
-    fn split_and_continue<D: Dataset, S: Sample>(
-        &mut self,
-        target_robustness: usize,
-        samples: &mut [S],
-        dataset: &D,
-        current_id: u64,
-        constant_attribute_indexes: &mut Cow<[u8]>,
-        best_split: &Split,
-        best_split_stats: &SplitStats
-    ) {
+    #[test]
+    fn forest_learns_directions_for_missing_values() {
+        let (dataset, samples) = synthetic_samples_with_missing_values(2000);
+        let (training_samples, test_samples) = samples.split_at(1500);
+
+        let mut trees = ExtremelyRandomizedTrees::fit(&dataset, training_samples.to_vec(), 42, 10, 2, 5);
 
-        let (samples_left, constant_on_the_left, samples_right, constant_on_the_right) =
-            split(samples, best_split);
+        let accuracy_of = |trees: &ExtremelyRandomizedTrees| -> f64 {
+            let num_correct = test_samples.iter()
+                .filter(|sample| trees.predict(*sample) == (sample.label == 1))
+                .count();
+            num_correct as f64 / test_samples.len() as f64
+        };
 
-        let node = Tree::node(best_split.clone());
+        assert!(accuracy_of(&trees) > 0.8);
 
-        self.tree_elements.insert(current_id, node);
+        // Missing values of the first attribute indicate the negative label, which is on the left
+        let sends_missing_left = trees.trees.iter()
+            .flat_map(|tree| tree.tree_elements.iter())
+            .any(|element| match element {
+                TreeElement::Node { split, .. } => split.attribute_index() == 0 && split.missing_left(),
+                _ => false,
+            });
+        assert!(sends_missing_left);
 
-        let left_child_id = current_id * 2;
+        let incomplete_samples: Vec<&RowSample> = training_samples.iter()
+            .filter(|sample| sample.is_missing(0) || sample.is_missing(2))
+            .take(20)
+            .collect();
 
-        let label_constant_on_the_left =
-            best_split_stats.num_minus_left == 0 || best_split_stats.num_plus_left == 0;
+        for sample in &incomplete_samples {
+            assert_eq!(trees.forget(*sample), Ok(()));
+        }
 
-        if samples_left.len() <= self.min_leaf_size || label_constant_on_the_left {
-            //println!("Building leaf for {} records", record_ids_left.len());
+        assert!(trees.trees.iter().all(|tree| tree.validate().is_ok()));
+        assert!(accuracy_of(&trees) > 0.8);
 
-            let leaf = Tree::leaf(
-                best_split_stats.num_plus_left + best_split_stats.num_minus_left,
-                best_split_stats.num_plus_left
-            );
+        let completely_missing = RowSample { record_id: 5000, values: vec![MISSING; 3], label: 0, target: 0.0, weight: 1 };
+        let restored = ExtremelyRandomizedTrees::from_bytes(&trees.to_bytes()).unwrap();
+        assert_eq!(trees.predict_proba(&completely_missing), restored.predict_proba(&completely_missing));
+    }
 
-            self.tree_elements.insert(left_child_id, leaf);
+    #[test]
+    fn flipped_missing_directions_are_candidates() {
+        let (dataset, samples) = synthetic_samples_with_missing_values(500);
+        let columns = ColumnarSamples::from_samples(&dataset, &samples);
+        let rows = columns.all_rows();
 
-        } else {
+        let impurity_before = SplitCriterion::Gini.impurity(&columns.label_stats(&rows));
+        let mut candidate_splits = vec![Split::new_numerical(0, 7), Split::new_numerical(1, 3)];
 
-            let mut constant_attribute_indexes_left = constant_attribute_indexes.clone();
-            if constant_on_the_left {
-                //println!("Constant attribute found in {} records", record_ids_left.len());
-                let attribute_index = best_split.attribute_index();
-                constant_attribute_indexes_left.to_mut().push(attribute_index);
-            }
+        let split_stats = compute_split_stats(
+            impurity_before,
+            &columns,
+            &rows,
+            &dataset,
+            &mut candidate_splits,
+            &mut Histograms::new(dataset.num_attributes()),
+            SplitCriterion::Gini,
+            ScanKernel::detect()
+        );
 
-            self.determine_split(
-                best_split_stats.impurity_left,
-                target_robustness,
-                samples_left,
-                dataset,
-                left_child_id,
-                0,
-                &mut constant_attribute_indexes_left
-            );
-        }
+        // The second attribute has no missing values, so it has no flipped variant
+        assert_eq!(candidate_splits.len(), 3);
+        assert_eq!(split_stats.len(), 3);
+        assert_eq!(candidate_splits[2], candidate_splits[0].with_missing_left(true));
+        assert_ne!(split_stats[0].score, split_stats[2].score);
 
-        let right_child_id = (current_id * 2) + 1;
+        // Rows without missing values have the same stats in both directions
+        let complete_rows: Vec<u32> = rows.iter().copied().filter(|row| !columns.row(*row).is_missing(0)).collect();
+        let mut candidate_splits = vec![Split::new_numerical(0, 7)];
 
-        let label_constant_on_the_right =
-            best_split_stats.num_minus_right == 0 || best_split_stats.num_plus_right == 0;
+        compute_split_stats(
+            impurity_before,
+            &columns,
+            &complete_rows,
+            &dataset,
+            &mut candidate_splits,
+            &mut Histograms::new(dataset.num_attributes()),
+            SplitCriterion::Gini,
+            ScanKernel::detect()
+        );
 
-        if samples_right.len() <= self.min_leaf_size || label_constant_on_the_right {
-            //println!("Building leaf for {} records", record_ids_right.len());
+        assert_eq!(candidate_splits.len(), 1);
+    }
 
-            let leaf = Tree::leaf(
-                best_split_stats.num_plus_right + best_split_stats.num_minus_right,
-                best_split_stats.num_plus_right
-            );
+    // Leaves of non-robust positions are in the current best alternative subtree
+    fn total_weight_of(tree: &Tree) -> u32 {
+        let weight_in_leaves: u32 = tree.tree_elements.iter()
+            .map(|element| match element {
+                TreeElement::Leaf { label_stats, .. } => label_stats.total(),
+                _ => 0,
+            })
+            .sum();
 
-            self.tree_elements.insert(right_child_id, leaf);
+        weight_in_leaves + tree.current_subtrees().map(total_weight_of).sum::<u32>()
+    }
 
-        } else {
+    #[test]
+    fn weighted_samples_are_counted_and_forgotten_with_their_weight() {
+        let (dataset, samples) = synthetic_weighted_samples(2000);
+        let (training_samples, test_samples) = samples.split_at(1500);
 
-            let mut constant_attribute_indexes_right = constant_attribute_indexes.clone();
-            if constant_on_the_right {
-                //println!("Constant attribute found in {} records", record_ids_right.len());
-                let attribute_index = best_split.attribute_index();
-                constant_attribute_indexes_right.to_mut().push(attribute_index);
-            }
+        let mut trees = ExtremelyRandomizedTrees::fit(&dataset, training_samples.to_vec(), 42, 10, 2, 5);
 
-            self.determine_split(
-                best_split_stats.impurity_right,
-                target_robustness,
-                samples_right,
-                dataset,
-                right_child_id,
-                0,
-                &mut constant_attribute_indexes_right
-            );
+        let total_weight: u32 = training_samples.iter().map(|sample| sample.weight()).sum();
+        assert!(trees.trees.iter().all(|tree| total_weight_of(tree) == total_weight));
+
+        let num_correct = test_samples.iter()
+            .filter(|sample| trees.predict(*sample) == (sample.label == 1))
+            .count();
+        assert!(num_correct as f64 / test_samples.len() as f64 > 0.8);
+
+        let heavy_samples: Vec<&RowSample> = training_samples.iter()
+            .filter(|sample| sample.weight() > 1)
+            .take(10)
+            .collect();
+
+        for sample in &heavy_samples {
+            assert_eq!(trees.forget(*sample), Ok(()));
         }
+
+        assert!(trees.trees.iter().all(|tree| tree.validate().is_ok()));
+        assert!(trees.trees.iter().all(|tree| total_weight_of(tree) == total_weight - 30));
     }
-}
 
-fn compute_split_stats<S: Sample, D: Dataset>(
-    impurity_before: f64,
-    samples: &[S],
-    dataset: &D,
-    candidate_splits: &Vec<Split>,
-) -> Vec<SplitStats> {
+    #[test]
+    fn class_weights_and_threshold_favor_the_minority_class() {
+        let (dataset, samples) = synthetic_samples(6000);
+        let imbalanced_samples: Vec<RowSample> = samples.into_iter()
+            .filter(|sample| sample.label == 0 || sample.record_id % 10 == 0)
+            .collect();
+        let (training_samples, test_samples) = imbalanced_samples.split_at(imbalanced_samples.len() * 3 / 4);
 
-    let mut all_stats: Vec<SplitStats> = Vec::with_capacity(candidate_splits.len());
-    
-    for candidate in candidate_splits {
+        let mut trees = ExtremelyRandomizedTrees::fit(&dataset, training_samples.to_vec(), 42, 10, 2, 5);
 
-        let mut stats = match candidate {
-            Split::Numerical { attribute_index: _, cut_off: _ } => {
-                scan_simd_numerical(samples, candidate)
-            },
-            Split::Categorical { attribute_index, subset: _ } => {
-                // TODO we also need a SIMD version here
-                let (_, range) = dataset.attribute_range(*attribute_index);
-                if range <= 16 {
-                    scan_simd_categorical(samples, candidate)
-                } else {
-                    scan(samples, &candidate)
-                }
-            },
+        let num_predicted_plus = |trees: &ExtremelyRandomizedTrees| -> usize {
+            let predictions = trees.predict_batch(test_samples);
+            for (sample, prediction) in test_samples.iter().zip(predictions.iter()) {
+                assert_eq!(trees.predict(sample), *prediction);
+            }
+            predictions.into_iter().filter(|prediction| *prediction).count()
         };
 
-        stats.update_score(impurity_before);
-        all_stats.push(stats);
-    }
+        let uniform = num_predicted_plus(&trees);
 
-    all_stats
-}
+        assert_eq!(trees.set_class_weights(ClassWeights::Balanced), Ok(()));
+        let balanced = num_predicted_plus(&trees);
+        assert!(balanced > uniform);
 
-fn split<'a, S: Sample>(
-    samples: &'a mut [S],
-    split: &Split
-) -> (&'a mut [S], bool, &'a mut [S], bool) {
+        trees.set_decision_threshold(0.2);
+        let balanced_and_eager = num_predicted_plus(&trees);
+        assert!(balanced_and_eager >= balanced);
 
-    let mut cursor = 0;
-    let mut cursor_end = samples.len();
+        for sample in test_samples.iter().take(50) {
+            let (num_plus, num_minus) = trees.predict_votes(sample);
+            assert_eq!(trees.predict(sample), num_plus * 5 > num_plus + num_minus);
+        }
 
-    let mut constant_on_the_left = true;
-    let mut first_value_on_the_left: Option<u8> = None;
-    let mut constant_on_the_right = true;
-    let mut first_value_on_the_right: Option<u8> = None;
+        // Probabilities and votes use the same weights
+        for sample in test_samples.iter().take(50) {
+            let probabilities = trees.predict_class_proba(sample);
+            assert!((probabilities[1] - trees.predict_proba(sample)).abs() < 1e-9);
+        }
 
-    loop {
-        // TODO Maybe remove boundary checks here later
+        let restored = ExtremelyRandomizedTrees::from_bytes(&trees.to_bytes()).unwrap();
+        assert_eq!(restored.class_weights(), &ClassWeights::Balanced);
+        assert_eq!(restored.predict_batch(test_samples), trees.predict_batch(test_samples));
 
-        let sample = samples.get(cursor).unwrap();
-        let attribute_value: u8 = sample.attribute_value(split.attribute_index());
+        // Balanced weights follow the class frequencies of the remaining samples
+        let weights_before = trees.leaf_weights().class_weights;
+        for sample in training_samples.iter().filter(|sample| sample.label == 0).take(30) {
+            assert_eq!(trees.forget(sample), Ok(()));
+        }
+        let weights_after = trees.leaf_weights().class_weights;
+        assert!(weights_after[0] > weights_before[0]);
+        assert!(weights_after[1] < weights_before[1]);
 
-        if sample.is_left_of(&split) {
+        assert_eq!(trees.set_class_weights(ClassWeights::Custom(vec![1.0, 5.0])), Ok(()));
+        assert_eq!(trees.leaf_weights().class_weights[..2], [1.0, 5.0]);
+    }
 
-            if constant_on_the_left {
-                if first_value_on_the_left.is_none() {
-                    first_value_on_the_left = Some(attribute_value);
-                } else if attribute_value != first_value_on_the_left.unwrap() {
-                    constant_on_the_left = false;
-                }
-            }
+    // Deepest element of the tree and its alternatives, which start at the depth of their position
+    fn depth_below(tree: &Tree, node_id: NodeId, depth: usize) -> usize {
+        match &tree.tree_elements[node_id as usize] {
+            TreeElement::Node { split: _, left_child, right_child, budget: _ } =>
+                depth_below(tree, *left_child, depth + 1).max(depth_below(tree, *right_child, depth + 1)),
+            TreeElement::Leaf { .. } => depth,
+            TreeElement::Alternatives { alternatives, budget: _ } => tree.alternative_subtrees[*alternatives as usize]
+                .iter()
+                .map(|alternative_tree| depth_below(&alternative_tree.tree, ROOT, depth))
+                .max()
+                .unwrap(),
+        }
+    }
 
-            cursor += 1;
-        } else {
+    fn all_alternatives(tree: &Tree) -> Vec<&Vec<AlternativeTree>> {
+        tree.alternative_subtrees.iter()
+            .flat_map(|alternative_trees| {
+                let nested = alternative_trees.iter().flat_map(|alternative_tree| all_alternatives(&alternative_tree.tree));
+                std::iter::once(alternative_trees).chain(nested).collect::<Vec<_>>()
+            })
+            .collect()
+    }
 
-            if constant_on_the_right {
-                if first_value_on_the_right.is_none() {
-                    first_value_on_the_right = Some(attribute_value);
-                } else if attribute_value != first_value_on_the_right.unwrap() {
-                    constant_on_the_right = false;
+    #[test]
+    fn forest_follows_its_config() {
+        let (dataset, samples) = synthetic_samples(2000);
+
+        let trees = ExtremelyRandomizedTrees::fit(&dataset, samples.clone(), 42, 5, 2, 5);
+        let configured_trees = ExtremelyRandomizedTrees::fit_with_config(
+            &dataset,
+            samples.clone(),
+            ForestConfig::builder().seed(42).num_trees(5).num_threads(2).build().unwrap()
+        ).unwrap();
+        assert_eq!(trees.predict_proba_batch(&samples), configured_trees.predict_proba_batch(&samples));
+
+        let invalid_config = ForestConfig { num_trees: 0, ..ForestConfig::default() };
+        let invalid_trees = ExtremelyRandomizedTrees::fit_with_config(&dataset, samples.clone(), invalid_config);
+        assert_eq!(invalid_trees.err(), Some(FitError::InvalidConfig(ConfigError::NoTrees)));
+
+        let config = ForestConfig::builder()
+            .seed(42)
+            .num_trees(5)
+            .max_depth(4)
+            .min_samples_split(50)
+            .max_features(MaxFeatures::All)
+            .target_robustness(20)
+            .max_alternatives(2)
+            .build()
+            .unwrap();
+
+        let mut trees = ExtremelyRandomizedTrees::fit_with_config(&dataset, samples.clone(), config).unwrap();
+        assert_eq!(trees.config(), &config);
+
+        for tree in &trees.trees {
+            assert_eq!(tree.num_attributes_to_try_per_split, 3);
+            assert!(depth_below(tree, ROOT, 0) <= 4);
+            assert!(all_alternatives(tree).iter().all(|alternative_trees| alternative_trees.len() <= 2));
+            assert!(tree.budget(ROOT).unwrap().target_robustness <= 20);
+        }
+
+        for sample in samples.iter().take(3) {
+            assert_eq!(trees.forget(sample), Ok(()));
+        }
+
+        let restored = ExtremelyRandomizedTrees::from_bytes(&trees.to_bytes()).unwrap();
+        assert_eq!(restored.config(), &config);
+    }
+
+    // Checks the stopping rules at every node of the current trees, or of all alternative trees,
+    // and returns the label stats of the leaves below the element
+    fn assert_stopping_rules(
+        tree: &Tree,
+        node_id: NodeId,
+        depth: usize,
+        config: &ForestConfig,
+        all_alternative_trees: bool
+    ) -> LabelStats {
+        match &tree.tree_elements[node_id as usize] {
+            TreeElement::Node { split: _, left_child, right_child, budget: _ } => {
+                assert!(depth < config.max_depth.unwrap());
+
+                let left = assert_stopping_rules(tree, *left_child, depth + 1, config, all_alternative_trees);
+                let right = assert_stopping_rules(tree, *right_child, depth + 1, config, all_alternative_trees);
+
+                let mut stats = SplitStats::from_label_stats(left, right);
+                stats.update_score_and_impurity_before(config.split_criterion);
+
+                assert!((left.total() + right.total()) as usize >= config.min_samples_split);
+                assert!(stats.score.unwrap() >= to_score(config.min_impurity_decrease));
+
+                left.merged(&right)
+            }
+            TreeElement::Leaf { label_stats, .. } => label_stats.label_stats(),
+            TreeElement::Alternatives { alternatives, budget: _ } => {
+                let alternative_trees = &tree.alternative_subtrees[*alternatives as usize];
+                let num_checked = if all_alternative_trees { alternative_trees.len() } else { 1 };
+
+                let mut label_stats = None;
+                for alternative_tree in alternative_trees.iter().take(num_checked) {
+                    label_stats = Some(
+                        assert_stopping_rules(&alternative_tree.tree, ROOT, depth, config, all_alternative_trees)
+                    );
                 }
+                label_stats.unwrap()
             }
+        }
+    }
 
-            cursor_end -= 1;
-            //println!("Swapping {} and {} with record {}({}), cursor_end is now {}",
-            // cursor, cursor_end, record_id, value, cursor_end);
-            samples.swap(cursor, cursor_end);
+    #[test]
+    fn stopping_rules_hold_in_alternatives_and_after_forgetting() {
+        let (dataset, samples) = synthetic_samples(2000);
+
+        let config = ForestConfig::builder()
+            .seed(4)
+            .num_trees(5)
+            .max_depth(6)
+            .min_samples_split(60)
+            .min_impurity_decrease(0.01)
+            .target_robustness(10)
+            .build()
+            .unwrap();
+
+        let mut trees = ExtremelyRandomizedTrees::fit_with_config(&dataset, samples.clone(), config).unwrap();
+
+        assert!(trees.trees.iter().any(|tree| !tree.alternative_subtrees.is_empty()));
+
+        for tree in &trees.trees {
+            assert_stopping_rules(tree, ROOT, 0, &config, true);
         }
 
-        if cursor == cursor_end {
-            break;
+        for (num_forgotten, sample) in samples.iter().enumerate().take(40) {
+            assert_eq!(trees.forget(sample), Ok(()));
+            trees.retrain_exhausted(&dataset, &samples[num_forgotten + 1..]).unwrap();
+
+            for tree in &trees.trees {
+                assert_stopping_rules(tree, ROOT, 0, &config, false);
+            }
         }
     }
 
-    let (samples_left, samples_right) = samples.split_at_mut(cursor);
+    #[test]
+    fn splits_without_the_minimum_score_limit_the_budgets() {
+        let (dataset, samples) = synthetic_samples(2000);
+
+        let config = ForestConfig::builder()
+            .seed(42)
+            .num_trees(5)
+            .min_impurity_decrease(0.02)
+            .target_robustness(10)
+            .build()
+            .unwrap();
+
+        let trees = ExtremelyRandomizedTrees::fit_with_config(&dataset, samples, config).unwrap();
+
+        let elements = || trees.trees.iter()
+            .flat_map(|tree| tree.tree_elements.iter().map(move |element| (tree, element)));
+
+        // Some impure leaves were made because no split had the minimum score
+        assert!(elements().any(|(_, element)| matches!(element,
+            TreeElement::Leaf { label_stats, budget } if !label_stats.label_stats().is_pure() && budget.max_updates < 10)));
+
+        // Without a limit on the alternatives, only splits without the minimum score which can
+        // overtake the best split leave it as the single alternative with a lower budget
+        let single_alternatives: Vec<&Budget> = elements()
+            .filter_map(|(tree, element)| match element {
+                TreeElement::Alternatives { alternatives, budget }
+                    if tree.alternative_subtrees[*alternatives as usize].len() == 1 => Some(budget),
+                _ => None,
+            })
+            .collect();
 
-    (samples_left, constant_on_the_left, samples_right, constant_on_the_right)
-}
+        assert!(!single_alternatives.is_empty());
+        assert!(single_alternatives.iter().all(|budget| budget.max_updates < 10));
+    }
 
-fn generate_random_split<D: Dataset>(
-    rng: &mut XorShiftRng,
-    dataset: &D,
-    attribute_index: u8
-) -> Split {
-    match dataset.attribute_type(attribute_index) {
-        AttributeType::Numerical => {
-            let (min_value, max_value) = dataset.attribute_range(attribute_index);
+    #[test]
+    fn bagged_trees_forget_records_with_their_multiplicity() {
+        let (dataset, samples) = synthetic_weighted_samples(2000);
+        let (training_samples, test_samples) = samples.split_at(1500);
 
-            let random_cut_off = rng.gen_range(min_value, max_value + 1);
+        let config = ForestConfig::builder()
+            .seed(42)
+            .num_trees(10)
+            .bootstrap(true)
+            .build()
+            .unwrap();
 
-            Split::new_numerical(attribute_index, random_cut_off)
-        },
-        AttributeType::Categorical => {
-            let (_, cardinality) = dataset.attribute_range(attribute_index);
+        let mut trees = ExtremelyRandomizedTrees::fit_with_config(&dataset, training_samples.to_vec(), config).unwrap();
 
-            let how_many = rng.gen_range(0, cardinality + 1);
-            // TODO lets get rid of the allocation here...
-            let mut possible_values: Vec<u8> = (0..(cardinality + 1)).collect();
-            possible_values.shuffle(rng);
+        let bagged_weight = |tree: &Tree, samples: &[RowSample]| -> u32 {
+            samples.iter()
+                .map(|sample| sample.weight() * multiplicity(42, tree.index as u64, sample.record_id()))
+                .sum()
+        };
 
-            let mut subset: u64 = 0;
-            for bit_to_set in possible_values.iter().take(how_many as usize) {
-                subset |= 1_u64 << *bit_to_set as u64
-            }
+        for tree in &trees.trees {
+            assert_eq!(total_weight_of(tree), bagged_weight(tree, training_samples));
+        }
+        // The label statistics of the forest count every training sample once
+        assert_eq!(trees.label_stats, LabelStats::of_samples(training_samples, dataset.task()));
 
-            Split::new_categorical(attribute_index, subset)
+        // The bootstrap samples are derived from the seed
+        let refitted = ExtremelyRandomizedTrees::fit_with_config(&dataset, training_samples.to_vec(), config).unwrap();
+        assert_eq!(trees.predict_proba_batch(test_samples), refitted.predict_proba_batch(test_samples));
+
+        let num_correct = test_samples.iter()
+            .filter(|sample| trees.predict(*sample) == (sample.label == 1))
+            .count();
+        assert!(num_correct as f64 / test_samples.len() as f64 > 0.8);
+
+        let oob_accuracy = trees.oob_accuracy(training_samples).unwrap();
+        assert!(oob_accuracy > 0.8 && oob_accuracy <= 1.0);
+
+        // The out-of-bag votes are decided like the predictions
+        trees.set_decision_threshold(0.9);
+        assert_ne!(trees.oob_accuracy(training_samples), Some(oob_accuracy));
+        trees.set_decision_threshold(0.5);
+
+        assert_eq!(trees.oob_accuracy(test_samples), None);
+
+        // Records are missing from some trees and drawn repeatedly into others
+        let (forgotten, remaining) = training_samples.split_at(40);
+        assert!(forgotten.iter().any(|sample| multiplicity(42, 0, sample.record_id()) == 0));
+        assert!(forgotten.iter().any(|sample| multiplicity(42, 0, sample.record_id()) > 1));
+
+        for sample in forgotten {
+            assert_eq!(trees.forget(sample), Ok(()));
+        }
+
+        for tree in &trees.trees {
+            assert!(tree.validate().is_ok());
+            assert_eq!(total_weight_of(tree), bagged_weight(tree, remaining));
+        }
+
+        trees.retrain_exhausted(&dataset, remaining).unwrap();
+
+        for tree in &trees.trees {
+            assert!(!tree.is_budget_exceeded());
+            assert_eq!(total_weight_of(tree), bagged_weight(tree, remaining));
         }
+
+        assert!(trees.oob_accuracy(remaining).is_some());
+        assert_eq!(trees.oob_accuracy(forgotten), None);
     }
-}
\ No newline at end of file
+}
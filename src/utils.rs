@@ -1,3 +1,5 @@
+use crate::dataset::Sample;
+
 
 pub fn as_bytes(seed: u64, tree_index: u64) -> [u8; 16] {
 
@@ -25,3 +27,23 @@ pub fn as_bytes(seed: u64, tree_index: u64) -> [u8; 16] {
         *tree_index_bytes.get(7).unwrap(),
     ]
 }
+
+// Finalizer of SplitMix64, which spreads nearby seeds, tree indexes and record ids over all bits
+pub fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Hash of everything a sample contributes to the trees, so that a sample which only shares the
+// record id with a training sample can be told apart from it
+pub fn fingerprint<S: Sample>(sample: &S, num_attributes: u16) -> u64 {
+    let mut hash = mix(sample.record_id());
+    for attribute_index in 0..num_attributes {
+        hash = mix(hash ^ sample.attribute_value(attribute_index) as u64);
+    }
+    hash = mix(hash ^ sample.class_label() as u64);
+    hash = mix(hash ^ sample.target().to_bits());
+    mix(hash ^ sample.weight() as u64)
+}